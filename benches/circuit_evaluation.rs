@@ -2,6 +2,7 @@ use glam::Vec2;
 use logic_sim::logic::{
     circuit::{embedded::EmbeddedCircuit, Circuit},
     gate::Gate,
+    SolverMode,
 };
 
 use criterion::{criterion_group, criterion_main, Criterion};
@@ -12,7 +13,7 @@ pub fn benchmark(c: &mut Criterion) {
 
     let mut circuit = Circuit::default();
     circuit.add_gate(
-        Gate::Embedded(EmbeddedCircuit::new(Circuit::full_adder()).unwrap()),
+        Gate::Embedded(EmbeddedCircuit::new(Circuit::full_adder())),
         Vec2::ZERO,
     );
 
@@ -20,6 +21,14 @@ pub fn benchmark(c: &mut Criterion) {
 
     let mut circuit = Circuit::adder_8_bit();
     c.bench_function("8 bit Adder Circuit", |b| b.iter(|| circuit.step()));
+
+    let mut circuit = Circuit::extreme_test_circuit();
+    c.bench_function("10k Gate Circuit Sweep", |b| b.iter(|| circuit.step()));
+
+    let mut circuit = Circuit::extreme_test_circuit().with_solver_mode(SolverMode::EventDriven);
+    c.bench_function("10k Gate Circuit Event Driven", |b| {
+        b.iter(|| circuit.step())
+    });
 }
 
 criterion_group!(benches, benchmark);
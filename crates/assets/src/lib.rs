@@ -51,6 +51,35 @@ pub mod svg {
         asset!(OFF_ACTIVE: "objects/gates/off.svg", (stroke = "4"));
         asset!(OFF_NORMAL: "objects/gates/off.svg", (stroke = "0"));
 
+        asset!(MERGE_ACTIVE: "objects/gates/merge.svg", (stroke = "4"));
+        asset!(MERGE_NORMAL: "objects/gates/merge.svg", (stroke = "0"));
+
+        asset!(SPLIT_ACTIVE: "objects/gates/split.svg", (stroke = "4"));
+        asset!(SPLIT_NORMAL: "objects/gates/split.svg", (stroke = "0"));
+
+        asset!(SR_LATCH_ACTIVE: "objects/gates/sr_latch.svg", (stroke = "4"));
+        asset!(SR_LATCH_NORMAL: "objects/gates/sr_latch.svg", (stroke = "0"));
+
+        asset!(D_LATCH_ACTIVE: "objects/gates/d_latch.svg", (stroke = "4"));
+        asset!(D_LATCH_NORMAL: "objects/gates/d_latch.svg", (stroke = "0"));
+
+        asset!(D_FLIP_FLOP_ACTIVE: "objects/gates/d_flip_flop.svg", (stroke = "4"));
+        asset!(D_FLIP_FLOP_NORMAL: "objects/gates/d_flip_flop.svg", (stroke = "0"));
+
+        asset!(JK_FLIP_FLOP_ACTIVE: "objects/gates/jk_flip_flop.svg", (stroke = "4"));
+        asset!(JK_FLIP_FLOP_NORMAL: "objects/gates/jk_flip_flop.svg", (stroke = "0"));
+
+        asset!(REGISTER_ACTIVE: "objects/gates/register.svg", (stroke = "4"));
+        asset!(REGISTER_NORMAL: "objects/gates/register.svg", (stroke = "0"));
+
+        asset!(TRI_STATE_ACTIVE: "objects/gates/tri_state.svg", (stroke = "4"));
+        asset!(TRI_STATE_NORMAL: "objects/gates/tri_state.svg", (stroke = "0"));
+
+        asset!(CLOCK_HIGH_ACTIVE: "objects/gates/clock.svg", (stroke = "4", level = "white"));
+        asset!(CLOCK_HIGH_NORMAL: "objects/gates/clock.svg", (stroke = "0", level = "white"));
+        asset!(CLOCK_LOW_ACTIVE: "objects/gates/clock.svg", (stroke = "4", level = "none"));
+        asset!(CLOCK_LOW_NORMAL: "objects/gates/clock.svg", (stroke = "0", level = "none"));
+
         asset!(INPUT: "objects/gates/input.svg", ());
         asset!(OUTPUT: "objects/gates/output.svg", ());
     }
@@ -0,0 +1,196 @@
+use std::{io::Read, path::PathBuf, process::ExitCode};
+
+use logic_sim::logic::{
+    circuit::Circuit,
+    vectors::{parse_assignments, DEFAULT_SETTLE_STEPS},
+    SolverMode,
+};
+
+const USAGE: &str = "Usage: logic_sim_headless <circuit.json> [name=value]... [options]
+
+Drives Input gates by label, simulates the circuit and prints its Output gates.
+
+Options:
+  --stdin             Also read name=value assignments from stdin
+  --steps <n>         Step a fixed number of times instead of settling
+  --settle <max>      Settle within at most this many steps (default 1000)
+  --mode <mode>       sweep, event, four-valued or timing
+                      Bits which are X or Z in four-valued mode print as 0
+  --hierarchical      Simulate embedded circuits without flattening them
+  --format <format>   text (default), json or vcd, which records every signal";
+
+enum Run {
+    Steps(usize),
+    Settle(usize),
+}
+
+#[derive(PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Vcd,
+}
+
+struct Options {
+    circuit: PathBuf,
+    assignments: Vec<(String, u64)>,
+    run: Run,
+    mode: Option<SolverMode>,
+    hierarchical: bool,
+    format: Format,
+}
+
+pub fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        // Asking for help isn't an error
+        Err(err) if err.is_empty() => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut circuit = match Circuit::load(&options.circuit) {
+        Ok(circuit) => circuit,
+        Err(err) => {
+            eprintln!(
+                "Failed to load circuit from {}: {err}",
+                options.circuit.display()
+            );
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(mode) = options.mode {
+        circuit.set_solver_mode(mode);
+    }
+    circuit.set_hierarchical(options.hierarchical);
+    circuit.right_size_solver();
+
+    for (name, value) in &options.assignments {
+        if !circuit.set_named_input(name, *value) {
+            eprintln!("The circuit has no input named {name}");
+            return ExitCode::from(2);
+        }
+    }
+
+    if options.format == Format::Vcd {
+        circuit.start_recording(circuit.probe_all());
+    }
+    let steps = match options.run {
+        Run::Steps(steps) => {
+            circuit.step_n(steps);
+            steps
+        }
+        Run::Settle(max_steps) => match circuit.settle(max_steps) {
+            Ok(steps) => steps,
+            Err(err) => {
+                eprintln!("The circuit didn't settle: {err:?}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    match options.format {
+        Format::Text => {
+            for (name, value) in circuit.named_outputs() {
+                println!("{name}={value}");
+            }
+        }
+        Format::Json => {
+            let outputs: Vec<String> = circuit
+                .named_outputs()
+                .into_iter()
+                .map(|(name, value)| format!("{}:{value}", miniserde::json::to_string(&name)))
+                .collect();
+            println!(
+                "{{\"steps\":{steps},\"outputs\":{{{}}}}}",
+                outputs.join(",")
+            );
+        }
+        Format::Vcd => {
+            let trace = circuit.stop_recording().unwrap();
+            let module = options
+                .circuit
+                .file_stem()
+                .map_or("circuit".into(), |stem| stem.to_string_lossy());
+            print!("{}", trace.to_vcd(&module));
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut circuit = None;
+    let mut assignments = vec![];
+    let mut stdin = false;
+    let mut run = Run::Settle(DEFAULT_SETTLE_STEPS);
+    let mut mode = None;
+    let mut hierarchical = false;
+    let mut format = Format::Text;
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| args.next().ok_or_else(|| format!("{option} needs a value"));
+        let count = |option: &str, value: String| {
+            value
+                .parse()
+                .map_err(|_| format!("{option} needs a number, found {value}"))
+        };
+        match arg.as_str() {
+            "--stdin" => stdin = true,
+            "--steps" => run = Run::Steps(count(&arg, value(&arg)?)?),
+            "--settle" => run = Run::Settle(count(&arg, value(&arg)?)?),
+            "--hierarchical" => hierarchical = true,
+            "--mode" => {
+                mode = Some(match value(&arg)?.as_str() {
+                    "sweep" => SolverMode::Sweep,
+                    "event" => SolverMode::EventDriven,
+                    "four-valued" => SolverMode::FourValued,
+                    "timing" => SolverMode::Timing,
+                    other => return Err(format!("Unknown mode {other}")),
+                })
+            }
+            "--format" => {
+                format = match value(&arg)?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "vcd" => Format::Vcd,
+                    other => return Err(format!("Unknown format {other}")),
+                }
+            }
+            "--help" | "-h" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {arg}")),
+            _ if circuit.is_none() => circuit = Some(PathBuf::from(arg)),
+            _ => assignments.extend(
+                parse_assignments([arg.as_str()].into_iter(), 0)
+                    .map_err(|_| format!("Expected name=value, found {arg}"))?,
+            ),
+        }
+    }
+
+    if stdin {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|err| format!("Failed to read stdin: {err}"))?;
+        for (index, line) in source.lines().enumerate() {
+            let words = line.split('#').next().unwrap().split_whitespace();
+            assignments.extend(
+                parse_assignments(words, index + 1)
+                    .map_err(|err| format!("Invalid assignment on stdin: {err:?}"))?,
+            );
+        }
+    }
+
+    Ok(Options {
+        circuit: circuit.ok_or("No circuit file given")?,
+        assignments,
+        run,
+        mode,
+        hierarchical,
+        format,
+    })
+}
@@ -0,0 +1,62 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use logic_sim::logic::{circuit::Circuit, vectors::TestVectors};
+
+// Runs test vector files against a saved circuit, exiting with 1 if any vector fails and 2 if
+// the files can't be run at all
+pub fn main() -> ExitCode {
+    let args: Vec<PathBuf> = std::env::args_os().skip(1).map(PathBuf::from).collect();
+    let (circuit_path, vector_paths) = match args.as_slice() {
+        [circuit, vectors @ ..] if !vectors.is_empty() => (circuit, vectors),
+        _ => {
+            eprintln!("Usage: logic_sim_vectors <circuit.json> <vectors>...");
+            return ExitCode::from(2);
+        }
+    };
+
+    let circuit = match Circuit::load(circuit_path) {
+        Ok(circuit) => circuit,
+        Err(err) => {
+            eprintln!(
+                "Failed to load circuit from {}: {err}",
+                circuit_path.display()
+            );
+            return ExitCode::from(2);
+        }
+    };
+
+    let (mut passed, mut failed) = (0, 0);
+    for path in vector_paths {
+        let results = std::fs::read_to_string(path)
+            .map_err(|err| format!("{err}"))
+            .and_then(|source| TestVectors::parse(&source).map_err(|err| format!("{err:?}")))
+            .and_then(|vectors| vectors.run(&circuit).map_err(|err| format!("{err:?}")));
+        let results = match results {
+            Ok(results) => results,
+            Err(err) => {
+                eprintln!("Failed to run {}: {err}", path.display());
+                return ExitCode::from(2);
+            }
+        };
+
+        for result in results {
+            let status = if result.passed() { "PASS" } else { "FAIL" };
+            println!("{}:{}: {status}", path.display(), result.line);
+            for failure in &result.failures {
+                println!("    {failure}");
+            }
+            if result.passed() {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{passed} passed, {failed} failed");
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -1,17 +1,26 @@
 use std::{
+    fmt,
     hash::{Hash, Hasher},
     marker::PhantomData,
 };
 
 pub struct Handle<T> {
     pub index: usize,
+    // Counts how many times the slot at `index` has been reused, so handles to removed items
+    // don't compare equal to handles to whatever replaced them
+    pub generation: u32,
     pub _marker: PhantomData<T>,
 }
 
 impl<T> Handle<T> {
     pub fn new(index: usize) -> Handle<T> {
+        Self::with_generation(index, 0)
+    }
+
+    pub fn with_generation(index: usize, generation: u32) -> Handle<T> {
         Self {
             index,
+            generation,
             _marker: PhantomData,
         }
     }
@@ -27,12 +36,19 @@ impl<T> Clone for Handle<T> {
 impl<T> Eq for Handle<T> {}
 impl<T> PartialEq for Handle<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.index == other.index
+        self.index == other.index && self.generation == other.generation
     }
 }
 
 impl<T> Hash for Handle<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
     }
 }
@@ -1,3 +1,4 @@
 pub mod bounds;
 pub mod handle;
+pub mod slots;
 pub mod stopwatch;
@@ -0,0 +1,146 @@
+//! Stable handles for the items of a `Vec` which is stored elsewhere.
+//!
+//! Items keep their handle when earlier items are removed, and handles to removed
+//! items stop resolving instead of pointing at whatever took their place. Items
+//! pushed onto the `Vec` directly are given handles in order the first time
+//! they're needed, so only removals have to go through the table.
+
+use std::fmt;
+
+use crate::handle::Handle;
+
+pub struct Slots<T> {
+    slots: Vec<Slot>,
+    // The handle of each position which has been tracked so far
+    handles: Vec<Handle<T>>,
+    // Slots whose items have been removed, to be reused with the next generation
+    free: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    generation: u32,
+    position: Option<usize>,
+}
+
+impl<T> Slots<T> {
+    pub fn handle(&self, position: usize) -> Handle<T> {
+        match self.handles.get(position) {
+            Some(handle) => *handle,
+            // Untracked positions take fresh slots in order once they're tracked
+            None => Handle::new(self.slots.len() + position - self.handles.len()),
+        }
+    }
+
+    // The position of a handle's item in a `Vec` of the given length, or None if it was removed
+    pub fn position(&self, handle: Handle<T>, len: usize) -> Option<usize> {
+        let position = match self.slots.get(handle.index) {
+            Some(slot) if slot.generation == handle.generation => slot.position?,
+            Some(_) => return None,
+            None if handle.generation == 0 => self.handles.len() + handle.index - self.slots.len(),
+            None => return None,
+        };
+        (position < len).then_some(position)
+    }
+
+    pub fn push(&mut self, items: &mut Vec<T>, item: T) -> Handle<T> {
+        let position = items.len();
+        self.insert(items, position, item)
+    }
+
+    // Inserts an item with a new handle, moving the items after it along
+    pub fn insert(&mut self, items: &mut Vec<T>, position: usize, item: T) -> Handle<T> {
+        self.track(items.len());
+        let handle = match self.free.pop() {
+            Some(index) => Handle::with_generation(index, self.slots[index].generation),
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    position: None,
+                });
+                Handle::new(self.slots.len() - 1)
+            }
+        };
+        self.handles.insert(position, handle);
+        for (position, handle) in self.handles.iter().enumerate().skip(position) {
+            self.slots[handle.index].position = Some(position);
+        }
+        items.insert(position, item);
+        handle
+    }
+
+    pub fn remove(&mut self, items: &mut Vec<T>, position: usize) -> T {
+        self.track(items.len());
+        let handle = self.handles.remove(position);
+        self.release(handle);
+        for (position, handle) in self.handles.iter().enumerate().skip(position) {
+            self.slots[handle.index].position = Some(position);
+        }
+        items.remove(position)
+    }
+
+    pub fn retain(&mut self, items: &mut Vec<T>, mut keep: impl FnMut(&T) -> bool) {
+        self.track(items.len());
+        let kept: Vec<bool> = items.iter().map(&mut keep).collect();
+        let mut flags = kept.iter();
+        items.retain(|_| *flags.next().unwrap());
+
+        let handles = std::mem::take(&mut self.handles);
+        for (handle, kept) in handles.into_iter().zip(kept) {
+            if kept {
+                self.slots[handle.index].position = Some(self.handles.len());
+                self.handles.push(handle);
+            } else {
+                self.release(handle);
+            }
+        }
+    }
+
+    // Gives every position of a `Vec` of the given length a handle
+    fn track(&mut self, len: usize) {
+        while self.handles.len() < len {
+            self.handles.push(Handle::new(self.slots.len()));
+            self.slots.push(Slot {
+                generation: 0,
+                position: Some(self.handles.len() - 1),
+            });
+        }
+    }
+
+    fn release(&mut self, handle: Handle<T>) {
+        let slot = &mut self.slots[handle.index];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.position = None;
+        self.free.push(handle.index);
+    }
+}
+
+impl<T> Default for Slots<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            handles: vec![],
+            free: vec![],
+        }
+    }
+}
+
+impl<T> Clone for Slots<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            handles: self.handles.clone(),
+            free: self.free.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Slots<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Slots")
+            .field("slots", &self.slots)
+            .field("handles", &self.handles)
+            .field("free", &self.free)
+            .finish()
+    }
+}
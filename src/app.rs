@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use glam::Vec2;
 use winit::{
     application::ApplicationHandler,
@@ -29,11 +31,11 @@ pub struct App<'a> {
 }
 
 impl<'a> App<'a> {
-    pub async fn create(window: &'a Window) -> Self {
+    pub async fn create(window: &'a Window, circuit_path: Option<PathBuf>) -> Self {
         let render_state = RenderState::create(window).await;
         let input = InputState::default();
 
-        let mut game_state = GameState::new();
+        let mut game_state = GameState::new(circuit_path);
 
         let window_size = window.inner_size();
         let aspect = window_size.width as f32 / window_size.height as f32;
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use winit::{event_loop::EventLoop, window::Window};
 
 use logic_sim::app::App;
@@ -29,7 +31,8 @@ pub fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
-        let mut app = App::create(&window).block_on();
+        let circuit_path = std::env::args().nth(1).map(PathBuf::from);
+        let mut app = App::create(&window, circuit_path).block_on();
         app.run(event_loop)
     }
     #[cfg(target_arch = "wasm32")]
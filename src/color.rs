@@ -8,4 +8,5 @@ pub const RED: Vec4 = rgb(238, 99, 82);
 pub const GREEN: Vec4 = rgb(89, 205, 144);
 pub const BLUE: Vec4 = rgb(63, 167, 214);
 pub const YELLOW: Vec4 = rgb(250, 192, 94);
+pub const PURPLE: Vec4 = rgb(171, 130, 255);
 pub const WHITE: Vec4 = rgb(255, 229, 212);
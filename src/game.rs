@@ -1,6 +1,9 @@
 pub mod game_loop;
 pub mod input;
 mod ui;
+mod waveform;
+use std::path::PathBuf;
+
 use glam::Vec2;
 
 use crate::{
@@ -12,11 +15,14 @@ use crate::{
 };
 
 use common::stopwatch::Stopwatch;
+use waveform::WaveformPanel;
 
 pub struct GameState {
     pub text_object: TextObject,
     pub camera: Camera,
     circuit: EditCircuit,
+    circuit_path: PathBuf,
+    waveform: WaveformPanel,
 
     pub input: GameInput,
 
@@ -31,7 +37,16 @@ pub struct GameInput {
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    pub fn new(circuit_path: Option<PathBuf>) -> Self {
+        let circuit = match &circuit_path {
+            Some(path) if path.exists() => Circuit::load(path).unwrap_or_else(|err| {
+                println!("Failed to load circuit from {}: {err}", path.display());
+                Circuit::default()
+            }),
+            Some(_) => Circuit::default(),
+            None => Circuit::extreme_test_circuit(),
+        };
+
         let text_object = TextObject {
             content: "".to_string(),
             position: Vec2::new(0.0, 0.0),
@@ -42,14 +57,16 @@ impl GameState {
         Self {
             camera: Camera::new(),
             text_object,
-            circuit: Circuit::extreme_test_circuit().into(),
+            circuit: circuit.into(),
+            circuit_path: circuit_path.unwrap_or_else(|| PathBuf::from("circuit.json")),
+            waveform: WaveformPanel::default(),
             input: GameInput::default(),
             stopwatch: Stopwatch::default(),
         }
     }
 
     pub fn debug_text(&self, frame: &Frame) -> String {
-        let controls = "\nX : Delete\nC : Copy\nV : Paste\n";
+        let controls = "\nX : Delete\nC : Copy\nV : Paste\nS : Save\n[ ] : Inputs\nT : Pause Clock\nP : Pulse Clock\nW : Probe Wire\nCtrl+Z : Undo\nCtrl+Shift+Z : Redo\n";
         format!(
             "Hot: {:?}\nActive: {:?}\nFrame time: {:.2}ms\nDragging: {}\n Controls: {controls}",
             self.input.hot,
@@ -62,7 +79,7 @@ impl GameState {
 
 impl Default for GameState {
     fn default() -> Self {
-        Self::new()
+        Self::new(None)
     }
 }
 
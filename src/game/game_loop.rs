@@ -1,12 +1,16 @@
 use super::{input::InputState, GameState};
-use crate::render::frame::Frame;
+use crate::{color, render::frame::Frame};
 use glam::Vec2;
 
+// Width of the outline around the wire of the selected waveform trace
+const HIGHLIGHT_WIDTH: f32 = 0.2;
+
 impl GameState {
     pub fn update(&mut self, frame: &mut Frame) {
         self.stopwatch.start();
 
-        self.handle_inputs(frame.input());
+        let over_panel = self.waveform.update(&mut self.circuit.circuit, frame);
+        self.handle_inputs(frame.input(), over_panel);
 
         self.update_ui(frame);
 
@@ -22,15 +26,31 @@ impl GameState {
         self.text_object
             .draw(&mut frame.ui_render_queue, &frame.assets.font);
 
+        // Outlines the wire of the selected trace, underneath the wires themselves
+        // It may have been deleted since the panel last checked
+        let circuit = &self.circuit.circuit;
+        if let Some(highlighted) = self
+            .waveform
+            .highlighted
+            .and_then(|id| circuit.connection_idx(id))
+        {
+            let line = circuit.cubic_bezier_from_connection(&circuit.connections[highlighted.0]);
+            frame.draw_cubic_bezier(line, color::GREEN, HIGHLIGHT_WIDTH);
+        }
         self.circuit.draw(frame, &self.input);
+
+        self.waveform.draw(&self.circuit.circuit, frame);
     }
 
-    fn handle_inputs(&mut self, input_state: &InputState) {
+    // Input over the waveform panel doesn't reach the canvas
+    fn handle_inputs(&mut self, input_state: &InputState, over_panel: bool) {
         self.input.prev = self.input.clone().into();
-        self.camera_move(input_state);
+        if !over_panel {
+            self.camera_move(input_state);
+        }
 
         let hovering = self.circuit.hit_test(input_state.mouse_world_position);
-        self.input.hot = hovering;
+        self.input.hot = hovering.filter(|_| !over_panel);
         if input_state.left_mouse.pressed {
             self.input.active = self.input.hot;
         }
@@ -39,6 +59,29 @@ impl GameState {
         }
 
         self.circuit.handle_inputs(input_state, &mut self.input);
+
+        let s_key = winit::keyboard::Key::Character("s".into());
+        if input_state.keyboard.pressed(s_key) {
+            self.save_circuit();
+        }
+
+        let w_key = winit::keyboard::Key::Character("w".into());
+        if input_state.keyboard.pressed(w_key) {
+            for connection in self.circuit.selection.connections(&self.circuit.circuit) {
+                let connection = self.circuit.circuit.connection_id(connection);
+                self.waveform.toggle_probe(connection);
+            }
+        }
+    }
+
+    fn save_circuit(&self) {
+        match self.circuit.circuit.save(&self.circuit_path) {
+            Ok(()) => println!("Saved circuit to {}", self.circuit_path.display()),
+            Err(err) => println!(
+                "Failed to save circuit to {}: {err:?}",
+                self.circuit_path.display()
+            ),
+        }
     }
 
     fn camera_move(&mut self, input_state: &InputState) {
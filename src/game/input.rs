@@ -135,7 +135,7 @@ impl KeyboardState {
     }
 
     fn update(&mut self) {
-        for (_, state) in self.key_states.iter_mut() {
+        for state in self.key_states.values_mut() {
             state.update();
         }
 
@@ -7,23 +7,44 @@ use super::GameState;
 impl GameState {
     pub fn update_ui(&mut self, frame: &mut Frame) {
         let offset = Vec2::new(64.0, 32.0);
-        let button_width = 128.0;
+        let button_size = Vec2::new(128.0, 72.0);
+        let buttons_per_row = 10;
         let buttons = [
-            ("AND", Gate::And),
-            ("OR", Gate::Or),
+            ("AND", Gate::And(2)),
+            ("OR", Gate::Or(2)),
             ("NOT", Gate::Not),
-            ("NAND", Gate::Nand),
-            ("NOR", Gate::Nor),
-            ("XOR", Gate::Xor),
-            ("XNOR", Gate::Xnor),
+            ("NAND", Gate::Nand(2)),
+            ("NOR", Gate::Nor(2)),
+            ("XOR", Gate::Xor(2)),
+            ("XNOR", Gate::Xnor(2)),
             ("BUF", Gate::Buf),
-            ("BUTTON", Gate::Button(false)),
+            ("BUTTON", Gate::Button),
             ("IN", Gate::Input(None)),
             ("OUT", Gate::Output(None)),
+            ("MERGE", Gate::Merge(8)),
+            ("SPLIT", Gate::Split(8)),
+            ("SR", Gate::SrLatch),
+            ("DLATCH", Gate::DLatch),
+            ("DFF", Gate::DFlipFlop),
+            ("JKFF", Gate::JkFlipFlop),
+            ("REG", Gate::Register(8)),
+            ("TRI", Gate::TriState),
+            (
+                "CLOCK",
+                Gate::Clock {
+                    period: 60,
+                    high_ticks: 30,
+                    phase: 0,
+                },
+            ),
         ];
 
         for (index, (name, gate)) in buttons.iter().enumerate() {
-            let button_pos = Vec2::new(index as f32 * button_width, 0.0) + offset;
+            let cell = Vec2::new(
+                (index % buttons_per_row) as f32,
+                (index / buttons_per_row) as f32,
+            );
+            let button_pos = cell * button_size + offset;
 
             if frame.button(name, button_pos).clicked {
                 self.circuit
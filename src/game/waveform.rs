@@ -0,0 +1,328 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    color,
+    game::input::InputState,
+    logic::{
+        circuit::{Circuit, ConnectionId},
+        Logic, LogicBits,
+    },
+    render::{frame::Frame, msdf::text::TextObject},
+};
+
+use common::bounds::Bounds;
+
+const ROW_HEIGHT: f32 = 32.0;
+const TRACE_HEIGHT: f32 = 20.0;
+const LABEL_WIDTH: f32 = 160.0;
+const MARGIN: f32 = 8.0;
+const TEXT_SCALE: f32 = 16.0;
+const LINE_WIDTH: f32 = 2.0;
+// Bus values are only written on segments at least this wide
+const MIN_VALUE_WIDTH: f32 = 40.0;
+
+// Horizontal pixels per step
+const DEFAULT_ZOOM: f32 = 8.0;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 64.0;
+
+const COLOR_BACKGROUND: Vec4 = Vec4::new(0.1, 0.1, 0.1, 0.9);
+const COLOR_HIGH: Vec4 = color::RED;
+const COLOR_LOW: Vec4 = color::WHITE;
+const COLOR_CURSOR: Vec4 = color::YELLOW;
+const COLOR_HIGHLIGHTED: Vec4 = color::GREEN;
+const COLOR_UNKNOWN: Vec4 = color::PURPLE;
+
+// A scrolling timing diagram of probed connections along the bottom of the screen
+#[derive(Default)]
+pub struct WaveformPanel {
+    // Connections whose source outputs are recorded, in the order their traces are shown
+    probes: Vec<ConnectionId>,
+    zoom: f32,
+    // The step at the right edge of the diagram, or None to follow the latest step
+    scroll: Option<usize>,
+    cursor: Option<usize>,
+    // The connection of the selected trace, which is highlighted on the canvas
+    pub highlighted: Option<ConnectionId>,
+}
+
+impl WaveformPanel {
+    pub fn toggle_probe(&mut self, connection: ConnectionId) {
+        if let Some(index) = self.probes.iter().position(|probe| *probe == connection) {
+            self.probes.remove(index);
+            if self.highlighted == Some(connection) {
+                self.highlighted = None;
+            }
+        } else {
+            self.probes.push(connection);
+        }
+    }
+
+    // The panel is hidden while nothing is probed
+    pub fn bounds(&self, frame: &Frame) -> Option<Bounds> {
+        if self.probes.is_empty() {
+            return None;
+        }
+        let screen = frame.ui_camera().size * 2.0;
+        let height = self.probes.len() as f32 * ROW_HEIGHT + MARGIN * 2.0;
+        Some(Bounds::new(Vec2::new(0.0, screen.y - height), screen))
+    }
+
+    // Keeps the circuit recording the probed connections, and handles input over the panel
+    // Returns whether the mouse is over the panel, in which case the canvas shouldn't see it
+    pub fn update(&mut self, circuit: &mut Circuit, frame: &Frame) -> bool {
+        if self.zoom == 0.0 {
+            self.zoom = DEFAULT_ZOOM;
+        }
+
+        // Deleted connections stop being probed
+        self.probes
+            .retain(|probe| circuit.connection_idx(*probe).is_some());
+        if self
+            .highlighted
+            .is_some_and(|highlighted| !self.probes.contains(&highlighted))
+        {
+            self.highlighted = None;
+        }
+
+        // Changing what's probed starts a new recording
+        let recorded: Vec<_> = self
+            .probes
+            .iter()
+            .filter_map(|probe| {
+                let connection = circuit.connections[circuit.connection_idx(*probe)?.0];
+                Some(circuit.probe(connection.from))
+            })
+            .collect();
+        if self.probes.is_empty() {
+            circuit.stop_recording();
+        } else if circuit.recording().map(|trace| &trace.probes) != Some(&recorded) {
+            circuit.start_recording(recorded);
+            self.scroll = None;
+            self.cursor = None;
+        }
+
+        let Some(bounds) = self.bounds(frame) else {
+            return false;
+        };
+        // Samples which can't be shown even when zoomed all the way out are dropped
+        let diagram = diagram_bounds(bounds);
+        let history = ((diagram.bottom_right.x - diagram.top_left.x) / MIN_ZOOM) as usize + 1;
+        circuit.limit_recording(Some(history));
+
+        let input = frame.input();
+        if !bounds.contains(input.mouse_screen_position) {
+            return false;
+        }
+        self.handle_inputs(circuit, input, bounds);
+        true
+    }
+
+    fn handle_inputs(&mut self, circuit: &Circuit, input: &InputState, bounds: Bounds) {
+        let (earliest, latest) = circuit.recording().map_or((0, 0), |trace| {
+            (trace.first_step, trace.end_step().saturating_sub(1))
+        });
+        let diagram = diagram_bounds(bounds);
+        let step_at = |x: f32, zoom: f32, end: usize| {
+            let steps_from_end = ((diagram.bottom_right.x - x) / zoom).floor() as usize;
+            end.saturating_sub(steps_from_end)
+        };
+
+        // Zooming keeps the step under the mouse in place
+        if input.scroll_delta != 0.0 {
+            let end = self.scroll.unwrap_or(latest);
+            let mouse = input.mouse_screen_position.x.max(diagram.top_left.x);
+            let step = step_at(mouse, self.zoom, end);
+            self.zoom = (self.zoom * (1.0 + input.scroll_delta * 0.1)).clamp(MIN_ZOOM, MAX_ZOOM);
+            let steps_to_end = ((diagram.bottom_right.x - mouse) / self.zoom) as usize;
+            self.scroll = Some((step + steps_to_end).min(latest));
+        }
+
+        if input.right_mouse.down {
+            let end = self.scroll.unwrap_or(latest) as f32;
+            let end = end + input.mouse_screen_position_delta.x / self.zoom;
+            self.scroll = Some(end.round().clamp(earliest as f32, latest as f32) as usize);
+        }
+        // Scrolling back to the latest step follows it again
+        if self.scroll == Some(latest) {
+            self.scroll = None;
+        }
+
+        if input.left_mouse.pressed {
+            let position = input.mouse_screen_position;
+            let row = ((position.y - bounds.top_left.y - MARGIN) / ROW_HEIGHT).floor();
+            if let Some(&probe) = self.probes.get(row.max(0.0) as usize) {
+                self.highlighted = if self.highlighted == Some(probe) {
+                    None
+                } else {
+                    Some(probe)
+                };
+            }
+            if position.x >= diagram.top_left.x {
+                let end = self.scroll.unwrap_or(latest);
+                self.cursor = Some(step_at(position.x, self.zoom, end));
+            }
+        }
+    }
+
+    pub fn draw(&self, circuit: &Circuit, frame: &mut Frame) {
+        let (Some(bounds), Some(trace)) = (self.bounds(frame), circuit.recording()) else {
+            return;
+        };
+        let diagram = diagram_bounds(bounds);
+        let queue = &mut frame.ui_render_queue;
+        queue.fill_bounds(bounds, COLOR_BACKGROUND);
+
+        let latest = trace.end_step().saturating_sub(1);
+        let end = self
+            .scroll
+            .unwrap_or(latest)
+            .clamp(trace.first_step, latest);
+        let visible = ((diagram.bottom_right.x - diagram.top_left.x) / self.zoom) as usize;
+        let start = end.saturating_sub(visible).max(trace.first_step);
+        // The left edge of a step, with the end step finishing at the right edge of the diagram
+        let x = |step: usize| {
+            let x = diagram.bottom_right.x - (end + 1 - step) as f32 * self.zoom;
+            x.max(diagram.top_left.x)
+        };
+
+        for (row, probe) in trace.probes.iter().enumerate() {
+            let top = bounds.top_left.y + MARGIN + row as f32 * ROW_HEIGHT;
+            let high = top + (ROW_HEIGHT - TRACE_HEIGHT) / 2.0;
+            let low = high + TRACE_HEIGHT;
+            let value_at = |step: usize| {
+                trace
+                    .at(step)
+                    .map_or_else(Default::default, |values| values[row])
+            };
+
+            let highlighted = self
+                .probes
+                .get(row)
+                .is_some_and(|probe| self.highlighted == Some(*probe));
+            let shown = value_at(
+                self.cursor
+                    .unwrap_or(latest)
+                    .clamp(trace.first_step, latest),
+            );
+            let label = TextObject {
+                content: format!("{} = {}", probe.name, format_value(shown, probe.width)),
+                position: Vec2::new(MARGIN, top + (ROW_HEIGHT - TEXT_SCALE) / 2.0),
+                scale: TEXT_SCALE,
+                centered: false,
+            };
+            label.draw(queue, &frame.assets.font);
+
+            // Runs of steps holding the same value
+            let mut segments = vec![];
+            for step in start..=end {
+                match segments.last_mut() {
+                    Some((_, last, value)) if *value == value_at(step) => *last = step,
+                    _ => segments.push((step, step, value_at(step))),
+                }
+            }
+
+            // X and Z are drawn halfway between the levels, and picked out in their own colour
+            let middle = (high + low) / 2.0;
+            if probe.width == 1 {
+                let mut points = vec![];
+                for &(first, last, value) in &segments {
+                    let y = match value.bit(0) {
+                        Logic::One => high,
+                        Logic::Zero => low,
+                        Logic::X | Logic::Z => middle,
+                    };
+                    points.push(Vec2::new(x(first), y));
+                    points.push(Vec2::new(x(last + 1), y));
+                }
+                let color = if highlighted {
+                    COLOR_HIGHLIGHTED
+                } else {
+                    COLOR_HIGH
+                };
+                queue.draw_polyline(&points, LINE_WIDTH, color);
+                for &(first, last, value) in &segments {
+                    if value.unknown & 1 != 0 {
+                        queue.draw_polyline(
+                            &[Vec2::new(x(first), middle), Vec2::new(x(last + 1), middle)],
+                            LINE_WIDTH,
+                            COLOR_UNKNOWN,
+                        );
+                    }
+                }
+                continue;
+            }
+
+            // Buses are drawn as a band which crosses over wherever the value changes
+            let color = if highlighted {
+                COLOR_HIGHLIGHTED
+            } else {
+                COLOR_LOW
+            };
+            for &(first, last, value) in &segments {
+                let (left, right) = (x(first), x(last + 1));
+                let slope = (self.zoom / 2.0).min(4.0);
+                let color = if value.unknown != 0 {
+                    COLOR_UNKNOWN
+                } else {
+                    color
+                };
+                queue.draw_polyline(
+                    &[
+                        Vec2::new(left, middle),
+                        Vec2::new(left + slope, high),
+                        Vec2::new(right - slope, high),
+                        Vec2::new(right, middle),
+                        Vec2::new(right - slope, low),
+                        Vec2::new(left + slope, low),
+                        Vec2::new(left, middle),
+                    ],
+                    LINE_WIDTH,
+                    color,
+                );
+
+                if right - left >= MIN_VALUE_WIDTH {
+                    let text = TextObject {
+                        content: format_value(value, probe.width),
+                        position: Vec2::new(
+                            left + slope * 2.0,
+                            high + (TRACE_HEIGHT - TEXT_SCALE) / 2.0,
+                        ),
+                        scale: TEXT_SCALE,
+                        centered: false,
+                    };
+                    text.draw(queue, &frame.assets.font);
+                }
+            }
+        }
+
+        if let Some(cursor) = self.cursor.filter(|cursor| (start..=end).contains(cursor)) {
+            let x = x(cursor) + self.zoom / 2.0;
+            queue.draw_polyline(
+                &[
+                    Vec2::new(x, bounds.top_left.y),
+                    Vec2::new(x, bounds.bottom_right.y),
+                ],
+                LINE_WIDTH / 2.0,
+                COLOR_CURSOR,
+            );
+        }
+    }
+}
+
+// The part of the panel right of the labels
+fn diagram_bounds(bounds: Bounds) -> Bounds {
+    Bounds::new(
+        Vec2::new(bounds.top_left.x + LABEL_WIDTH, bounds.top_left.y),
+        Vec2::new(bounds.bottom_right.x - MARGIN, bounds.bottom_right.y),
+    )
+}
+
+// Buses with any X or Z bits are written bit by bit
+fn format_value(value: LogicBits, width: u8) -> String {
+    match width {
+        1 => value.symbols(1),
+        _ if value.unknown != 0 => value.symbols(width),
+        _ => format!("0x{:X}", value.value),
+    }
+}
@@ -1,9 +1,14 @@
-#![feature(duration_millis_float)]
-#![feature(mapped_lock_guards)]
+#![cfg_attr(feature = "gui", feature(duration_millis_float))]
+#![cfg_attr(feature = "gui", feature(mapped_lock_guards))]
 
+#[cfg(feature = "gui")]
 pub mod app;
+#[cfg(feature = "gui")]
 pub mod color;
+#[cfg(feature = "gui")]
 pub mod game;
 pub mod logic;
+#[cfg(feature = "gui")]
 pub mod render;
+#[cfg(feature = "gui")]
 pub mod ui;
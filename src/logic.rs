@@ -1,4 +1,13 @@
+pub mod blif;
 pub mod circuit;
+pub mod equivalence;
+pub mod file;
 pub mod gate;
 pub mod hit_test;
+mod netlist;
 mod solver;
+pub mod synth;
+pub mod vectors;
+pub mod verilog;
+
+pub use solver::{Logic, LogicBits, SettleError, SolverMode};
@@ -0,0 +1,460 @@
+//! Berkeley Logic Interchange Format netlists.
+//!
+//! Every `.names` cover becomes a sum of products built from NOT, AND and OR
+//! gates, or the inverse of one when its rows give the OFF-set. Latches become
+//! flip-flops or transparent latches, and those on the global clock share an
+//! Input gate named `clock`. Each model instantiated with `.subckt` becomes a
+//! component, so the top model, which is the first one, embeds the others.
+
+use std::{collections::HashMap, sync::Arc};
+
+use super::{
+    circuit::{
+        connection::ConnectionError,
+        embedded::{Component, EmbeddedCircuit},
+        Circuit,
+    },
+    gate::{Gate, FAN_IN},
+    netlist::{Netlist, Source},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlifError {
+    // A line which doesn't fit the format, by line number
+    Syntax { line: usize, found: String },
+    // A command outside of the supported subset, such as `.gate` which needs a cell library
+    Unsupported { line: usize, construct: String },
+    NoModels,
+    UnknownModel { line: usize, model: String },
+    // A model which instantiates itself, directly or through others
+    RecursiveModel(String),
+    UnknownPort { line: usize, port: String },
+    MultipleDrivers { line: usize, net: String },
+    // A gate which reads its own output, like a buffer feeding itself
+    Connection(ConnectionError),
+}
+
+// Latches without a clock of their own use this one
+const GLOBAL_CLOCK: &str = "clock";
+
+// Commands which only carry timing or area information
+const IGNORED: &[&str] = &[
+    ".area",
+    ".attr",
+    ".cname",
+    ".default_input_arrival",
+    ".default_input_drive",
+    ".default_max_input_load",
+    ".default_output_load",
+    ".default_output_required",
+    ".delay",
+    ".input_arrival",
+    ".input_drive",
+    ".max_input_load",
+    ".output_load",
+    ".output_required",
+    ".param",
+    ".wire",
+    ".wire_load_slope",
+];
+
+impl Circuit {
+    pub fn from_blif(source: &str) -> Result<Self, BlifError> {
+        let models = parse(source)?;
+        let top = models.first().ok_or(BlifError::NoModels)?;
+        let mut importer = Importer {
+            models: models
+                .iter()
+                .map(|model| (model.name.as_str(), model))
+                .collect(),
+            components: HashMap::new(),
+            stack: vec![],
+        };
+        importer.circuit(top)
+    }
+}
+
+struct Model {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    commands: Vec<(usize, Command)>,
+}
+
+enum Command {
+    // Rows of input values, each `0`, `1` or `-`, for which the output has the given value
+    Names {
+        inputs: Vec<String>,
+        output: String,
+        rows: Vec<String>,
+        value: Option<bool>,
+    },
+    Latch {
+        input: String,
+        output: String,
+        kind: Option<String>,
+        control: Option<String>,
+    },
+    Subckt {
+        model: String,
+        ports: Vec<(String, String)>,
+    },
+}
+
+// Logical lines with their line numbers, without comments or continuations
+fn lines(source: &str) -> Vec<(usize, Vec<&str>)> {
+    let mut lines = vec![];
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let (line, continues) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        let (_, tokens) = current.get_or_insert((index + 1, vec![]));
+        tokens.extend(line.split_whitespace());
+        if !continues {
+            let (number, tokens) = current.take().unwrap();
+            if !tokens.is_empty() {
+                lines.push((number, tokens));
+            }
+        }
+    }
+    lines.extend(current.filter(|(_, tokens)| !tokens.is_empty()));
+    lines
+}
+
+fn parse(source: &str) -> Result<Vec<Model>, BlifError> {
+    let mut models = vec![];
+    let mut model: Option<Model> = None;
+    for (line, tokens) in lines(source) {
+        let syntax = || BlifError::Syntax {
+            line,
+            found: tokens.join(" "),
+        };
+        let command = tokens[0];
+        let arguments: Vec<String> = tokens[1..].iter().map(|token| token.to_string()).collect();
+
+        if command == ".model" {
+            models.extend(model.take());
+            model = Some(Model {
+                name: arguments.first().cloned().unwrap_or_default(),
+                inputs: vec![],
+                outputs: vec![],
+                commands: vec![],
+            });
+            continue;
+        }
+        if command == ".end" {
+            models.extend(model.take());
+            continue;
+        }
+
+        // Files may leave out `.model` when they only have one
+        let current = model.get_or_insert_with(|| Model {
+            name: String::new(),
+            inputs: vec![],
+            outputs: vec![],
+            commands: vec![],
+        });
+        match command {
+            ".inputs" | ".clock" => current.inputs.extend(arguments),
+            ".outputs" => current.outputs.extend(arguments),
+            ".names" => {
+                let (output, inputs) = arguments.split_last().ok_or_else(syntax)?;
+                let command = Command::Names {
+                    inputs: inputs.to_vec(),
+                    output: output.clone(),
+                    rows: vec![],
+                    value: None,
+                };
+                current.commands.push((line, command));
+            }
+            ".latch" => {
+                // Either `input output [init]` or `input output type control [init]`
+                let (kind, control) = match &arguments[..] {
+                    [_, _] | [_, _, _] => (None, None),
+                    [_, _, kind, control] | [_, _, kind, control, _] => {
+                        let control = (control != "NIL").then(|| control.clone());
+                        (Some(kind.clone()), control)
+                    }
+                    _ => return Err(syntax()),
+                };
+                if arguments.len() % 2 == 1 && arguments.last().unwrap() == "1" {
+                    return Err(BlifError::Unsupported {
+                        line,
+                        construct: ".latch initialised to 1".to_string(),
+                    });
+                }
+                let command = Command::Latch {
+                    input: arguments[0].clone(),
+                    output: arguments[1].clone(),
+                    kind,
+                    control,
+                };
+                current.commands.push((line, command));
+            }
+            ".subckt" => {
+                let (model, ports) = arguments.split_first().ok_or_else(syntax)?;
+                let ports = ports
+                    .iter()
+                    .map(|port| {
+                        let (formal, actual) = port.split_once('=').ok_or_else(syntax)?;
+                        Ok((formal.to_string(), actual.to_string()))
+                    })
+                    .collect::<Result<_, _>>()?;
+                let command = Command::Subckt {
+                    model: model.clone(),
+                    ports,
+                };
+                current.commands.push((line, command));
+            }
+            command if IGNORED.contains(&command) => {}
+            command if command.starts_with('.') => {
+                return Err(BlifError::Unsupported {
+                    line,
+                    construct: command.to_string(),
+                })
+            }
+            _ => {
+                // A row of the latest cover
+                let Some((
+                    _,
+                    Command::Names {
+                        inputs,
+                        rows,
+                        value,
+                        ..
+                    },
+                )) = current.commands.last_mut()
+                else {
+                    return Err(syntax());
+                };
+                let (row, output) = match &tokens[..] {
+                    [output] if inputs.is_empty() => ("", *output),
+                    [row, output] if row.len() == inputs.len() => (*row, *output),
+                    _ => return Err(syntax()),
+                };
+                let output = match output {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(syntax()),
+                };
+                // Rows give either the ON-set or the OFF-set
+                if !row.chars().all(|char| matches!(char, '0' | '1' | '-'))
+                    || value.is_some_and(|value| value != output)
+                {
+                    return Err(syntax());
+                }
+                *value = Some(output);
+                rows.push(row.to_string());
+            }
+        }
+    }
+    models.extend(model);
+    Ok(models)
+}
+
+struct Importer<'a> {
+    models: HashMap<&'a str, &'a Model>,
+    // Every instance of a model shares one component
+    components: HashMap<&'a str, Arc<Component>>,
+    // Models being imported, innermost last
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Importer<'a> {
+    fn component(&mut self, name: &str, line: usize) -> Result<Arc<Component>, BlifError> {
+        if let Some(component) = self.components.get(name) {
+            return Ok(component.clone());
+        }
+
+        let model = *self
+            .models
+            .get(name)
+            .ok_or_else(|| BlifError::UnknownModel {
+                line,
+                model: name.to_string(),
+            })?;
+        if self.stack.contains(&model.name.as_str()) {
+            return Err(BlifError::RecursiveModel(model.name.clone()));
+        }
+
+        let component = Arc::new(Component::named(&model.name, self.circuit(model)?));
+        self.components.insert(&model.name, component.clone());
+        Ok(component)
+    }
+
+    fn circuit(&mut self, model: &'a Model) -> Result<Circuit, BlifError> {
+        self.stack.push(&model.name);
+        let mut builder = Builder {
+            netlist: Netlist::default(),
+            inverters: HashMap::new(),
+        };
+        for input in &model.inputs {
+            builder.netlist.input(input, 1);
+        }
+        let global_clock = model
+            .commands
+            .iter()
+            .any(|(_, command)| matches!(command, Command::Latch { control: None, .. }));
+        if global_clock && !model.inputs.iter().any(|input| input == GLOBAL_CLOCK) {
+            builder.netlist.input(GLOBAL_CLOCK, 1);
+        }
+        for output in &model.outputs {
+            builder.netlist.output(output);
+        }
+
+        for (line, command) in &model.commands {
+            builder.command(*line, command, self)?;
+        }
+        self.stack.pop();
+        builder.netlist.finish().map_err(BlifError::Connection)
+    }
+}
+
+struct Builder {
+    netlist: Netlist,
+    // A NOT gate for each net which is used inverted
+    inverters: HashMap<String, Source>,
+}
+
+impl Builder {
+    fn drive(&mut self, net: &str, source: Source, line: usize) -> Result<(), BlifError> {
+        if self.netlist.drive(net, source) {
+            Ok(())
+        } else {
+            Err(BlifError::MultipleDrivers {
+                line,
+                net: net.to_string(),
+            })
+        }
+    }
+
+    fn inverted(&mut self, net: &str) -> Source {
+        if let Some(inverter) = self.inverters.get(net) {
+            return inverter.clone();
+        }
+        let not = self.netlist.add(Gate::Not, &[1]);
+        self.netlist
+            .connect(Source::Net(net.to_string()), not.input(0));
+        let inverter = Source::Pin(not.output(0));
+        self.inverters.insert(net.to_string(), inverter.clone());
+        inverter
+    }
+
+    // A single source combining the sources with a gate, using a tree of gates if there are too
+    // many for one, or `empty` if there are none
+    fn combine(&mut self, mut sources: Vec<Source>, gate: fn(u8) -> Gate, empty: Gate) -> Source {
+        if sources.is_empty() {
+            return Source::Pin(self.netlist.add(empty, &[1]).output(0));
+        }
+
+        while sources.len() > 1 {
+            sources = sources
+                .chunks(*FAN_IN.end() as usize)
+                .map(|chunk| match chunk {
+                    [source] => source.clone(),
+                    chunk => {
+                        let combined = self.netlist.add(gate(chunk.len() as u8), &[1]);
+                        for (input, source) in chunk.iter().enumerate() {
+                            self.netlist.connect(source.clone(), combined.input(input));
+                        }
+                        Source::Pin(combined.output(0))
+                    }
+                })
+                .collect();
+        }
+        sources.pop().unwrap()
+    }
+
+    fn command(
+        &mut self,
+        line: usize,
+        command: &Command,
+        importer: &mut Importer,
+    ) -> Result<(), BlifError> {
+        match command {
+            Command::Names {
+                inputs,
+                output,
+                rows,
+                value,
+            } => {
+                let terms = rows
+                    .iter()
+                    .map(|row| {
+                        let literals = row
+                            .chars()
+                            .zip(inputs)
+                            .filter_map(|(char, input)| match char {
+                                '1' => Some(Source::Net(input.clone())),
+                                '0' => Some(self.inverted(input)),
+                                _ => None,
+                            })
+                            .collect();
+                        self.combine(literals, Gate::And, Gate::On)
+                    })
+                    .collect();
+                let mut sum = self.combine(terms, Gate::Or, Gate::Off);
+
+                // Rows of the OFF-set give the inverse of the output
+                if *value == Some(false) {
+                    let not = self.netlist.add(Gate::Not, &[1]);
+                    self.netlist.connect(sum, not.input(0));
+                    sum = Source::Pin(not.output(0));
+                }
+                self.drive(output, sum, line)
+            }
+            Command::Latch {
+                input,
+                output,
+                kind,
+                control,
+            } => {
+                let control = control.as_deref().unwrap_or(GLOBAL_CLOCK);
+                let (gate, control) = match kind.as_deref() {
+                    None | Some("re") => (Gate::DFlipFlop, Source::Net(control.to_string())),
+                    Some("fe") => (Gate::DFlipFlop, self.inverted(control)),
+                    Some("ah") => (Gate::DLatch, Source::Net(control.to_string())),
+                    Some("al") => (Gate::DLatch, self.inverted(control)),
+                    Some(kind) => {
+                        return Err(BlifError::Unsupported {
+                            line,
+                            construct: format!(".latch {kind}"),
+                        })
+                    }
+                };
+                let latch = self.netlist.add(gate, &[1, 1]);
+                self.netlist
+                    .connect(Source::Net(input.clone()), latch.input(0));
+                self.netlist.connect(control, latch.input(1));
+                self.drive(output, Source::Pin(latch.output(0)), line)
+            }
+            Command::Subckt { model, ports } => {
+                let embed = EmbeddedCircuit::instance(importer.component(model, line)?);
+                let instance = self
+                    .netlist
+                    .add(embed.clone().into(), embed.output_widths());
+                let position = |names: &[Option<String>], port: &str| {
+                    names.iter().position(|name| name.as_deref() == Some(port))
+                };
+                for (formal, actual) in ports {
+                    if let Some(input) = position(embed.input_names(), formal) {
+                        self.netlist
+                            .connect(Source::Net(actual.clone()), instance.input(input));
+                    } else if let Some(output) = position(embed.output_names(), formal) {
+                        self.drive(actual, Source::Pin(instance.output(output)), line)?;
+                    } else {
+                        return Err(BlifError::UnknownPort {
+                            line,
+                            port: formal.clone(),
+                        });
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,47 +1,83 @@
 pub mod connection;
+#[cfg(feature = "gui")]
 mod edit_circuit;
 pub mod embedded;
 mod examples;
+mod flatten;
+mod history;
+mod lanes;
+mod layout;
+pub use flatten::FlatCircuit;
+pub use history::{Edit, History, Items, Wire};
+pub use lanes::{exhaustive_lanes, LaneError, LANES};
+mod redefine;
+#[cfg(feature = "gui")]
 pub use edit_circuit::EditCircuit;
 use embedded::EmbeddedCircuit;
-mod element;
+pub mod element;
+#[cfg(feature = "gui")]
 mod render;
+mod trace;
+pub use trace::{Probe, Trace};
+mod truth_table;
+pub use truth_table::{Column, Row, TruthTable, TruthTableError, MAX_TRUTH_TABLE_BITS};
 
 #[cfg(test)]
 mod test;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     ops::{Index, IndexMut},
 };
 
-use element::CircuitElement;
+use element::{CircuitElement, Delay};
 use glam::{vec2, Vec2};
 
-use super::{gate::Gate, hit_test::HitTestResult, solver::SolverState};
+#[cfg(feature = "gui")]
+use super::hit_test::HitTestResult;
+use super::{
+    gate::{Gate, FAN_IN},
+    solver::{Logic, LogicBits, SettleError, SolverMode, SolverState},
+};
+#[cfg(feature = "gui")]
 use crate::render::line::cubic_bezier::CubicBezier;
 
+#[cfg(feature = "gui")]
 use common::bounds::Bounds;
+use common::{handle::Handle, slots::Slots};
 
 use connection::{
-    Connection, ConnectionIdx, ElementIdx, IOSpecifier, InputIdx, InputSpecifier, OutputIdx,
-    OutputSpecifier,
+    Connection, ConnectionError, ConnectionIdx, ElementIdx, IOSpecifier, InputIdx, InputSpecifier,
+    OutputIdx, OutputSpecifier, MAX_BUS_WIDTH,
 };
 
+// Identifies an element or connection for as long as it exists, unlike its index which shifts
+// whenever an earlier one is removed
+pub type ElementId = Handle<CircuitElement>;
+pub type ConnectionId = Handle<Connection>;
+
 #[derive(Default, Clone, Debug)]
 pub struct Circuit {
     // TODO: Make this generic
     pub(crate) elements: Vec<CircuitElement>,
     pub(crate) connections: Vec<Connection>,
+    // Removals must go through these to keep the IDs of the remaining items
+    pub(crate) element_ids: Slots<CircuitElement>,
+    pub(crate) connection_ids: Slots<Connection>,
     pub(crate) solver: SolverState,
+    pub(crate) recording: Option<Box<Trace>>,
 }
 
 impl Circuit {
     // Progress a single clock cycle
     pub fn step(&mut self) {
-        let solver = self.solver.clone();
-        let solver = solver.step(self);
-        self.solver = solver;
+        let solver = std::mem::take(&mut self.solver);
+        self.solver = solver.step(self);
+
+        if let Some(mut trace) = self.recording.take() {
+            trace.sample(self);
+            self.recording = Some(trace);
+        }
     }
 
     pub fn step_n(&mut self, n: usize) {
@@ -50,6 +86,59 @@ impl Circuit {
         }
     }
 
+    // Steps until the circuit stops changing, returning the number of steps which changed its state
+    pub fn settle(&mut self, max_steps: usize) -> Result<usize, SettleError> {
+        self.right_size_solver();
+
+        let mut state = self.state_snapshot();
+        let mut seen = HashMap::from([(state.clone(), 0)]);
+
+        for step in 1..=max_steps {
+            self.step();
+            let next = self.state_snapshot();
+
+            if next == state {
+                return Ok(step - 1);
+            }
+
+            if let Some(first_seen) = seen.insert(next.clone(), step) {
+                return Err(SettleError::Oscillating {
+                    period: step - first_seen,
+                });
+            }
+
+            state = next;
+        }
+
+        Err(SettleError::StepLimit)
+    }
+
+    // Every value which can affect future steps, including those of embedded circuits
+    pub(crate) fn state_snapshot(&self) -> Vec<u64> {
+        self.solver.snapshot()
+    }
+
+    pub fn solver_mode(&self) -> SolverMode {
+        self.solver.mode
+    }
+
+    pub fn set_solver_mode(&mut self, mode: SolverMode) {
+        self.solver.mode = mode;
+        self.solver.invalidate();
+    }
+
+    pub fn with_solver_mode(mut self, mode: SolverMode) -> Self {
+        self.set_solver_mode(mode);
+        self
+    }
+
+    // Steps each embedded instance with its own solver instead of simulating the flattened circuit
+    // Flattening keeps embedded circuits from adding a step of delay at their pins
+    pub fn set_hierarchical(&mut self, hierarchical: bool) {
+        self.solver.hierarchical = hierarchical;
+        self.solver.invalidate();
+    }
+
     pub fn extreme_test_circuit() -> Self {
         let mut circuit = Circuit::default();
 
@@ -65,19 +154,19 @@ impl Circuit {
     }
 
     pub fn embed(&self) -> EmbeddedCircuit {
-        EmbeddedCircuit::new(self.clone()).unwrap()
+        EmbeddedCircuit::new(self.clone())
     }
 
     fn add_random_component(&mut self) {
         let position = vec2(rand::random::<f32>() * 100.0, rand::random::<f32>() * 100.0);
         let gates = [
-            Gate::And,
-            Gate::Or,
+            Gate::And(2),
+            Gate::Or(2),
             Gate::Not,
             Gate::Buf,
-            Gate::Xor,
-            Gate::Xnor,
-            Gate::Nand,
+            Gate::Xor(2),
+            Gate::Xnor(2),
+            Gate::Nand(2),
         ];
         let gate = gates[rand::random::<u64>() as usize % gates.len()].clone();
 
@@ -95,26 +184,173 @@ impl Circuit {
             InputIdx(0),
         );
 
-        self.add_connection(from.to(to));
+        // Random connections which aren't allowed are skipped
+        let _ = self.add_connection(from.to(to));
     }
 
     pub fn add_gate(&mut self, gate: Gate, position: Vec2) -> ElementIdx {
         let idx = ElementIdx(self.elements.len());
-        self.elements.push(CircuitElement { gate, position });
+        let element = CircuitElement {
+            gate,
+            position,
+            delay: Delay::default(),
+        };
+        self.element_ids.push(&mut self.elements, element);
+        self.solver.invalidate();
         idx
     }
 
-    pub fn add_connection(&mut self, connection: Connection) {
+    pub fn set_delay(&mut self, element: ElementIdx, delay: Delay) {
+        self[element].delay = Delay::new(delay.rise, delay.fall);
+        self.solver.invalidate();
+    }
+
+    // Adds a connection after checking it against the pins it connects
+    // Adding a connection which already exists does nothing
+    pub fn add_connection(&mut self, connection: Connection) -> Result<(), ConnectionError> {
         if self.connections.contains(&connection) {
-            return;
+            return Ok(());
+        }
+        self.check_connection(connection)?;
+        self.connection_ids.push(&mut self.connections, connection);
+        self.solver.invalidate();
+        Ok(())
+    }
+
+    // Whether a new connection could be added, or why not
+    pub fn check_connection(&self, connection: Connection) -> Result<(), ConnectionError> {
+        let Connection { from, to, width } = connection;
+        let exists = |element: ElementIdx, pins: fn(&Gate) -> usize, pin: usize| {
+            self.elements
+                .get(element.0)
+                .is_some_and(|element| pin < pins(&element.gate))
+        };
+        if !exists(from.0, Gate::output_count, from.1 .0) {
+            return Err(ConnectionError::InvalidPin(from.into()));
+        }
+        if !exists(to.0, Gate::input_count, to.1 .0) {
+            return Err(ConnectionError::InvalidPin(to.into()));
+        }
+
+        if !(1..=MAX_BUS_WIDTH).contains(&width) {
+            return Err(ConnectionError::InvalidWidth(width));
+        }
+
+        for pin in [IOSpecifier::from(connection.from), connection.to.into()] {
+            match self.pin_width(pin) {
+                Some(expected) if expected != connection.width => {
+                    return Err(ConnectionError::WidthMismatch {
+                        expected,
+                        found: connection.width,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        if from.0 == to.0 && !self[to.0].gate.allows_self_loop() {
+            return Err(ConnectionError::SelfLoop);
+        }
+
+        // Tri-state buffers can share a bus since only the enabled ones drive it
+        let tri_state = |output: OutputSpecifier| matches!(self[output.0].gate, Gate::TriState);
+        if let Some(driver) = self
+            .drivers(to)
+            .find(|driver| *driver != from && !(tri_state(*driver) && tri_state(from)))
+        {
+            return Err(ConnectionError::AlreadyDriven(driver));
+        }
+        Ok(())
+    }
+
+    // The outputs connected to an input
+    pub fn drivers(&self, input: InputSpecifier) -> impl Iterator<Item = OutputSpecifier> + '_ {
+        self.connections
+            .iter()
+            .filter(move |connection| connection.to == input)
+            .map(|connection| connection.from)
+    }
+
+    // The bit width of a pin, or None if it can still be connected at any width
+    // Pins without a declared width share the width of the gate's other undeclared pins
+    pub fn pin_width(&self, pin: impl Into<IOSpecifier>) -> Option<u8> {
+        let pin = pin.into();
+        let element = pin.element();
+        let gate = &self[element].gate;
+
+        let declared = match pin {
+            IOSpecifier::Input(InputSpecifier(_, idx)) => gate.input_width(idx),
+            IOSpecifier::Output(OutputSpecifier(_, idx)) => gate.output_width(idx),
+        };
+
+        declared.or_else(|| {
+            self.connections.iter().find_map(|connection| {
+                let undeclared_output =
+                    connection.from.0 == element && gate.output_width(connection.from.1).is_none();
+                let undeclared_input =
+                    connection.to.0 == element && gate.input_width(connection.to.1).is_none();
+                (undeclared_output || undeclared_input).then_some(connection.width)
+            })
+        })
+    }
+
+    // Input gates, which become the pins of the circuit when it's embedded
+    pub fn input_ports(&self) -> Vec<ElementIdx> {
+        self.ports(|gate| matches!(gate, Gate::Input(_)))
+    }
+
+    // Output gates, which become the pins of the circuit when it's embedded
+    pub fn output_ports(&self) -> Vec<ElementIdx> {
+        self.ports(|gate| matches!(gate, Gate::Output(_)))
+    }
+
+    // Ports are ordered top to bottom like the pins of a gate, then left to right, then by index
+    fn ports(&self, is_port: impl Fn(&Gate) -> bool) -> Vec<ElementIdx> {
+        let mut ports: Vec<_> = (0..self.elements.len())
+            .map(ElementIdx)
+            .filter(|&element| is_port(&self[element].gate))
+            .collect();
+
+        ports.sort_by(|a, b| {
+            let (a, b) = (self[*a].position, self[*b].position);
+            b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x))
+        });
+        ports
+    }
+
+    // Drives the value of an Input gate from outside of the circuit
+    pub fn set_input(&mut self, port: ElementIdx, value: u64) {
+        self.right_size_solver();
+        self.solver.drive_output(port.output(0), value);
+    }
+
+    // Inserts an element at an index, moving the elements after it along
+    pub fn insert_gate(
+        &mut self,
+        ElementIdx(index): ElementIdx,
+        element: CircuitElement,
+    ) -> ElementId {
+        for connection in self.connections.iter_mut() {
+            if connection.from.0 .0 >= index {
+                connection.from.0 .0 += 1;
+            }
+
+            if connection.to.0 .0 >= index {
+                connection.to.0 .0 += 1;
+            }
         }
-        self.connections.push(connection);
+
+        let id = self.element_ids.insert(&mut self.elements, index, element);
+        self.solver.invalidate();
+        id
     }
 
     pub fn remove_gate(&mut self, ElementIdx(index): ElementIdx) {
         // Remove connections referencing the removed gate
-        self.connections
-            .retain(|connection| connection.from.0 .0 != index && connection.to.0 .0 != index);
+        self.connection_ids
+            .retain(&mut self.connections, |connection| {
+                connection.from.0 .0 != index && connection.to.0 .0 != index
+            });
 
         // Modify the indices of the remaining connections which come after the removed gate
         for connection in self.connections.iter_mut() {
@@ -128,7 +364,23 @@ impl Circuit {
         }
 
         // Finally remove the element
-        self.elements.remove(index);
+        self.element_ids.remove(&mut self.elements, index);
+        self.solver.invalidate();
+    }
+
+    // Changes the number of inputs of a basic gate, removing connections to inputs which no longer exist
+    pub fn set_fan_in(&mut self, element: ElementIdx, inputs: u8) {
+        let Some(fan_in) = self[element].gate.fan_in_mut() else {
+            return;
+        };
+        *fan_in = inputs.clamp(*FAN_IN.start(), *FAN_IN.end());
+        let inputs = *fan_in as usize;
+
+        self.connection_ids
+            .retain(&mut self.connections, |connection| {
+                connection.to.0 != element || connection.to.1 .0 < inputs
+            });
+        self.solver.invalidate();
     }
 
     // Some gates will change state based on click events
@@ -136,36 +388,98 @@ impl Circuit {
         println!("Clicked gate {}", index);
 
         match &mut self.elements[index].gate {
-            Gate::Button(state) => *state = true,
+            Gate::Button => {
+                self.right_size_solver();
+                self.solver.gate_state_mut().element_mut(ElementIdx(index))[0] = !0;
+            }
             Gate::Const(state) => *state = !*state,
             _ => {}
         }
+        self.solver.invalidate();
+    }
+
+    pub fn clock_paused(&self, element: ElementIdx) -> bool {
+        matches!(self[element].gate, Gate::Clock { .. })
+            && self
+                .solver
+                .element_state(self.element_id(element))
+                .is_some_and(|state| state[1] != 0)
+    }
+
+    pub fn set_clock_paused(&mut self, element: ElementIdx, paused: bool) {
+        if !matches!(self[element].gate, Gate::Clock { .. }) {
+            return;
+        }
+
+        self.right_size_solver();
+        self.solver.gate_state_mut().element_mut(element)[1] = paused as u64;
+    }
+
+    // Pauses the clock and emits a single high period
+    pub fn pulse_clock(&mut self, element: ElementIdx) {
+        let Gate::Clock { high_ticks, .. } = self[element].gate else {
+            return;
+        };
+
+        self.right_size_solver();
+        let state = self.solver.gate_state_mut().element_mut(element);
+        state[1] = 1;
+        state[2] = high_ticks as u64;
     }
 
     pub fn remove_connections(&mut self, spec: impl Into<IOSpecifier>) {
         match spec.into() {
             IOSpecifier::Input(input) => {
-                self.connections.retain(|connection| connection.to != input);
+                self.connection_ids
+                    .retain(&mut self.connections, |connection| connection.to != input);
             }
             IOSpecifier::Output(output) => {
-                self.connections
-                    .retain(|connection| connection.from != output);
+                self.connection_ids
+                    .retain(&mut self.connections, |connection| {
+                        connection.from != output
+                    });
             }
         }
+        self.solver.invalidate();
     }
 
     pub fn remove_many_connections(&mut self, connections: HashSet<ConnectionIdx>) {
         let mut index: usize = 0;
-        self.connections.retain(|_| {
+        self.connection_ids.retain(&mut self.connections, |_| {
             index += 1;
             !connections.contains(&ConnectionIdx(index - 1))
         });
+        self.solver.invalidate();
     }
 
     pub fn remove_connection(&mut self, idx: ConnectionIdx) {
-        self.connections.remove(idx.0);
+        self.connection_ids.remove(&mut self.connections, idx.0);
+        self.solver.invalidate();
+    }
+
+    pub fn element_id(&self, ElementIdx(index): ElementIdx) -> ElementId {
+        self.element_ids.handle(index)
     }
 
+    // The current index of an element, or None if it has been removed
+    pub fn element_idx(&self, id: ElementId) -> Option<ElementIdx> {
+        self.element_ids
+            .position(id, self.elements.len())
+            .map(ElementIdx)
+    }
+
+    pub fn connection_id(&self, ConnectionIdx(index): ConnectionIdx) -> ConnectionId {
+        self.connection_ids.handle(index)
+    }
+
+    // The current index of a connection, or None if it has been removed
+    pub fn connection_idx(&self, id: ConnectionId) -> Option<ConnectionIdx> {
+        self.connection_ids
+            .position(id, self.connections.len())
+            .map(ConnectionIdx)
+    }
+
+    #[cfg(feature = "gui")]
     pub fn hit_test_bounds(&self, bounds: Bounds) -> HashSet<HitTestResult> {
         let mut res = vec![];
 
@@ -211,6 +525,7 @@ impl Circuit {
         HashSet::from_iter(res)
     }
 
+    #[cfg(feature = "gui")]
     pub fn hit_test(&self, position: Vec2) -> Option<HitTestResult> {
         for (element_idx, element) in self.elements.iter().enumerate() {
             for (input_idx, offset) in element.gate.input_offsets().into_iter().enumerate() {
@@ -254,6 +569,7 @@ impl Circuit {
         None
     }
 
+    #[cfg(feature = "gui")]
     fn connection_dots(&self) -> impl Iterator<Item = IOSpecifier> + '_ {
         self.elements
             .iter()
@@ -276,6 +592,7 @@ impl Circuit {
             })
     }
 
+    #[cfg(feature = "gui")]
     fn io_position(&self, spec: impl Into<IOSpecifier>) -> Vec2 {
         let spec = spec.into();
         let element = &self[spec.element()];
@@ -291,6 +608,7 @@ impl Circuit {
         element.position + offset
     }
 
+    #[cfg(feature = "gui")]
     pub fn cubic_bezier_from_connection(&self, connection: &Connection) -> CubicBezier {
         let from_elm = &self[connection.from.0];
         let from = from_elm.gate.output_offset(connection.from.1) + from_elm.position;
@@ -300,13 +618,31 @@ impl Circuit {
         CubicBezier::between_points(from, to)
     }
 
+    // Every read of an output goes through here, so X and Z bits are never mistaken for levels
+    // Nothing is X or Z outside of four-valued mode
+    pub fn output_bits(&self, io: OutputSpecifier) -> LogicBits {
+        let width = self.pin_width(io).unwrap_or(1);
+        self.solver.read_logic(io).masked(width)
+    }
+
+    // X and Z read as low, `output_logic` tells them apart
     pub fn output_value(&self, io: OutputSpecifier) -> bool {
-        // TODO: Make this handle multiple outputs
-        self.solver.output_results.read_output(io)
+        self.output_bits(io).known() & 1 == 1
+    }
+
+    pub fn output_logic(&self, io: OutputSpecifier) -> Logic {
+        self.output_bits(io).bit(0)
+    }
+
+    // The value of an output, masked to the width of the pin, where bits which are X or Z read as low
+    pub fn output_bus_value(&self, io: OutputSpecifier) -> u64 {
+        self.output_bits(io).known()
     }
 
     pub fn right_size_solver(&mut self) {
-        self.solver.set_size(self.elements.len());
+        let mut solver = std::mem::take(&mut self.solver);
+        solver.prepare(self);
+        self.solver = solver;
     }
 
     pub fn center(&self) -> Vec2 {
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct OutputSpecifier(pub ElementIdx, pub OutputIdx);
 
@@ -10,10 +12,26 @@ pub enum IOSpecifier {
     Output(OutputSpecifier),
 }
 
+pub const MAX_BUS_WIDTH: u8 = 64;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Connection {
     pub from: OutputSpecifier,
     pub to: InputSpecifier,
+    // Number of bits carried by the connection
+    pub width: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionError {
+    InvalidWidth(u8),
+    WidthMismatch { expected: u8, found: u8 },
+    // A pin of an element which doesn't exist, or beyond the pins of its gate
+    InvalidPin(IOSpecifier),
+    // The input already has a driver, which only tri-state buffers can share
+    AlreadyDriven(OutputSpecifier),
+    // An output connected to an input of the same gate, which needs state in between
+    SelfLoop,
 }
 
 impl OutputSpecifier {
@@ -21,10 +39,49 @@ impl OutputSpecifier {
         Connection {
             from: self,
             to: other,
+            width: 1,
         }
     }
 }
 
+impl Connection {
+    pub fn with_width(self, width: u8) -> Self {
+        Self { width, ..self }
+    }
+
+    // Mask of the bits carried by this connection
+    pub fn mask(&self) -> u64 {
+        width_mask(self.width)
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionError::InvalidWidth(width) => write!(f, "{width} isn't a valid bus width"),
+            ConnectionError::WidthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Expected a {expected} bit connection, found {found} bits"
+                )
+            }
+            ConnectionError::InvalidPin(_) => write!(f, "The pin doesn't exist"),
+            ConnectionError::AlreadyDriven(_) => {
+                write!(f, "The input is already driven, disconnect it first")
+            }
+            ConnectionError::SelfLoop => write!(f, "Only stateful gates can drive themselves"),
+        }
+    }
+}
+
+pub fn width_mask(width: u8) -> u64 {
+    if width >= MAX_BUS_WIDTH {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ElementIdx(pub usize);
 
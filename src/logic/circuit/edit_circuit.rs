@@ -8,54 +8,153 @@ use common::bounds::Bounds;
 
 use super::{
     connection::{
-        ConnectionIdx, ElementIdx, IOSpecifier, InputIdx, InputSpecifier, OutputIdx,
-        OutputSpecifier,
+        Connection, ConnectionError, ConnectionIdx, ElementIdx, IOSpecifier, InputIdx,
+        InputSpecifier, OutputIdx, OutputSpecifier,
     },
     element::CircuitElement,
+    history::{self, Edit, History, Items, Wire},
 };
 
 use crate::logic::hit_test::HitTestResult;
 
-use super::Circuit;
+use super::{Circuit, ConnectionId, ElementId};
+
+// A selected item by ID, so it stays selected while other items are removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) enum Selected {
+    Element(ElementId),
+    Input(ElementId, InputIdx),
+    Output(ElementId, OutputIdx),
+    Connection(ConnectionId),
+}
 
 #[derive(Default)]
 pub struct ElementSelection {
-    pub(super) elements: HashSet<HitTestResult>,
+    pub(super) elements: HashSet<Selected>,
     pub(super) bound_select: Option<Bounds>,
 }
 
+// How many frames the reason for a refused connection stays on screen
+const REFUSAL_FRAMES: u32 = 180;
+
+// Why the last attempted connection wasn't made
+pub(crate) struct Refusal {
+    pub message: String,
+    // Where on the screen the connection was attempted
+    pub position: Vec2,
+    // The connection already driving the input, when that's why
+    pub driver: Option<ConnectionId>,
+    pub frames: u32,
+}
+
 #[derive(Default)]
 pub struct EditCircuit {
     pub(crate) circuit: Circuit,
     pub(crate) selection: ElementSelection,
     pub(crate) clipboard: Option<Circuit>,
+    pub(crate) history: History,
+    pub(crate) refusal: Option<Refusal>,
 }
 
 impl EditCircuit {
     fn remove_elements(&mut self, elements: ElementSelection) {
-        // Selection deletion must be applied as a single batch
-        // Since deleting single circuit elements invalidates connection pointers, the order of deletion matters
-        // 1. Delete individual connections
-        // 2. Delete connections to/from selected input/output nodes
-        // 3. Delete selected circuit elements
-
-        // 1. Delete individual connections
-        // Since we delete them all in a single batch, we don't need to worry about invalidating indices
-        self.circuit
-            .remove_many_connections(elements.connections().into_iter().collect::<HashSet<_>>());
+        // Everything to remove is captured by ID as a single edit, which removes the connections
+        // of the selected elements and pins along with them
+        let mut ids = vec![];
+        let mut wires = vec![];
+        for item in elements.elements {
+            match item.resolve(&self.circuit) {
+                Some(HitTestResult::Element(element)) => ids.push(self.circuit.element_id(element)),
+                Some(HitTestResult::IO(node)) => {
+                    wires.extend(
+                        self.circuit
+                            .connections
+                            .iter()
+                            .filter(|connection| match node {
+                                IOSpecifier::Input(input) => connection.to == input,
+                                IOSpecifier::Output(output) => connection.from == output,
+                            })
+                            .map(|connection| Wire::new(&self.circuit, *connection)),
+                    );
+                }
+                Some(HitTestResult::Connection(connection)) => {
+                    wires.push(Wire::new(&self.circuit, self.circuit[connection]))
+                }
+                None => {}
+            }
+        }
+
+        let items = Items::capture(&self.circuit, &ids, &wires);
+        if !items.is_empty() {
+            self.history.apply(&mut self.circuit, Edit::Remove(items));
+        }
+    }
 
-        // 2. Delete connections to/from selected input/output nodes
-        for node in elements.connection_nodes() {
-            self.circuit.remove_connections(node);
+    // Records the elements added after the given index, and every connection to them
+    fn record_added(&mut self, first: usize) {
+        let ids: Vec<_> = (first..self.circuit.elements.len())
+            .map(|index| self.circuit.element_id(ElementIdx(index)))
+            .collect();
+        let items = Items::capture(&self.circuit, &ids, &[]);
+        if !items.is_empty() {
+            self.history.record(Edit::Add(items));
         }
+    }
 
-        // 3. Delete selected circuit elements
-        // We must remove the elements in reverse index order to avoid invalidating indices
-        let mut gates: Vec<_> = elements.elements().into_iter().collect();
-        gates.sort_unstable_by_key(|v| v.0);
-        for element in gates.iter().rev() {
-            self.circuit.remove_gate(*element);
+    // Connects two pins unless they already are, which can be undone
+    pub fn connect(&mut self, connection: Connection) -> Result<(), ConnectionError> {
+        if self.circuit.connections.contains(&connection) {
+            return Ok(());
         }
+        self.circuit.add_connection(connection)?;
+        let items = Items {
+            elements: vec![],
+            connections: vec![Wire::new(&self.circuit, connection)],
+        };
+        self.history.record(Edit::Add(items));
+        Ok(())
+    }
+
+    // Consecutive moves are undone together until `finish_move` is called at the end of a drag
+    pub fn move_elements(&mut self, elements: &[ElementIdx], offset: Vec2) {
+        let ids: Vec<_> = elements
+            .iter()
+            .map(|&element| self.circuit.element_id(element))
+            .collect();
+        history::move_elements(&mut self.circuit, &ids, offset);
+        self.history.record_move(ids, offset);
+    }
+
+    pub fn finish_move(&mut self) {
+        self.history.finish_move();
+    }
+
+    pub fn delete_selection(&mut self) {
+        let selection = self.take_selection();
+        self.remove_elements(selection);
+    }
+
+    pub fn copy_selection(&mut self) {
+        let selection = self.take_selection();
+        self.clipboard = Some(self.extract_elements_into_circuit(selection));
+    }
+
+    pub fn paste(&mut self, position: Vec2) {
+        if let Some(clipboard) = self.clipboard.clone() {
+            let first = self.circuit.elements.len();
+            self.paste_circuit(clipboard, position);
+            self.record_added(first);
+        }
+    }
+
+    // Returns whether there was anything to undo
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(&mut self.circuit)
+    }
+
+    // Returns whether there was anything to redo
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(&mut self.circuit)
     }
 
     fn extract_elements_into_circuit(&mut self, elements: ElementSelection) -> Circuit {
@@ -63,16 +162,15 @@ impl EditCircuit {
 
         // First create a lookup table of remapped element IDs in the new circuit
         let mut circuit_indexes = HashMap::<ElementIdx, ElementIdx>::new();
-        for gate_idx in elements.elements() {
+        for gate_idx in elements.elements(&self.circuit) {
             let element = &self.circuit[gate_idx];
-            circuit_indexes.insert(
-                gate_idx,
-                res.add_gate(element.gate.clone(), element.position),
-            );
+            let new_idx = res.add_gate(element.gate.clone(), element.position);
+            res.set_delay(new_idx, element.delay);
+            circuit_indexes.insert(gate_idx, new_idx);
         }
 
         // Then we use this lookup table to add the remapped connections
-        for connection_idx in elements.connections() {
+        for connection_idx in elements.connections(&self.circuit) {
             let connection = self.circuit[connection_idx];
 
             let Some(from) = circuit_indexes
@@ -89,7 +187,8 @@ impl EditCircuit {
                 continue;
             };
 
-            res.add_connection(from.to(to));
+            res.add_connection(from.to(to).with_width(connection.width))
+                .unwrap();
         }
 
         res
@@ -107,23 +206,18 @@ impl EditCircuit {
 
         self.circuit
             .elements
-            .extend(
-                circuit
-                    .elements
-                    .into_iter()
-                    .map(|CircuitElement { gate, position }| CircuitElement {
-                        gate,
-                        position: position + offset,
-                    }),
-            );
+            .extend(circuit.elements.into_iter().map(|element| CircuitElement {
+                position: element.position + offset,
+                ..element
+            }));
         self.circuit.connections.extend(circuit.connections);
+        self.circuit.solver.invalidate();
 
         self.selection.clear();
 
         for idx in elements_len..self.circuit.elements.len() {
             self.selection
-                .elements
-                .insert(HitTestResult::Element(ElementIdx(idx)));
+                .insert(&self.circuit, HitTestResult::Element(ElementIdx(idx)));
         }
     }
 
@@ -219,7 +313,7 @@ impl EditCircuit {
 
     pub fn take_selection(&mut self) -> ElementSelection {
         let mut selection = ElementSelection::default();
-        // Replace the existing selection, since after deletion the indices will be invalid
+        // The taken items are about to be removed or copied, so nothing stays selected
         std::mem::swap(&mut self.selection, &mut selection);
         selection
     }
@@ -228,23 +322,49 @@ impl EditCircuit {
         let selection = self.take_selection();
         let new_circuit = self.extract_elements_into_circuit(selection);
         let position = new_circuit.center();
+        let first = self.circuit.elements.len();
         self.circuit.add_gate(new_circuit.embed().into(), position);
+        self.record_added(first);
     }
 
     pub fn handle_inputs(&mut self, input_state: &InputState, game_input: &mut GameInput) {
+        if let Some(refusal) = &mut self.refusal {
+            refusal.frames -= 1;
+            if refusal.frames == 0 {
+                self.refusal = None;
+            }
+        }
+
         let x_key = winit::keyboard::Key::Character("x".into());
         let c_key = winit::keyboard::Key::Character("c".into());
         let v_key = winit::keyboard::Key::Character("v".into());
         let z_key = winit::keyboard::Key::Character("z".into());
+        let grow_key = winit::keyboard::Key::Character("]".into());
+        let shrink_key = winit::keyboard::Key::Character("[".into());
+        let pause_key = winit::keyboard::Key::Character("t".into());
+        let pulse_key = winit::keyboard::Key::Character("p".into());
 
         let shift_key = winit::keyboard::Key::Named(winit::keyboard::NamedKey::Shift);
+        let control_key = winit::keyboard::Key::Named(winit::keyboard::NamedKey::Control);
 
         let delete_pressed = input_state.keyboard.pressed(x_key);
         let copy_pressed = input_state.keyboard.pressed(c_key);
         let paste_pressed = input_state.keyboard.pressed(v_key);
-        let embed_pressed = input_state.keyboard.pressed(z_key);
+        let z_pressed = input_state.keyboard.pressed(z_key)
+            || input_state
+                .keyboard
+                .pressed(winit::keyboard::Key::Character("Z".into()));
+        let grow_pressed = input_state.keyboard.pressed(grow_key);
+        let shrink_pressed = input_state.keyboard.pressed(shrink_key);
+        let pause_pressed = input_state.keyboard.pressed(pause_key);
+        let pulse_pressed = input_state.keyboard.pressed(pulse_key);
 
         let shift_down = input_state.keyboard.down(shift_key);
+        let control_down = input_state.keyboard.down(control_key);
+
+        let embed_pressed = z_pressed && !control_down;
+        let undo_pressed = z_pressed && control_down && !shift_down;
+        let redo_pressed = z_pressed && control_down && shift_down;
 
         let left_click = input_state.left_mouse.released && !input_state.dragging();
 
@@ -278,66 +398,117 @@ impl EditCircuit {
             } if input_state.left_mouse.released => match (a, b) {
                 (IOSpecifier::Input(input), IOSpecifier::Output(output))
                 | (IOSpecifier::Output(output), IOSpecifier::Input(input)) => {
-                    self.circuit.add_connection(output.to(*input));
+                    let width = self
+                        .circuit
+                        .pin_width(*output)
+                        .or(self.circuit.pin_width(*input))
+                        .unwrap_or(1);
+
+                    if let Err(err) = self.connect(output.to(*input).with_width(width)) {
+                        let driver = match err {
+                            ConnectionError::AlreadyDriven(driver) => self
+                                .circuit
+                                .connections
+                                .iter()
+                                .position(|connection| {
+                                    connection.from == driver && connection.to == *input
+                                })
+                                .map(|index| self.circuit.connection_id(ConnectionIdx(index))),
+                            _ => None,
+                        };
+                        self.refusal = Some(Refusal {
+                            message: err.to_string(),
+                            position: input_state.mouse_screen_position,
+                            driver,
+                            frames: REFUSAL_FRAMES,
+                        });
+                    }
                 }
                 _ => {}
             },
 
             GameInput { .. } if box_select && input_state.left_mouse.released => {
                 if let Some(bounds) = self.selection.bound_select {
-                    self.selection.elements = self.hit_test_bounds(bounds);
+                    self.selection.clear();
+                    for hit in self.hit_test_bounds(bounds) {
+                        self.selection.insert(&self.circuit, hit);
+                    }
                     self.selection.bound_select = None;
                 }
             }
             GameInput { hot: Some(res), .. } if shift_down && left_click => {
-                self.selection.toggle(*res);
+                self.selection.toggle(&self.circuit, *res);
                 println!("Toggling Selection");
             }
             GameInput {
                 active: Some(elm), ..
             } if input_state.dragging() => {
-                if self.selection.contains(*elm) {
-                    for item in self.selection.elements.iter() {
-                        match item {
-                            HitTestResult::Element(elm) => {
-                                self.circuit.elements[elm.0].position +=
-                                    input_state.mouse_world_position_delta
-                            }
-                            HitTestResult::IO(_) => {}
-                            HitTestResult::Connection(_) => {}
-                        }
-                    }
+                let elements: Vec<_> = if self.selection.contains(&self.circuit, *elm) {
+                    self.selection.elements(&self.circuit).collect()
                 } else if let HitTestResult::Element(elm) = elm {
-                    self.circuit[*elm].position += input_state.mouse_world_position_delta;
+                    vec![*elm]
+                } else {
+                    vec![]
+                };
+                if !elements.is_empty() {
+                    self.move_elements(&elements, input_state.mouse_world_position_delta);
                 }
             }
+            GameInput { .. } if undo_pressed => {
+                self.undo();
+            }
+            GameInput { .. } if redo_pressed => {
+                self.redo();
+            }
             GameInput { .. } if delete_pressed => {
-                let selection = self.take_selection();
-                self.remove_elements(selection);
+                self.delete_selection();
             }
             GameInput { .. } if copy_pressed => {
-                let selection = self.take_selection();
-                self.clipboard = Some(self.extract_elements_into_circuit(selection));
+                self.copy_selection();
             }
             GameInput { .. } if paste_pressed => {
-                if let Some(clipboard) = self.clipboard.clone() {
-                    self.paste_circuit(clipboard, input_state.mouse_world_position);
-                }
+                self.paste(input_state.mouse_world_position);
             }
             GameInput { .. } if embed_pressed => {
                 self.embed_selection();
             }
+            GameInput { .. } if grow_pressed || shrink_pressed => {
+                let elements: Vec<_> = self.selection.elements(&self.circuit).collect();
+                for element in elements {
+                    let Some(&mut inputs) = self.circuit[element].gate.fan_in_mut() else {
+                        continue;
+                    };
+                    let inputs = if grow_pressed {
+                        inputs.saturating_add(1)
+                    } else {
+                        inputs.saturating_sub(1)
+                    };
+                    self.circuit.set_fan_in(element, inputs);
+                }
+            }
+            GameInput { .. } if pause_pressed || pulse_pressed => {
+                let elements: Vec<_> = self.selection.elements(&self.circuit).collect();
+                for element in elements {
+                    if pulse_pressed {
+                        self.circuit.pulse_clock(element);
+                    } else {
+                        let paused = self.circuit.clock_paused(element);
+                        self.circuit.set_clock_paused(element, !paused);
+                    }
+                }
+            }
             GameInput {
                 active: Some(res), ..
             } if left_click => {
                 self.selection.clear();
-                self.selection.toggle(*res);
+                self.selection.toggle(&self.circuit, *res);
             }
             _ => {}
         }
 
         if !input_state.left_mouse.down {
             self.selection.bound_select = None;
+            self.finish_move();
         }
 
         if clear_selection {
@@ -352,16 +523,54 @@ impl From<Circuit> for EditCircuit {
             circuit: value,
             selection: ElementSelection::default(),
             clipboard: None,
+            history: History::default(),
+            refusal: None,
+        }
+    }
+}
+
+impl Selected {
+    fn new(circuit: &Circuit, hit: HitTestResult) -> Self {
+        match hit {
+            HitTestResult::Element(element) => Self::Element(circuit.element_id(element)),
+            HitTestResult::IO(IOSpecifier::Input(InputSpecifier(element, input))) => {
+                Self::Input(circuit.element_id(element), input)
+            }
+            HitTestResult::IO(IOSpecifier::Output(OutputSpecifier(element, output))) => {
+                Self::Output(circuit.element_id(element), output)
+            }
+            HitTestResult::Connection(connection) => {
+                Self::Connection(circuit.connection_id(connection))
+            }
         }
     }
+
+    // The item's current position in the circuit, or None if it has been removed
+    fn resolve(self, circuit: &Circuit) -> Option<HitTestResult> {
+        Some(match self {
+            Self::Element(id) => HitTestResult::Element(circuit.element_idx(id)?),
+            Self::Input(id, input) => {
+                HitTestResult::IO(InputSpecifier(circuit.element_idx(id)?, input).into())
+            }
+            Self::Output(id, output) => {
+                HitTestResult::IO(OutputSpecifier(circuit.element_idx(id)?, output).into())
+            }
+            Self::Connection(id) => HitTestResult::Connection(circuit.connection_idx(id)?),
+        })
+    }
 }
 
 impl ElementSelection {
-    pub fn contains(&self, element: HitTestResult) -> bool {
-        self.elements.contains(&element)
+    pub fn contains(&self, circuit: &Circuit, element: HitTestResult) -> bool {
+        self.elements.contains(&Selected::new(circuit, element))
     }
 
-    pub fn toggle(&mut self, element: HitTestResult) {
+    pub fn insert(&mut self, circuit: &Circuit, element: HitTestResult) {
+        self.elements.insert(Selected::new(circuit, element));
+    }
+
+    pub fn toggle(&mut self, circuit: &Circuit, element: HitTestResult) {
+        let element = Selected::new(circuit, element);
         if self.elements.contains(&element) {
             self.elements.remove(&element);
         } else {
@@ -373,30 +582,43 @@ impl ElementSelection {
         self.elements.clear();
     }
 
-    pub fn connections(&self) -> impl IntoIterator<Item = ConnectionIdx> + '_ {
-        self.elements.iter().filter_map(|hit| {
+    // The selected items which still exist, by their current positions
+    fn resolved<'a>(&'a self, circuit: &'a Circuit) -> impl Iterator<Item = HitTestResult> + 'a {
+        self.elements
+            .iter()
+            .filter_map(|selected| selected.resolve(circuit))
+    }
+
+    pub fn connections<'a>(
+        &'a self,
+        circuit: &'a Circuit,
+    ) -> impl Iterator<Item = ConnectionIdx> + 'a {
+        self.resolved(circuit).filter_map(|hit| {
             if let HitTestResult::Connection(idx) = hit {
-                Some(*idx)
+                Some(idx)
             } else {
                 None
             }
         })
     }
 
-    pub fn connection_nodes(&self) -> impl IntoIterator<Item = IOSpecifier> + '_ {
-        self.elements.iter().filter_map(|hit| {
+    pub fn connection_nodes<'a>(
+        &'a self,
+        circuit: &'a Circuit,
+    ) -> impl Iterator<Item = IOSpecifier> + 'a {
+        self.resolved(circuit).filter_map(|hit| {
             if let HitTestResult::IO(node) = hit {
-                Some(*node)
+                Some(node)
             } else {
                 None
             }
         })
     }
 
-    pub fn elements(&self) -> impl IntoIterator<Item = ElementIdx> + '_ {
-        self.elements.iter().filter_map(|hit| {
+    pub fn elements<'a>(&'a self, circuit: &'a Circuit) -> impl Iterator<Item = ElementIdx> + 'a {
+        self.resolved(circuit).filter_map(|hit| {
             if let HitTestResult::Element(element) = hit {
-                Some(*element)
+                Some(element)
             } else {
                 None
             }
@@ -7,6 +7,39 @@ use crate::logic::gate::Gate;
 pub struct CircuitElement {
     pub gate: Gate,
     pub position: Vec2,
+    pub delay: Delay,
+}
+
+// Propagation delay of an element's outputs in solver steps, used by the timing solver
+// Rising bits take `rise` steps to appear and falling bits take `fall` steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delay {
+    pub rise: u32,
+    pub fall: u32,
+}
+
+impl Delay {
+    // Delays are at least one step, so a gate never sees its own output within a step
+    pub fn new(rise: u32, fall: u32) -> Self {
+        Self {
+            rise: rise.max(1),
+            fall: fall.max(1),
+        }
+    }
+
+    pub fn uniform(ticks: u32) -> Self {
+        Self::new(ticks, ticks)
+    }
+
+    pub fn max(&self) -> u32 {
+        self.rise.max(self.fall)
+    }
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Self::uniform(1)
+    }
 }
 
 impl CircuitElement {
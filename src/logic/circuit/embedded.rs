@@ -1,21 +1,49 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
 use glam::Vec2;
 
-use crate::logic::gate::Gate;
+use crate::logic::{gate::Gate, solver::SolverState};
 
 use super::{
     connection::{ElementIdx, InputIdx, InputSpecifier, OutputIdx, OutputSpecifier},
     Circuit,
 };
 
+static NEXT_COMPONENT_ID: AtomicU64 = AtomicU64::new(0);
+
+// A circuit definition which any number of embedded instances share
+// Definitions are never modified, a component is changed by redefining it and updating its instances
+#[derive(Debug)]
+pub struct Component {
+    // Shared by every version of the component
+    id: u64,
+    name: Option<String>,
+    circuit: Circuit,
+
+    // Input and Output gates of the circuit, in pin order
+    inputs: Vec<ElementIdx>,
+    outputs: Vec<ElementIdx>,
+
+    input_widths: Vec<u8>,
+    output_widths: Vec<u8>,
+
+    input_names: Vec<Option<String>>,
+    output_names: Vec<Option<String>>,
+}
+
+// An instance of a component, whose state is kept by the solver of the circuit it's placed in
 #[derive(Clone, Debug)]
 pub struct EmbeddedCircuit {
-    circuit: Box<Circuit>,
-
-    output_idx: ElementIdx,
-    output_count: usize,
+    component: Arc<Component>,
+}
 
-    input_idx: ElementIdx,
-    input_count: usize,
+// A bit for each of a gate's pins, where bus gates can have a pin for each of 64 bits
+fn pin_mask(count: usize) -> u64 {
+    1u64.checked_shl(count as u32)
+        .map_or(u64::MAX, |bit| bit - 1)
 }
 
 fn disconnected_outputs(circuit: &Circuit) -> Vec<OutputSpecifier> {
@@ -23,7 +51,7 @@ fn disconnected_outputs(circuit: &Circuit) -> Vec<OutputSpecifier> {
     let mut output_mask: Vec<u64> = circuit
         .elements
         .iter()
-        .map(|elm| (1 << elm.gate.output_count()) - 1)
+        .map(|elm| pin_mask(elm.gate.output_count()))
         .collect();
 
     for conn in &circuit.connections {
@@ -47,7 +75,7 @@ fn disconnected_inputs(circuit: &Circuit) -> Vec<InputSpecifier> {
     let mut input_mask: Vec<u64> = circuit
         .elements
         .iter()
-        .map(|elm| (1 << elm.gate.input_count()) - 1)
+        .map(|elm| pin_mask(elm.gate.input_count()))
         .collect();
 
     for conn in &circuit.connections {
@@ -66,42 +94,193 @@ fn disconnected_inputs(circuit: &Circuit) -> Vec<InputSpecifier> {
     res
 }
 
+// Circuits without any Input or Output gates get one for each disconnected pin
+// Ports are stacked in element order, beside the circuit, so their pin order follows it
+fn add_ports_for_disconnected_pins(circuit: &mut Circuit) {
+    let inputs = disconnected_inputs(circuit);
+    let outputs = disconnected_outputs(circuit);
+
+    let input_widths: Vec<u8> = inputs
+        .iter()
+        .map(|input| circuit.pin_width(*input).unwrap_or(1))
+        .collect();
+    let output_widths: Vec<u8> = outputs
+        .iter()
+        .map(|output| circuit.pin_width(*output).unwrap_or(1))
+        .collect();
+
+    let (min, max) = circuit.elements.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), element| (min.min(element.position), max.max(element.position)),
+    );
+    let (min, max) = if circuit.elements.is_empty() {
+        (Vec2::ZERO, Vec2::ZERO)
+    } else {
+        (min, max)
+    };
+    let port_position = |x: f32, index: usize| Vec2::new(x, max.y - index as f32 * 0.5);
+
+    for (index, (input, width)) in inputs.iter().zip(&input_widths).enumerate() {
+        let port = circuit.add_gate(Gate::Input(None), port_position(min.x - 1.0, index));
+        circuit
+            .add_connection(port.output(0).to(*input).with_width(*width))
+            .unwrap();
+    }
+
+    for (index, (output, width)) in outputs.iter().zip(&output_widths).enumerate() {
+        let port = circuit.add_gate(Gate::Output(None), port_position(max.x + 1.0, index));
+        circuit
+            .add_connection(output.to(port.input(0)).with_width(*width))
+            .unwrap();
+    }
+}
+
+impl Component {
+    pub fn new(circuit: Circuit) -> Self {
+        let id = NEXT_COMPONENT_ID.fetch_add(1, Ordering::Relaxed);
+        Self::with_id(id, None, circuit)
+    }
+
+    pub fn named(name: impl Into<String>, circuit: Circuit) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Self::new(circuit)
+        }
+    }
+
+    // A new version of this component, which its instances can be updated to
+    pub fn redefine(&self, circuit: Circuit) -> Self {
+        Self::with_id(self.id, self.name.clone(), circuit)
+    }
+
+    fn with_id(id: u64, name: Option<String>, circuit: Circuit) -> Self {
+        // Only the structure is shared, instances keep their own solver state
+        let mut circuit = Circuit {
+            elements: circuit.elements,
+            connections: circuit.connections,
+            ..Default::default()
+        };
+
+        let has_ports = circuit
+            .elements
+            .iter()
+            .any(|element| matches!(element.gate, Gate::Input(_) | Gate::Output(_)));
+        if !has_ports {
+            add_ports_for_disconnected_pins(&mut circuit);
+        }
+
+        let inputs = circuit.input_ports();
+        let outputs = circuit.output_ports();
+
+        let input_widths = inputs
+            .iter()
+            .map(|port| circuit.pin_width(port.output(0)).unwrap_or(1))
+            .collect();
+        let output_widths = outputs
+            .iter()
+            .map(|port| circuit.pin_width(port.input(0)).unwrap_or(1))
+            .collect();
+
+        let port_name = |port: &ElementIdx| circuit[*port].gate.port_name().map(str::to_string);
+        let input_names = inputs.iter().map(port_name).collect();
+        let output_names = outputs.iter().map(port_name).collect();
+
+        Self {
+            id,
+            name,
+            circuit,
+            inputs,
+            outputs,
+            input_widths,
+            output_widths,
+            input_names,
+            output_names,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // The definition, including any ports added for disconnected pins
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    // Input and Output gates of the definition, in pin order
+    pub fn inputs(&self) -> &[ElementIdx] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[ElementIdx] {
+        &self.outputs
+    }
+}
+
 impl EmbeddedCircuit {
+    pub fn new(circuit: Circuit) -> Self {
+        Self::instance(Arc::new(Component::new(circuit)))
+    }
+
+    pub fn instance(component: Arc<Component>) -> Self {
+        Self { component }
+    }
+
+    pub fn component(&self) -> &Arc<Component> {
+        &self.component
+    }
+
+    pub(crate) fn set_component(&mut self, component: Arc<Component>) {
+        self.component = component;
+    }
+
     pub fn input_count(&self) -> usize {
-        self.input_count
+        self.component.inputs.len()
     }
     pub fn output_count(&self) -> usize {
-        self.output_count
+        self.component.outputs.len()
+    }
+
+    pub fn input_widths(&self) -> &[u8] {
+        &self.component.input_widths
+    }
+
+    pub fn output_widths(&self) -> &[u8] {
+        &self.component.output_widths
     }
 
-    pub fn new(mut circuit: Circuit) -> Option<Self> {
-        let inputs = disconnected_inputs(&circuit);
-        let outputs = disconnected_outputs(&circuit);
+    pub fn input_names(&self) -> &[Option<String>] {
+        &self.component.input_names
+    }
 
-        let input_elm = circuit.add_gate(Gate::Buf, Vec2::ZERO);
-        let output_elm = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+    pub fn output_names(&self) -> &[Option<String>] {
+        &self.component.output_names
+    }
 
-        for (id, input) in inputs.iter().enumerate() {
-            circuit.add_connection(OutputSpecifier(input_elm, OutputIdx(id)).to(*input));
+    // A copy of the component's definition, which can be edited without affecting any instances
+    pub fn definition(&self) -> Circuit {
+        Circuit {
+            elements: self.component.circuit.elements.clone(),
+            connections: self.component.circuit.connections.clone(),
+            ..Default::default()
         }
+    }
 
-        for (id, output) in outputs.iter().enumerate() {
-            circuit.add_connection(output.to(InputSpecifier(output_elm, InputIdx(id))));
+    pub fn eval(&self, state: &mut SolverState, inputs: &[u64], outputs: &mut [u64]) {
+        let component = &self.component;
+        state.prepare(&component.circuit);
+        for (port, value) in component.inputs.iter().zip(inputs) {
+            state.drive_output(port.output(0), *value);
         }
 
-        Some(Self {
-            circuit: Box::new(circuit),
-            output_idx: output_elm,
-            output_count: outputs.len(),
-            input_idx: input_elm,
-            input_count: inputs.len(),
-        })
-    }
+        *state = std::mem::take(state).step(&component.circuit);
 
-    pub fn eval(&mut self, inputs: u64) -> u64 {
-        self.circuit.right_size_solver();
-        self.circuit.solver.output_results.inner[self.input_idx.0] = inputs;
-        self.circuit.step();
-        self.circuit.solver.output_results.inner[self.output_idx.0]
+        for (port, value) in component.outputs.iter().zip(outputs) {
+            *value = state.output_results.read_output(port.output(0));
+        }
     }
 }
@@ -7,29 +7,52 @@ use super::Circuit;
 impl Circuit {
     pub fn full_adder() -> Self {
         let mut adder = Circuit::default();
-        let in_a = adder.add_gate(Gate::Buf, Vec2::ZERO).output(0);
-        let in_b = adder.add_gate(Gate::Buf, Vec2::ZERO).output(0);
-        let carry = adder.add_gate(Gate::Buf, Vec2::ZERO).output(0);
-
-        let a_xor_b = adder.add_gate(Gate::Xor, Vec2::ZERO);
-        adder.add_connection(in_a.to(a_xor_b.input(0)));
-        adder.add_connection(in_b.to(a_xor_b.input(1)));
-
-        let a_and_b = adder.add_gate(Gate::And, Vec2::ZERO);
-        adder.add_connection(in_a.to(a_and_b.input(0)));
-        adder.add_connection(in_b.to(a_and_b.input(1)));
+        let input = |name: &str| Gate::Input(Some(name.into()));
+        let in_a = adder.add_gate(input("a"), Vec2::new(0.0, 1.0)).output(0);
+        let in_b = adder.add_gate(input("b"), Vec2::new(0.0, 0.0)).output(0);
+        let carry = adder
+            .add_gate(input("carry"), Vec2::new(0.0, -1.0))
+            .output(0);
+
+        let a_xor_b = adder.add_gate(Gate::Xor(2), Vec2::ZERO);
+        adder.add_connection(in_a.to(a_xor_b.input(0))).unwrap();
+        adder.add_connection(in_b.to(a_xor_b.input(1))).unwrap();
+
+        let a_and_b = adder.add_gate(Gate::And(2), Vec2::ZERO);
+        adder.add_connection(in_a.to(a_and_b.input(0))).unwrap();
+        adder.add_connection(in_b.to(a_and_b.input(1))).unwrap();
+
+        let sum = adder.add_gate(Gate::Xor(2), Vec2::ZERO);
+        adder
+            .add_connection(a_xor_b.output(0).to(sum.input(0)))
+            .unwrap();
+        adder.add_connection(carry.to(sum.input(1))).unwrap();
 
-        let sum = adder.add_gate(Gate::Xor, Vec2::ZERO);
-        adder.add_connection(a_xor_b.output(0).to(sum.input(0)));
-        adder.add_connection(carry.to(sum.input(1)));
+        let pre_carry_out = adder.add_gate(Gate::And(2), Vec2::ZERO);
+        adder
+            .add_connection(a_xor_b.output(0).to(pre_carry_out.input(0)))
+            .unwrap();
+        adder
+            .add_connection(carry.to(pre_carry_out.input(1)))
+            .unwrap();
 
-        let pre_carry_out = adder.add_gate(Gate::And, Vec2::ZERO);
-        adder.add_connection(a_xor_b.output(0).to(pre_carry_out.input(0)));
-        adder.add_connection(carry.to(pre_carry_out.input(1)));
+        let carry_out = adder.add_gate(Gate::Or(2), Vec2::ZERO);
+        adder
+            .add_connection(a_and_b.output(0).to(carry_out.input(0)))
+            .unwrap();
+        adder
+            .add_connection(pre_carry_out.output(0).to(carry_out.input(1)))
+            .unwrap();
 
-        let carry_out = adder.add_gate(Gate::Or, Vec2::ZERO);
-        adder.add_connection(a_and_b.output(0).to(carry_out.input(0)));
-        adder.add_connection(pre_carry_out.output(0).to(carry_out.input(1)));
+        let output = |name: &str| Gate::Output(Some(name.into()));
+        let sum_out = adder.add_gate(output("sum"), Vec2::new(4.0, 0.5));
+        adder
+            .add_connection(sum.output(0).to(sum_out.input(0)))
+            .unwrap();
+        let carry_out_port = adder.add_gate(output("carry_out"), Vec2::new(4.0, -0.5));
+        adder
+            .add_connection(carry_out.output(0).to(carry_out_port.input(0)))
+            .unwrap();
 
         adder
     }
@@ -37,12 +60,18 @@ impl Circuit {
     pub fn adder_8_bit() -> Self {
         let mut circuit = Circuit::default();
         let carry = circuit.add_gate(Gate::Const(false), Vec2::ZERO).output(0);
-        let mut prev_adder = circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
-        circuit.add_connection(carry.to(prev_adder.input(2)));
+        // Every adder is an instance of the same component
+        let full_adder = Circuit::full_adder().embed();
+        let mut prev_adder = circuit.add_gate(full_adder.clone().into(), Vec2::ZERO);
+        circuit
+            .add_connection(carry.to(prev_adder.input(2)))
+            .unwrap();
 
         for _ in 0..7 {
-            let adder = circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
-            circuit.add_connection(prev_adder.output(1).to(adder.input(2)));
+            let adder = circuit.add_gate(full_adder.clone().into(), Vec2::ZERO);
+            circuit
+                .add_connection(prev_adder.output(1).to(adder.input(2)))
+                .unwrap();
             prev_adder = adder;
         }
 
@@ -55,14 +84,22 @@ impl Circuit {
         let a = circuit.add_gate(Gate::Buf, Vec2::new(0.0, 0.0));
         let b = circuit.add_gate(Gate::Not, Vec2::new(0.0, 1.0));
 
-        let xor = circuit.add_gate(Gate::Xor, Vec2::new(3.0, 0.0));
-        let and = circuit.add_gate(Gate::And, Vec2::new(3.0, 1.0));
+        let xor = circuit.add_gate(Gate::Xor(2), Vec2::new(3.0, 0.0));
+        let and = circuit.add_gate(Gate::And(2), Vec2::new(3.0, 1.0));
 
-        circuit.add_connection(a.output(0).to(xor.input(0)));
-        circuit.add_connection(a.output(0).to(and.input(0)));
+        circuit
+            .add_connection(a.output(0).to(xor.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(a.output(0).to(and.input(0)))
+            .unwrap();
 
-        circuit.add_connection(b.output(0).to(xor.input(1)));
-        circuit.add_connection(b.output(0).to(and.input(1)));
+        circuit
+            .add_connection(b.output(0).to(xor.input(1)))
+            .unwrap();
+        circuit
+            .add_connection(b.output(0).to(and.input(1)))
+            .unwrap();
 
         circuit
     }
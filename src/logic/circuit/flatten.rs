@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::logic::gate::Gate;
+
+use super::{
+    connection::{ElementIdx, InputSpecifier, OutputIdx, OutputSpecifier},
+    embedded::Component,
+    Circuit, ElementId,
+};
+
+// A circuit with every embedded circuit inlined, along with where each of its elements came from
+// The Input and Output gates of embedded circuits are replaced by direct connections, so crossing
+// into an embedded circuit doesn't add a step of delay
+#[derive(Default, Clone, Debug)]
+pub struct FlatCircuit {
+    pub(crate) circuit: Circuit,
+    // The instances leading to each element, followed by the element within the innermost circuit
+    paths: Vec<Vec<ElementIdx>>,
+    // The same paths by the IDs of the elements, which stay the same when other elements are edited
+    id_paths: Vec<Vec<ElementId>>,
+    elements: HashMap<Vec<ElementIdx>, ElementIdx>,
+    // The flat outputs driving each output pin of the top level elements
+    root_sources: Vec<Vec<Vec<OutputSpecifier>>>,
+    // The same for the ports and instances within embedded circuits, which aren't flat elements
+    nested_sources: HashMap<Vec<ElementIdx>, Vec<Vec<OutputSpecifier>>>,
+}
+
+impl FlatCircuit {
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    pub fn path(&self, element: ElementIdx) -> &[ElementIdx] {
+        &self.paths[element.0]
+    }
+
+    pub fn id_path(&self, element: ElementIdx) -> &[ElementId] {
+        &self.id_paths[element.0]
+    }
+
+    // The flat element for a path of instances ending in an element which isn't inlined
+    pub fn element(&self, path: &[ElementIdx]) -> Option<ElementIdx> {
+        self.elements.get(path).copied()
+    }
+
+    // The flat outputs which together drive an output pin of the top level circuit
+    // Several drivers of the same net combine as a wired OR
+    pub fn sources(&self, OutputSpecifier(element, pin): OutputSpecifier) -> &[OutputSpecifier] {
+        &self.root_sources[element.0][pin.0]
+    }
+
+    // The flat outputs which drive an output pin of an inlined element within an embedded circuit
+    pub fn nested_sources(
+        &self,
+        path: &[ElementIdx],
+        pin: OutputIdx,
+    ) -> Option<&[OutputSpecifier]> {
+        Some(self.nested_sources.get(path)?.get(pin.0)?)
+    }
+}
+
+impl Circuit {
+    pub fn flatten(&self) -> FlatCircuit {
+        let mut flattener = Flattener::default();
+        flattener.add_scope(self, vec![], vec![], None);
+        flattener.connect();
+
+        let root_sources = self
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(element, circuit_element)| {
+                (0..circuit_element.gate.output_count())
+                    .map(|pin| {
+                        flattener.resolve(0, ElementIdx(element).output(pin), &mut HashSet::new())
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut nested_sources = HashMap::new();
+        for (index, scope) in flattener.scopes.iter().enumerate().skip(1) {
+            for (element, circuit_element) in scope.circuit.elements.iter().enumerate() {
+                if scope.flat[element].is_some() {
+                    continue;
+                }
+                let sources = (0..circuit_element.gate.output_count())
+                    .map(|pin| {
+                        let output = ElementIdx(element).output(pin);
+                        flattener.resolve(index, output, &mut HashSet::new())
+                    })
+                    .collect();
+                let mut path = scope.path.clone();
+                path.push(ElementIdx(element));
+                nested_sources.insert(path, sources);
+            }
+        }
+
+        FlatCircuit {
+            root_sources,
+            nested_sources,
+            ..flattener.flat
+        }
+    }
+
+    pub fn has_embedded(&self) -> bool {
+        self.elements
+            .iter()
+            .any(|element| matches!(element.gate, Gate::Embedded(_)))
+    }
+}
+
+// One circuit in the hierarchy being flattened
+struct Scope<'a> {
+    circuit: &'a Circuit,
+    // The instances leading to this circuit
+    path: Vec<ElementIdx>,
+    // The scope and instance this circuit is embedded by, and its definition
+    parent: Option<(usize, ElementIdx, &'a Component)>,
+    // The flat element of each element, unless it's an embedded instance or one of its ports
+    flat: Vec<Option<ElementIdx>>,
+    children: Vec<Option<usize>>,
+}
+
+#[derive(Default)]
+struct Flattener<'a> {
+    scopes: Vec<Scope<'a>>,
+    flat: FlatCircuit,
+}
+
+impl<'a> Flattener<'a> {
+    fn add_scope(
+        &mut self,
+        circuit: &'a Circuit,
+        path: Vec<ElementIdx>,
+        id_path: Vec<ElementId>,
+        parent: Option<(usize, ElementIdx, &'a Component)>,
+    ) -> usize {
+        let scope = self.scopes.len();
+        self.scopes.push(Scope {
+            circuit,
+            path: path.clone(),
+            parent,
+            flat: vec![None; circuit.elements.len()],
+            children: vec![None; circuit.elements.len()],
+        });
+
+        for (index, element) in circuit.elements.iter().enumerate() {
+            let mut element_path = path.clone();
+            element_path.push(ElementIdx(index));
+            let mut element_id_path = id_path.clone();
+            element_id_path.push(circuit.element_id(ElementIdx(index)));
+
+            match &element.gate {
+                Gate::Embedded(embed) => {
+                    let component = embed.component().as_ref();
+                    let child = self.add_scope(
+                        component.circuit(),
+                        element_path,
+                        element_id_path,
+                        Some((scope, ElementIdx(index), component)),
+                    );
+                    self.scopes[scope].children[index] = Some(child);
+                }
+                // The ports of embedded circuits become connections
+                Gate::Input(_) | Gate::Output(_) if parent.is_some() => {}
+                gate => {
+                    let flat = &mut self.flat;
+                    let flat_index = flat.circuit.add_gate(gate.clone(), element.position);
+                    flat.circuit[flat_index].delay = element.delay;
+                    flat.paths.push(element_path.clone());
+                    flat.id_paths.push(element_id_path);
+                    flat.elements.insert(element_path, flat_index);
+                    self.scopes[scope].flat[index] = Some(flat_index);
+                }
+            }
+        }
+
+        scope
+    }
+
+    // Connects every flat input to the flat outputs which drive it through the hierarchy
+    fn connect(&mut self) {
+        let mut connections = vec![];
+        let mut seen = HashSet::new();
+        for (index, scope) in self.scopes.iter().enumerate() {
+            for connection in &scope.circuit.connections {
+                let Some(to) = scope.flat[connection.to.0 .0] else {
+                    continue;
+                };
+
+                let to = InputSpecifier(to, connection.to.1);
+                for from in self.resolve(index, connection.from, &mut HashSet::new()) {
+                    let connection = from.to(to).with_width(connection.width);
+                    if seen.insert(connection) {
+                        connections.push(connection);
+                    }
+                }
+            }
+        }
+        self.flat.circuit.connections = connections;
+    }
+
+    // The flat outputs which drive an output within a scope
+    fn resolve(
+        &self,
+        scope: usize,
+        output: OutputSpecifier,
+        visiting: &mut HashSet<(usize, OutputSpecifier)>,
+    ) -> Vec<OutputSpecifier> {
+        let current = &self.scopes[scope];
+        let OutputSpecifier(element, pin) = output;
+        if let Some(flat) = current.flat[element.0] {
+            return vec![OutputSpecifier(flat, pin)];
+        }
+
+        // Ports wired in a loop without any gates aren't driven by anything
+        if !visiting.insert((scope, output)) {
+            return vec![];
+        }
+
+        let sources = if let Some(child) = current.children[element.0] {
+            let (_, _, component) = self.scopes[child].parent.unwrap();
+            let port = component.outputs()[pin.0];
+            self.drivers(child, port.input(0), visiting)
+        } else {
+            match &current.circuit[element].gate {
+                Gate::Input(_) => {
+                    let (parent, instance, component) = current.parent.unwrap();
+                    let port = component.inputs().iter().position(|&port| port == element);
+                    self.drivers(parent, instance.input(port.unwrap()), visiting)
+                }
+                // An Output gate passes on whatever drives it
+                _ => self.drivers(scope, element.input(0), visiting),
+            }
+        };
+
+        visiting.remove(&(scope, output));
+        sources
+    }
+
+    fn drivers(
+        &self,
+        scope: usize,
+        input: InputSpecifier,
+        visiting: &mut HashSet<(usize, OutputSpecifier)>,
+    ) -> Vec<OutputSpecifier> {
+        self.scopes[scope]
+            .circuit
+            .connections
+            .iter()
+            .filter(|connection| connection.to == input)
+            .flat_map(|connection| self.resolve(scope, connection.from, visiting))
+            .collect()
+    }
+}
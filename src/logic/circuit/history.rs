@@ -0,0 +1,301 @@
+use glam::Vec2;
+
+use super::{
+    connection::{
+        Connection, ConnectionIdx, ElementIdx, InputIdx, InputSpecifier, OutputIdx, OutputSpecifier,
+    },
+    element::CircuitElement,
+    Circuit, ElementId,
+};
+
+// A connection by the IDs of the elements it connects, so it can be found again after other edits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wire {
+    pub from: (ElementId, OutputIdx),
+    pub to: (ElementId, InputIdx),
+    pub width: u8,
+}
+
+// Elements and connections which an edit adds or removes
+#[derive(Clone, Debug, Default)]
+pub struct Items {
+    // Each element along with the index it had, so undoing its removal puts it back in place
+    pub elements: Vec<(ElementId, ElementIdx, CircuitElement)>,
+    pub connections: Vec<Wire>,
+}
+
+// A reversible change to a circuit
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Move {
+        elements: Vec<ElementId>,
+        offset: Vec2,
+    },
+    Add(Items),
+    Remove(Items),
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    // Whether moves are still being added to the last edit, until the end of a drag
+    moving: bool,
+}
+
+// The elements which were put back by undoing or redoing an edit, by their old and new IDs
+type Remap = Vec<(ElementId, ElementId)>;
+
+impl Wire {
+    pub fn new(circuit: &Circuit, connection: Connection) -> Self {
+        Self {
+            from: (circuit.element_id(connection.from.0), connection.from.1),
+            to: (circuit.element_id(connection.to.0), connection.to.1),
+            width: connection.width,
+        }
+    }
+
+    // The connection between the wire's pins, or None if one of them no longer exists
+    fn resolve(&self, circuit: &Circuit) -> Option<Connection> {
+        let from = OutputSpecifier(circuit.element_idx(self.from.0)?, self.from.1);
+        let to = InputSpecifier(circuit.element_idx(self.to.0)?, self.to.1);
+        let exists = from.1 .0 < circuit[from.0].gate.output_count()
+            && to.1 .0 < circuit[to.0].gate.input_count();
+        exists.then_some(from.to(to).with_width(self.width))
+    }
+
+    fn remap(&mut self, remap: &Remap) {
+        for (old, new) in remap {
+            if self.from.0 == *old {
+                self.from.0 = *new;
+            }
+            if self.to.0 == *old {
+                self.to.0 = *new;
+            }
+        }
+    }
+}
+
+impl Items {
+    // The current state of some elements and connections, along with every connection to the elements
+    pub fn capture(circuit: &Circuit, elements: &[ElementId], connections: &[Wire]) -> Self {
+        let mut captured: Vec<_> = elements
+            .iter()
+            .filter_map(|&id| {
+                let index = circuit.element_idx(id)?;
+                Some((id, index, circuit[index].clone()))
+            })
+            .collect();
+        captured.sort_by_key(|(_, index, _)| index.0);
+
+        let mut wires: Vec<Wire> = vec![];
+        let attached = circuit.connections.iter().filter(|connection| {
+            captured
+                .iter()
+                .any(|(_, index, _)| connection.from.0 == *index || connection.to.0 == *index)
+        });
+        for wire in connections
+            .iter()
+            .copied()
+            .chain(attached.map(|connection| Wire::new(circuit, *connection)))
+        {
+            if !wires.contains(&wire) {
+                wires.push(wire);
+            }
+        }
+
+        Self {
+            elements: captured,
+            connections: wires,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty() && self.connections.is_empty()
+    }
+
+    // Removes the items, first capturing any changes made to them since they were captured
+    fn remove(&mut self, circuit: &mut Circuit) {
+        let elements: Vec<_> = self.elements.iter().map(|(id, ..)| *id).collect();
+        *self = Self::capture(circuit, &elements, &self.connections);
+
+        for wire in &self.connections {
+            let Some(connection) = wire.resolve(circuit) else {
+                continue;
+            };
+            if let Some(index) = circuit.connections.iter().position(|c| *c == connection) {
+                circuit.remove_connection(ConnectionIdx(index));
+            }
+        }
+        for &(id, ..) in &self.elements {
+            if let Some(index) = circuit.element_idx(id) {
+                circuit.remove_gate(index);
+            }
+        }
+    }
+
+    // Puts the items back, where the elements get new IDs
+    fn add(&mut self, circuit: &mut Circuit) -> Remap {
+        // Inserting in index order puts every element back at the index it was captured at
+        let mut remap = vec![];
+        for (id, index, element) in &mut self.elements {
+            let at = ElementIdx(index.0.min(circuit.elements.len()));
+            let new = circuit.insert_gate(at, element.clone());
+            remap.push((*id, new));
+            *id = new;
+        }
+
+        for wire in &mut self.connections {
+            wire.remap(&remap);
+            // Edits which weren't recorded, like changing a gate's inputs, can leave a pin
+            // unable to take its connection back
+            if let Some(connection) = wire.resolve(circuit) {
+                let _ = circuit.add_connection(connection);
+            }
+        }
+        remap
+    }
+
+    fn remap(&mut self, remap: &Remap) {
+        for (id, ..) in &mut self.elements {
+            if let Some((_, new)) = remap.iter().find(|(old, _)| old == id) {
+                *id = *new;
+            }
+        }
+        for wire in &mut self.connections {
+            wire.remap(remap);
+        }
+    }
+}
+
+impl Edit {
+    fn undo(&mut self, circuit: &mut Circuit) -> Remap {
+        match self {
+            Edit::Move { elements, offset } => {
+                move_elements(circuit, elements, -*offset);
+                vec![]
+            }
+            Edit::Add(items) => {
+                items.remove(circuit);
+                vec![]
+            }
+            Edit::Remove(items) => items.add(circuit),
+        }
+    }
+
+    fn redo(&mut self, circuit: &mut Circuit) -> Remap {
+        match self {
+            Edit::Move { elements, offset } => {
+                move_elements(circuit, elements, *offset);
+                vec![]
+            }
+            Edit::Add(items) => items.add(circuit),
+            Edit::Remove(items) => {
+                items.remove(circuit);
+                vec![]
+            }
+        }
+    }
+
+    fn remap(&mut self, remap: &Remap) {
+        match self {
+            Edit::Move { elements, .. } => {
+                for id in elements {
+                    if let Some((_, new)) = remap.iter().find(|(old, _)| old == id) {
+                        *id = *new;
+                    }
+                }
+            }
+            Edit::Add(items) | Edit::Remove(items) => items.remap(remap),
+        }
+    }
+}
+
+impl History {
+    // Makes an edit to the circuit and records it
+    pub fn apply(&mut self, circuit: &mut Circuit, mut edit: Edit) {
+        let remap = edit.redo(circuit);
+        self.record(edit);
+        self.remap(&remap);
+    }
+
+    // Records an edit which has already been made to the circuit
+    pub fn record(&mut self, edit: Edit) {
+        self.moving = false;
+        self.redo.clear();
+        self.undo.push(edit);
+    }
+
+    // Records a move which has already been made, adding it to the last move until the drag finishes
+    pub fn record_move(&mut self, elements: Vec<ElementId>, offset: Vec2) {
+        if let (
+            true,
+            Some(Edit::Move {
+                elements: last,
+                offset: total,
+            }),
+        ) = (self.moving, self.undo.last_mut())
+        {
+            if last.len() == elements.len() && elements.iter().all(|id| last.contains(id)) {
+                *total += offset;
+                return;
+            }
+        }
+        self.record(Edit::Move { elements, offset });
+        self.moving = true;
+    }
+
+    pub fn finish_move(&mut self) {
+        self.moving = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // Returns whether there was anything to undo
+    pub fn undo(&mut self, circuit: &mut Circuit) -> bool {
+        self.moving = false;
+        let Some(mut edit) = self.undo.pop() else {
+            return false;
+        };
+        let remap = edit.undo(circuit);
+        self.redo.push(edit);
+        self.remap(&remap);
+        true
+    }
+
+    // Returns whether there was anything to redo
+    pub fn redo(&mut self, circuit: &mut Circuit) -> bool {
+        self.moving = false;
+        let Some(mut edit) = self.redo.pop() else {
+            return false;
+        };
+        let remap = edit.redo(circuit);
+        self.undo.push(edit);
+        self.remap(&remap);
+        true
+    }
+
+    // Elements which were put back have new IDs, which every other edit has to refer to them by
+    fn remap(&mut self, remap: &Remap) {
+        if remap.is_empty() {
+            return;
+        }
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            edit.remap(remap);
+        }
+    }
+}
+
+pub(super) fn move_elements(circuit: &mut Circuit, elements: &[ElementId], offset: Vec2) {
+    for &id in elements {
+        if let Some(index) = circuit.element_idx(id) {
+            circuit[index].position += offset;
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::logic::{gate::Gate, solver::SettleError};
+
+use super::{
+    connection::{ElementIdx, OutputSpecifier},
+    Circuit,
+};
+
+// Number of independent stimulus vectors simulated at once, one per bit of a pin's value
+pub const LANES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaneError {
+    // A bus or a gate which doesn't treat every bit alike, so lanes would affect each other
+    NotLaneParallel,
+    // More lane values were given than the circuit has Input gates
+    TooManyInputs { expected: usize, found: usize },
+    Settle(SettleError),
+}
+
+// Values for `inputs` Input gates which together cover every combination of up to six of them
+// Lane n of input i is bit i of n, so any inputs after the sixth stay low
+pub fn exhaustive_lanes(inputs: usize) -> Vec<u64> {
+    (0..inputs as u32)
+        .map(|input| {
+            (0..LANES)
+                .filter(|lane| lane.checked_shr(input).unwrap_or(0) & 1 == 1)
+                .fold(0, |acc, lane| acc | (1 << lane))
+        })
+        .collect()
+}
+
+impl Gate {
+    // Whether every bit of the gate's pins is computed only from the same bit of its inputs
+    pub fn is_lane_parallel(&self) -> bool {
+        match self {
+            // These treat bits as positions in a bus, or broadcast bit 0 to every bit
+            Gate::Merge(_) | Gate::Split(_) | Gate::Register(_) | Gate::TriState => false,
+            Gate::Embedded(embed) => embed.component().circuit().is_lane_parallel(),
+            _ => true,
+        }
+    }
+}
+
+impl Circuit {
+    // Whether each bit of a single bit net can carry a separate simulation
+    pub fn is_lane_parallel(&self) -> bool {
+        self.elements
+            .iter()
+            .all(|element| element.gate.is_lane_parallel())
+            && self
+                .connections
+                .iter()
+                .all(|connection| connection.width == 1)
+    }
+
+    // Drives each lane of a single bit Input gate with a separate value, lane n is bit n
+    pub fn set_input_lanes(&mut self, port: ElementIdx, lanes: u64) {
+        self.set_input(port, lanes);
+    }
+
+    // The value of every lane of a single bit output
+    pub fn output_lanes(&self, io: OutputSpecifier) -> u64 {
+        self.solver.read_logic(io).known()
+    }
+
+    // Settles the circuit with one stimulus vector per lane, returning the lanes of each Output
+    // gate in port order
+    pub fn simulate_lanes(
+        &mut self,
+        inputs: &[u64],
+        max_steps: usize,
+    ) -> Result<Vec<u64>, LaneError> {
+        if !self.is_lane_parallel() {
+            return Err(LaneError::NotLaneParallel);
+        }
+
+        let ports = self.input_ports();
+        if inputs.len() > ports.len() {
+            return Err(LaneError::TooManyInputs {
+                expected: ports.len(),
+                found: inputs.len(),
+            });
+        }
+
+        for (port, lanes) in ports.into_iter().zip(inputs) {
+            self.set_input_lanes(port, *lanes);
+        }
+        self.settle(max_steps).map_err(LaneError::Settle)?;
+
+        Ok(self
+            .output_ports()
+            .into_iter()
+            .map(|port| self.output_lanes(port.output(0)))
+            .collect())
+    }
+}
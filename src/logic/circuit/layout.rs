@@ -0,0 +1,107 @@
+use glam::Vec2;
+
+use crate::logic::gate::Gate;
+
+use super::{connection::ElementIdx, Circuit};
+
+// Horizontal distance between columns, and vertical space left between gates in a column
+const COLUMN: f32 = 2.0;
+const GAP: f32 = 0.5;
+
+impl Circuit {
+    // Places every element in a column by its logic depth, with Input gates on the left and Output
+    // gates on the right, both kept in their port order
+    // Gates within a column are ordered by the average row of the gates driving them
+    pub fn auto_layout(&mut self) {
+        let count = self.elements.len();
+        let mut drivers = vec![vec![]; count];
+        for connection in &self.connections {
+            drivers[connection.to.0 .0].push(connection.from.0 .0);
+        }
+
+        let mut levels = depths(&drivers);
+        let inputs = self.input_ports();
+        let outputs = self.output_ports();
+        let last = levels.iter().copied().max().unwrap_or(0) + 1;
+        for ElementIdx(input) in &inputs {
+            levels[*input] = 0;
+        }
+        for ElementIdx(output) in &outputs {
+            levels[*output] = last;
+        }
+
+        let mut columns = vec![vec![]; last + 1];
+        columns[0] = inputs.iter().map(|ElementIdx(index)| *index).collect();
+        for (index, level) in levels.iter().enumerate() {
+            let is_port = matches!(self.elements[index].gate, Gate::Input(_) | Gate::Output(_));
+            if !is_port {
+                columns[*level].push(index);
+            }
+        }
+        columns[last] = outputs.iter().map(|ElementIdx(index)| *index).collect();
+
+        let mut rows = vec![0.0; count];
+        for (level, column) in columns.iter_mut().enumerate() {
+            if level != 0 && level != last {
+                let barycenter = |index: &usize| {
+                    let placed: Vec<f32> = drivers[*index]
+                        .iter()
+                        .filter(|driver| levels[**driver] < level)
+                        .map(|driver| rows[*driver])
+                        .collect();
+                    placed.iter().sum::<f32>() / placed.len().max(1) as f32
+                };
+                column.sort_by(|a, b| barycenter(a).total_cmp(&barycenter(b)));
+            }
+
+            let mut y = 0.0;
+            for &index in column.iter() {
+                let bounds = self.elements[index].gate.bounds();
+                let height = (bounds.bottom_right.y - bounds.top_left.y).abs();
+                rows[index] = y + height / 2.0;
+                self.elements[index].position = Vec2::new(level as f32 * COLUMN, -rows[index]);
+                y += height + GAP;
+            }
+        }
+    }
+}
+
+// The longest path to each element from one without any drivers, ignoring connections which
+// close a loop
+fn depths(drivers: &[Vec<usize>]) -> Vec<usize> {
+    const UNVISITED: u8 = 0;
+    const VISITING: u8 = 1;
+    const DONE: u8 = 2;
+
+    let mut state = vec![UNVISITED; drivers.len()];
+    let mut depths = vec![0; drivers.len()];
+    for root in 0..drivers.len() {
+        if state[root] != UNVISITED {
+            continue;
+        }
+        state[root] = VISITING;
+        // Elements being visited, along with the next driver to visit
+        let mut stack = vec![(root, 0)];
+        while let Some((element, next)) = stack.last_mut() {
+            let element = *element;
+            if let Some(&driver) = drivers[element].get(*next) {
+                *next += 1;
+                if state[driver] == UNVISITED {
+                    state[driver] = VISITING;
+                    stack.push((driver, 0));
+                }
+                continue;
+            }
+
+            depths[element] = drivers[element]
+                .iter()
+                .filter(|driver| state[**driver] == DONE)
+                .map(|driver| depths[*driver] + 1)
+                .max()
+                .unwrap_or(0);
+            state[element] = DONE;
+            stack.pop();
+        }
+    }
+    depths
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::logic::gate::Gate;
+
+use super::{connection::Connection, embedded::Component, Circuit};
+
+// Updated versions of components which contain another component, keyed by their old definition
+// The old definition is kept alive so its address can't be reused while updating
+type Updated = HashMap<*const Component, (Arc<Component>, Option<Arc<Component>>)>;
+
+impl Circuit {
+    // Points every instance of the component at its new definition, including instances nested in
+    // other embedded circuits, returning the connections of this circuit which were removed, or
+    // None if nothing uses the component
+    // This is how a component is edited: `Component::redefine` makes the new definition, which
+    // keeps the component's identity, and every design using it is updated with it here
+    // Pin order follows the positions of the ports, so connections to instances follow their pins
+    // by port name, and pins without a matching name keep their index if it's free
+    // Connections to pins which no longer exist, or whose width changed, are removed
+    pub fn update_component(&mut self, component: &Arc<Component>) -> Option<Vec<Connection>> {
+        self.replace_component(component, &mut HashMap::new())
+    }
+
+    fn replace_component(
+        &mut self,
+        component: &Arc<Component>,
+        updated: &mut Updated,
+    ) -> Option<Vec<Connection>> {
+        // The new index of each pin of the updated instances, by their old index
+        let mut remapped = HashMap::new();
+        for (index, element) in self.elements.iter_mut().enumerate() {
+            let Gate::Embedded(embed) = &mut element.gate else {
+                continue;
+            };
+
+            let replacement = if embed.component().id() == component.id() {
+                Some(component.clone())
+            } else {
+                updated_component(embed.component(), component, updated)
+            };
+
+            if let Some(replacement) = replacement {
+                let (inputs, outputs) =
+                    (embed.input_names().to_vec(), embed.output_names().to_vec());
+                embed.set_component(replacement);
+                let pins = (
+                    pin_map(&inputs, embed.input_names()),
+                    pin_map(&outputs, embed.output_names()),
+                );
+                remapped.insert(index, pins);
+            }
+        }
+        if remapped.is_empty() {
+            return None;
+        }
+
+        let elements = &self.elements;
+        let remap = |mut connection: Connection| {
+            if let Some((_, outputs)) = remapped.get(&connection.from.0 .0) {
+                connection.from.1 .0 = outputs[connection.from.1 .0]?;
+            }
+            if let Some((inputs, _)) = remapped.get(&connection.to.0 .0) {
+                connection.to.1 .0 = inputs[connection.to.1 .0]?;
+            }
+
+            let (from, to) = (
+                &elements[connection.from.0 .0].gate,
+                &elements[connection.to.0 .0].gate,
+            );
+            let fits = connection.from.1 .0 < from.output_count()
+                && connection.to.1 .0 < to.input_count()
+                && from
+                    .output_width(connection.from.1)
+                    .is_none_or(|width| width == connection.width)
+                && to
+                    .input_width(connection.to.1)
+                    .is_none_or(|width| width == connection.width);
+            fits.then_some(connection)
+        };
+        let connections: Vec<_> = self
+            .connections
+            .iter()
+            .map(|&connection| remap(connection))
+            .collect();
+
+        let mut removed = vec![];
+        for (connection, remapped) in self.connections.iter_mut().zip(&connections) {
+            match remapped {
+                Some(remapped) => *connection = *remapped,
+                None => removed.push(*connection),
+            }
+        }
+        let mut kept = connections.iter().map(Option::is_some);
+        self.connection_ids
+            .retain(&mut self.connections, |_| kept.next().unwrap());
+        self.solver.invalidate();
+        Some(removed)
+    }
+}
+
+// The new index of each old pin, by name where the new pins have the same name
+fn pin_map(old: &[Option<String>], new: &[Option<String>]) -> Vec<Option<usize>> {
+    let by_name: Vec<Option<usize>> = old
+        .iter()
+        .map(|name| {
+            let name = name.as_ref()?;
+            new.iter().position(|new| new.as_ref() == Some(name))
+        })
+        .collect();
+    let named: HashSet<usize> = by_name.iter().flatten().copied().collect();
+    by_name
+        .iter()
+        .enumerate()
+        .map(|(pin, by_name)| by_name.or((pin < new.len() && !named.contains(&pin)).then_some(pin)))
+        .collect()
+}
+
+// The new version of a component which contains the updated component, if it does
+// Instances sharing a definition keep sharing the updated one
+fn updated_component(
+    old: &Arc<Component>,
+    component: &Arc<Component>,
+    updated: &mut Updated,
+) -> Option<Arc<Component>> {
+    if old.id() == component.id() {
+        return None;
+    }
+
+    let key = Arc::as_ptr(old);
+    if let Some((_, replacement)) = updated.get(&key) {
+        return replacement.clone();
+    }
+
+    let mut definition = old.circuit().clone();
+    let replacement = definition
+        .replace_component(component, updated)
+        .map(|_| Arc::new(old.redefine(definition)));
+    updated.insert(key, (old.clone(), replacement.clone()));
+    replacement
+}
@@ -10,62 +10,96 @@ use crate::{
     color,
     game::GameInput,
     logic::{circuit::connection::ConnectionIdx, hit_test::HitTestResult},
-    render::{frame::Frame, line::cubic_bezier::CubicBezier},
+    render::{frame::Frame, line::cubic_bezier::CubicBezier, msdf::text::TextObject},
 };
 
 const COLOR_SIGNAL_HIGH: Vec4 = color::RED;
 const COLOR_SIGNAL_LOW: Vec4 = color::WHITE;
+const COLOR_SIGNAL_UNKNOWN: Vec4 = color::PURPLE;
 const COLOR_DRAWING: Vec4 = color::YELLOW;
 const COLOR_SELECTED: Vec4 = color::BLUE;
+const COLOR_REFUSED: Vec4 = color::YELLOW;
+
+const REFUSAL_TEXT_SCALE: f32 = 16.0;
 
 const BASE_LINE_WIDTH: f32 = 0.05;
 
-pub fn sprite_of(gate: &Gate, active: bool) -> Option<&'static SVGSource> {
+// `high` is the level of the gate's first output, for gates which display it
+pub fn sprite_of(gate: &Gate, active: bool, high: bool) -> Option<&'static SVGSource> {
     use assets::svg::gates;
     match (gate, active) {
+        (Gate::Clock { .. }, true) if high => Some(&gates::CLOCK_HIGH_ACTIVE),
+        (Gate::Clock { .. }, false) if high => Some(&gates::CLOCK_HIGH_NORMAL),
+        (Gate::Clock { .. }, true) => Some(&gates::CLOCK_LOW_ACTIVE),
+        (Gate::Clock { .. }, false) => Some(&gates::CLOCK_LOW_NORMAL),
+
         (Gate::Const(_), _) => None,
         (Gate::Embedded(_), _) => None,
         (Gate::Input(_), _) => Some(&gates::INPUT),
         (Gate::Output(_), _) => Some(&gates::OUTPUT),
 
-        (Gate::And, true) => Some(&gates::AND_ACTIVE),
-        (Gate::And, false) => Some(&gates::AND_NORMAL),
+        (Gate::And(_), true) => Some(&gates::AND_ACTIVE),
+        (Gate::And(_), false) => Some(&gates::AND_NORMAL),
 
-        (Gate::Or, true) => Some(&gates::OR_ACTIVE),
-        (Gate::Or, false) => Some(&gates::OR_NORMAL),
+        (Gate::Or(_), true) => Some(&gates::OR_ACTIVE),
+        (Gate::Or(_), false) => Some(&gates::OR_NORMAL),
 
         (Gate::Not, true) => Some(&gates::NOT_ACTIVE),
         (Gate::Not, false) => Some(&gates::NOT_NORMAL),
 
-        (Gate::Xor, true) => Some(&gates::XOR_ACTIVE),
-        (Gate::Xor, false) => Some(&gates::XOR_NORMAL),
+        (Gate::Xor(_), true) => Some(&gates::XOR_ACTIVE),
+        (Gate::Xor(_), false) => Some(&gates::XOR_NORMAL),
 
-        (Gate::Nand, true) => Some(&gates::NAND_ACTIVE),
-        (Gate::Nand, false) => Some(&gates::NAND_NORMAL),
+        (Gate::Nand(_), true) => Some(&gates::NAND_ACTIVE),
+        (Gate::Nand(_), false) => Some(&gates::NAND_NORMAL),
 
-        (Gate::Nor, true) => Some(&gates::NOR_ACTIVE),
-        (Gate::Nor, false) => Some(&gates::NOR_NORMAL),
+        (Gate::Nor(_), true) => Some(&gates::NOR_ACTIVE),
+        (Gate::Nor(_), false) => Some(&gates::NOR_NORMAL),
 
-        (Gate::Xnor, true) => Some(&gates::XNOR_ACTIVE),
-        (Gate::Xnor, false) => Some(&gates::XNOR_NORMAL),
+        (Gate::Xnor(_), true) => Some(&gates::XNOR_ACTIVE),
+        (Gate::Xnor(_), false) => Some(&gates::XNOR_NORMAL),
 
         (Gate::Buf, true) => Some(&gates::BUF_ACTIVE),
         (Gate::Buf, false) => Some(&gates::BUF_NORMAL),
 
-        (Gate::Button(_), true) => Some(&gates::BUTTON_ACTIVE),
-        (Gate::Button(_), false) => Some(&gates::BUTTON_NORMAL),
+        (Gate::Button, true) => Some(&gates::BUTTON_ACTIVE),
+        (Gate::Button, false) => Some(&gates::BUTTON_NORMAL),
 
         (Gate::On, true) => Some(&gates::ON_ACTIVE),
         (Gate::On, false) => Some(&gates::ON_NORMAL),
 
         (Gate::Off, true) => Some(&gates::OFF_ACTIVE),
         (Gate::Off, false) => Some(&gates::OFF_NORMAL),
+
+        (Gate::Merge(_), true) => Some(&gates::MERGE_ACTIVE),
+        (Gate::Merge(_), false) => Some(&gates::MERGE_NORMAL),
+
+        (Gate::Split(_), true) => Some(&gates::SPLIT_ACTIVE),
+        (Gate::Split(_), false) => Some(&gates::SPLIT_NORMAL),
+
+        (Gate::SrLatch, true) => Some(&gates::SR_LATCH_ACTIVE),
+        (Gate::SrLatch, false) => Some(&gates::SR_LATCH_NORMAL),
+
+        (Gate::DLatch, true) => Some(&gates::D_LATCH_ACTIVE),
+        (Gate::DLatch, false) => Some(&gates::D_LATCH_NORMAL),
+
+        (Gate::DFlipFlop, true) => Some(&gates::D_FLIP_FLOP_ACTIVE),
+        (Gate::DFlipFlop, false) => Some(&gates::D_FLIP_FLOP_NORMAL),
+
+        (Gate::JkFlipFlop, true) => Some(&gates::JK_FLIP_FLOP_ACTIVE),
+        (Gate::JkFlipFlop, false) => Some(&gates::JK_FLIP_FLOP_NORMAL),
+
+        (Gate::TriState, true) => Some(&gates::TRI_STATE_ACTIVE),
+        (Gate::TriState, false) => Some(&gates::TRI_STATE_NORMAL),
+
+        (Gate::Register(_), true) => Some(&gates::REGISTER_ACTIVE),
+        (Gate::Register(_), false) => Some(&gates::REGISTER_NORMAL),
     }
 }
 
 impl CircuitElement {
-    pub fn draw(&self, selected: bool, hot: bool, frame: &mut Frame) {
-        let Some(sprite) = sprite_of(&self.gate, selected) else {
+    pub fn draw(&self, selected: bool, hot: bool, high: bool, frame: &mut Frame) {
+        let Some(sprite) = sprite_of(&self.gate, selected, high) else {
             return;
         };
 
@@ -73,7 +107,7 @@ impl CircuitElement {
             Vec2::splat(1.2)
         } else {
             Vec2::splat(1.0)
-        };
+        } * self.gate.sprite_scale();
 
         frame.draw_vector_lazy(sprite, self.position, Vec4::ONE, scale, selected as u16)
     }
@@ -90,11 +124,18 @@ impl EditCircuit {
 
             let is_selected = self
                 .selection
-                .contains(HitTestResult::Element(ElementIdx(idx)));
+                .contains(&self.circuit, HitTestResult::Element(ElementIdx(idx)));
 
-            element.draw(is_selected, is_hot, frame);
+            let is_high = element.gate.output_count() > 0
+                && self.circuit.output_value(ElementIdx(idx).output(0));
+
+            element.draw(is_selected, is_hot, is_high, frame);
         }
 
+        let refused_driver = self
+            .refusal
+            .as_ref()
+            .and_then(|refusal| self.circuit.connection_idx(refusal.driver?));
         self.circuit
             .connections
             .iter()
@@ -102,8 +143,17 @@ impl EditCircuit {
             .for_each(|(idx, conn)| {
                 let line = self.circuit.cubic_bezier_from_connection(conn);
                 if frame.camera().bounds().overlaps(&line.bounds()) {
-                    let is_active = self.circuit.solver.output_results.read_output(conn.from);
-                    let color = if is_active {
+                    let value = self.circuit.output_bits(conn.from);
+                    let is_active = value.known() & conn.mask() != 0;
+                    let is_unknown = value.unknown & conn.mask() != 0;
+                    let line_width = if conn.width > 1 {
+                        BASE_LINE_WIDTH * 2.0
+                    } else {
+                        BASE_LINE_WIDTH
+                    };
+                    let color = if is_unknown {
+                        COLOR_SIGNAL_UNKNOWN
+                    } else if is_active {
                         COLOR_SIGNAL_HIGH
                     } else {
                         COLOR_SIGNAL_LOW
@@ -111,17 +161,15 @@ impl EditCircuit {
 
                     let is_selected = self
                         .selection
-                        .contains(HitTestResult::Connection(ConnectionIdx(idx)));
-                    // Draw an outline if selected
+                        .contains(&self.circuit, HitTestResult::Connection(ConnectionIdx(idx)));
+                    // Draw an outline if selected, or if it's why a connection was refused
                     if is_selected {
-                        frame.draw_cubic_bezier(
-                            line.clone(),
-                            COLOR_SELECTED,
-                            BASE_LINE_WIDTH * 1.5,
-                        );
+                        frame.draw_cubic_bezier(line.clone(), COLOR_SELECTED, line_width * 1.5);
+                    } else if refused_driver == Some(ConnectionIdx(idx)) {
+                        frame.draw_cubic_bezier(line.clone(), COLOR_REFUSED, line_width * 2.0);
                     }
 
-                    frame.draw_cubic_bezier(line, color, BASE_LINE_WIDTH)
+                    frame.draw_cubic_bezier(line, color, line_width)
                 }
             });
 
@@ -157,6 +205,16 @@ impl EditCircuit {
             frame.draw_vector_lazy(dot_source, position, Vec4::ONE, scale, 2);
         });
 
+        if let Some(refusal) = &self.refusal {
+            let text = TextObject {
+                content: refusal.message.clone(),
+                position: refusal.position + Vec2::splat(REFUSAL_TEXT_SCALE),
+                scale: REFUSAL_TEXT_SCALE,
+                centered: false,
+            };
+            text.draw(&mut frame.ui_render_queue, &frame.assets.font);
+        }
+
         // Draw box select outline
         if let Some(bounds) = self.selection.bound_select {
             let width = frame.world_pixel_size().max_element() * 2.0;
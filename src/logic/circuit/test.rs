@@ -1,14 +1,16 @@
 use crate::logic::{
     circuit::{embedded::EmbeddedCircuit, Circuit},
     gate::Gate,
+    SettleError, SolverMode,
 };
 use glam::Vec2;
 
 #[cfg(test)]
 pub mod gates {
     use super::*;
+    use crate::logic::gate::FAN_IN;
 
-    fn test_gate(gate: Gate, inputs: &'static [bool], outputs: &'static [bool]) {
+    fn test_gate(gate: Gate, inputs: &[bool], outputs: &[bool]) {
         let mut circuit = Circuit::default();
         let gate_under_test = circuit.add_gate(gate, Vec2::ZERO);
         let in_zero = circuit.add_gate(Gate::Off, Vec2::ZERO).output(0);
@@ -16,10 +18,12 @@ pub mod gates {
 
         for (i, &input) in inputs.iter().enumerate() {
             let source = if input { in_one } else { in_zero };
-            circuit.add_connection(source.to(gate_under_test.input(i)));
+            circuit
+                .add_connection(source.to(gate_under_test.input(i)))
+                .unwrap();
         }
 
-        circuit.step_n(10);
+        circuit.settle(10).unwrap();
 
         for (i, &output) in outputs.iter().enumerate() {
             assert_eq!(circuit.output_value(gate_under_test.output(i)), output);
@@ -50,57 +54,106 @@ pub mod gates {
 
     #[test]
     fn and() {
-        test_gate(Gate::And, &[false, false], &[false]);
-        test_gate(Gate::And, &[false, true], &[false]);
-        test_gate(Gate::And, &[true, false], &[false]);
-        test_gate(Gate::And, &[true, true], &[true]);
+        test_gate(Gate::And(2), &[false, false], &[false]);
+        test_gate(Gate::And(2), &[false, true], &[false]);
+        test_gate(Gate::And(2), &[true, false], &[false]);
+        test_gate(Gate::And(2), &[true, true], &[true]);
     }
 
     #[test]
     fn or() {
-        test_gate(Gate::Or, &[false, false], &[false]);
-        test_gate(Gate::Or, &[false, true], &[true]);
-        test_gate(Gate::Or, &[true, false], &[true]);
-        test_gate(Gate::Or, &[true, true], &[true]);
+        test_gate(Gate::Or(2), &[false, false], &[false]);
+        test_gate(Gate::Or(2), &[false, true], &[true]);
+        test_gate(Gate::Or(2), &[true, false], &[true]);
+        test_gate(Gate::Or(2), &[true, true], &[true]);
     }
 
     #[test]
     fn xor() {
-        test_gate(Gate::Xor, &[false, false], &[false]);
-        test_gate(Gate::Xor, &[false, true], &[true]);
-        test_gate(Gate::Xor, &[true, false], &[true]);
-        test_gate(Gate::Xor, &[true, true], &[false]);
+        test_gate(Gate::Xor(2), &[false, false], &[false]);
+        test_gate(Gate::Xor(2), &[false, true], &[true]);
+        test_gate(Gate::Xor(2), &[true, false], &[true]);
+        test_gate(Gate::Xor(2), &[true, true], &[false]);
     }
 
     #[test]
     fn nand() {
-        test_gate(Gate::Nand, &[false, false], &[true]);
-        test_gate(Gate::Nand, &[false, true], &[true]);
-        test_gate(Gate::Nand, &[true, false], &[true]);
-        test_gate(Gate::Nand, &[true, true], &[false]);
+        test_gate(Gate::Nand(2), &[false, false], &[true]);
+        test_gate(Gate::Nand(2), &[false, true], &[true]);
+        test_gate(Gate::Nand(2), &[true, false], &[true]);
+        test_gate(Gate::Nand(2), &[true, true], &[false]);
     }
 
     #[test]
     fn nor() {
-        test_gate(Gate::Nor, &[false, false], &[true]);
-        test_gate(Gate::Nor, &[false, true], &[false]);
-        test_gate(Gate::Nor, &[true, false], &[false]);
-        test_gate(Gate::Nor, &[true, true], &[false]);
+        test_gate(Gate::Nor(2), &[false, false], &[true]);
+        test_gate(Gate::Nor(2), &[false, true], &[false]);
+        test_gate(Gate::Nor(2), &[true, false], &[false]);
+        test_gate(Gate::Nor(2), &[true, true], &[false]);
+    }
+
+    #[test]
+    fn fan_in() {
+        for inputs in FAN_IN {
+            let all_high = vec![true; inputs as usize];
+            let mut one_low = all_high.clone();
+            one_low[inputs as usize / 2] = false;
+            let mut one_high = vec![false; inputs as usize];
+            one_high[inputs as usize - 1] = true;
+            let odd = inputs & 1 == 1;
+
+            test_gate(Gate::And(inputs), &all_high, &[true]);
+            test_gate(Gate::And(inputs), &one_low, &[false]);
+            test_gate(Gate::Nand(inputs), &one_low, &[true]);
+            test_gate(Gate::Or(inputs), &one_high, &[true]);
+            test_gate(Gate::Nor(inputs), &one_high, &[false]);
+            test_gate(Gate::Xor(inputs), &all_high, &[odd]);
+            test_gate(Gate::Xnor(inputs), &one_low, &[odd]);
+        }
+    }
+
+    #[test]
+    fn fan_in_layout() {
+        let gate = Gate::Or(16);
+        let offsets: Vec<_> = gate.input_offsets().into_iter().collect();
+        assert_eq!(offsets.len(), 16);
+
+        let bounds = gate.bounds();
+        for offset in offsets {
+            assert!(offset.y >= bounds.top_left.y && offset.y <= bounds.bottom_right.y);
+        }
+    }
+
+    #[test]
+    fn set_fan_in() {
+        let mut circuit = Circuit::default();
+        let and = circuit.add_gate(Gate::And(4), Vec2::ZERO);
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        for input in 0..4 {
+            circuit.add_connection(on.to(and.input(input))).unwrap();
+        }
+
+        circuit.set_fan_in(and, 2);
+        assert_eq!(circuit[and].gate.input_count(), 2);
+        assert_eq!(circuit.connections.len(), 2);
+
+        circuit.set_fan_in(and, 100);
+        assert_eq!(circuit[and].gate.input_count(), 16);
     }
 
     #[test]
     fn xnor() {
-        test_gate(Gate::Xnor, &[false, false], &[true]);
-        test_gate(Gate::Xnor, &[false, true], &[false]);
-        test_gate(Gate::Xnor, &[true, false], &[false]);
-        test_gate(Gate::Xnor, &[true, true], &[true]);
+        test_gate(Gate::Xnor(2), &[false, false], &[true]);
+        test_gate(Gate::Xnor(2), &[false, true], &[false]);
+        test_gate(Gate::Xnor(2), &[true, false], &[false]);
+        test_gate(Gate::Xnor(2), &[true, true], &[true]);
     }
 }
 
 #[test]
 fn full_adder() {
     fn make_embedded_adder(in_a: bool, in_b: bool, carry: bool) -> (bool, bool) {
-        let adder_gate = EmbeddedCircuit::new(Circuit::full_adder()).unwrap();
+        let adder_gate = EmbeddedCircuit::new(Circuit::full_adder());
         let mut circuit = Circuit::default();
         let adder_instance = circuit.add_gate(Gate::Embedded(adder_gate), Vec2::ZERO);
 
@@ -108,11 +161,17 @@ fn full_adder() {
         let in_b = circuit.add_gate(Gate::Const(in_b), Vec2::ZERO).output(0);
         let carry = circuit.add_gate(Gate::Const(carry), Vec2::ZERO).output(0);
 
-        circuit.add_connection(in_a.to(adder_instance.input(0)));
-        circuit.add_connection(in_b.to(adder_instance.input(1)));
-        circuit.add_connection(carry.to(adder_instance.input(2)));
+        circuit
+            .add_connection(in_a.to(adder_instance.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(in_b.to(adder_instance.input(1)))
+            .unwrap();
+        circuit
+            .add_connection(carry.to(adder_instance.input(2)))
+            .unwrap();
 
-        circuit.step_n(6);
+        circuit.settle(20).unwrap();
 
         (
             circuit.output_value(adder_instance.output(0)),
@@ -129,3 +188,3372 @@ fn full_adder() {
     assert_eq!(make_embedded_adder(true, true, false), (false, true));
     assert_eq!(make_embedded_adder(true, true, true), (true, true));
 }
+
+#[cfg(test)]
+mod sequential {
+    use super::*;
+    use crate::logic::circuit::connection::ElementIdx;
+
+    // A single gate with each input driven by a constant which tests can change
+    struct Harness {
+        circuit: Circuit,
+        gate: ElementIdx,
+        drivers: Vec<ElementIdx>,
+    }
+
+    impl Harness {
+        fn new(gate: Gate) -> Self {
+            let mut circuit = Circuit::default();
+            let inputs = gate.input_count();
+            let gate = circuit.add_gate(gate, Vec2::ZERO);
+            let drivers = (0..inputs)
+                .map(|pin| {
+                    let driver = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+                    circuit
+                        .add_connection(driver.output(0).to(gate.input(pin)))
+                        .unwrap();
+                    driver
+                })
+                .collect();
+
+            Self {
+                circuit,
+                gate,
+                drivers,
+            }
+        }
+
+        // Applies the input values, settles and returns Q
+        fn apply(&mut self, inputs: &[bool]) -> bool {
+            for (driver, value) in self.drivers.iter().zip(inputs) {
+                self.circuit[*driver].gate = Gate::Const(*value);
+            }
+            self.circuit.settle(10).unwrap();
+
+            let q = self.circuit.output_value(self.gate.output(0));
+            if self.circuit[self.gate].gate.output_count() > 1 {
+                assert_eq!(self.circuit.output_value(self.gate.output(1)), !q);
+            }
+            q
+        }
+    }
+
+    #[test]
+    fn sr_latch() {
+        let mut latch = Harness::new(Gate::SrLatch);
+        assert!(!latch.apply(&[false, false]));
+        assert!(latch.apply(&[true, false]));
+        assert!(latch.apply(&[false, false]));
+        assert!(!latch.apply(&[false, true]));
+        assert!(!latch.apply(&[false, false]));
+        // Set dominates
+        assert!(latch.apply(&[true, true]));
+    }
+
+    #[test]
+    fn d_latch() {
+        let mut latch = Harness::new(Gate::DLatch);
+        assert!(!latch.apply(&[true, false]));
+        assert!(latch.apply(&[true, true]));
+        assert!(latch.apply(&[false, false]));
+        assert!(!latch.apply(&[false, true]));
+        assert!(!latch.apply(&[true, false]));
+    }
+
+    #[test]
+    fn d_flip_flop() {
+        let mut flip_flop = Harness::new(Gate::DFlipFlop);
+        assert!(!flip_flop.apply(&[true, false]));
+        assert!(flip_flop.apply(&[true, true]));
+        // Changes while the clock is high are ignored
+        assert!(flip_flop.apply(&[false, true]));
+        assert!(flip_flop.apply(&[false, false]));
+        assert!(!flip_flop.apply(&[false, true]));
+    }
+
+    #[test]
+    fn jk_flip_flop() {
+        let mut flip_flop = Harness::new(Gate::JkFlipFlop);
+        let mut clock = |j, k| {
+            flip_flop.apply(&[j, k, false]);
+            flip_flop.apply(&[j, k, true])
+        };
+
+        assert!(clock(true, false));
+        assert!(clock(false, false));
+        assert!(!clock(false, true));
+        assert!(clock(true, true));
+        assert!(!clock(true, true));
+    }
+
+    #[test]
+    fn register() {
+        let mut circuit = Circuit::default();
+        let register = circuit.add_gate(Gate::Register(4), Vec2::ZERO);
+        let merge = circuit.add_gate(Gate::Merge(4), Vec2::ZERO);
+        circuit
+            .add_connection(merge.output(0).to(register.input(0)).with_width(4))
+            .unwrap();
+
+        let data: Vec<_> = (0..4)
+            .map(|bit| {
+                let driver = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+                circuit
+                    .add_connection(driver.output(0).to(merge.input(bit)))
+                    .unwrap();
+                driver
+            })
+            .collect();
+        let [clock, enable, reset] = [1, 2, 3].map(|pin| {
+            let driver = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+            circuit
+                .add_connection(driver.output(0).to(register.input(pin)))
+                .unwrap();
+            driver
+        });
+
+        let mut apply = |value: u64, clk: bool, en: bool, rst: bool| {
+            for (bit, driver) in data.iter().enumerate() {
+                circuit[*driver].gate = Gate::Const((value >> bit) & 1 == 1);
+            }
+            circuit[clock].gate = Gate::Const(clk);
+            circuit[enable].gate = Gate::Const(en);
+            circuit[reset].gate = Gate::Const(rst);
+            circuit.settle(10).unwrap();
+            circuit.output_bus_value(register.output(0))
+        };
+
+        assert_eq!(apply(0b1011, false, true, false), 0);
+        assert_eq!(apply(0b1011, true, true, false), 0b1011);
+        assert_eq!(apply(0b0110, false, false, false), 0b1011);
+        // Disabled, the clock edge is ignored
+        assert_eq!(apply(0b0110, true, false, false), 0b1011);
+        assert_eq!(apply(0b0110, false, true, false), 0b1011);
+        assert_eq!(apply(0b0110, true, true, false), 0b0110);
+        // Reset doesn't wait for the clock
+        assert_eq!(apply(0b0110, true, true, true), 0);
+    }
+}
+
+#[cfg(test)]
+mod clock {
+    use super::*;
+    use crate::logic::circuit::connection::ElementIdx;
+
+    fn clock_circuit(period: u32, high_ticks: u32, phase: u32) -> (Circuit, ElementIdx) {
+        let mut circuit = Circuit::default();
+        let clock = circuit.add_gate(
+            Gate::Clock {
+                period,
+                high_ticks,
+                phase,
+            },
+            Vec2::ZERO,
+        );
+        (circuit, clock)
+    }
+
+    fn waveform(circuit: &mut Circuit, clock: ElementIdx, steps: usize) -> Vec<bool> {
+        (0..steps)
+            .map(|_| {
+                circuit.step();
+                circuit.output_value(clock.output(0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let (mut circuit, clock) = clock_circuit(4, 1, 0);
+        assert_eq!(
+            waveform(&mut circuit, clock, 8),
+            [true, false, false, false, true, false, false, false]
+        );
+
+        let (mut circuit, clock) = clock_circuit(3, 2, 0);
+        assert_eq!(
+            waveform(&mut circuit, clock, 6),
+            [true, true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn phase() {
+        let (mut circuit, clock) = clock_circuit(4, 2, 1);
+        assert_eq!(
+            waveform(&mut circuit, clock, 8),
+            [true, false, false, true, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn pause_and_pulse() {
+        let (mut circuit, clock) = clock_circuit(2, 1, 0);
+        assert_eq!(waveform(&mut circuit, clock, 1), [true]);
+
+        circuit.set_clock_paused(clock, true);
+        assert!(circuit.clock_paused(clock));
+        assert_eq!(waveform(&mut circuit, clock, 3), [false, false, false]);
+
+        circuit.pulse_clock(clock);
+        assert_eq!(waveform(&mut circuit, clock, 3), [true, false, false]);
+
+        // Resumes from where it was paused
+        circuit.set_clock_paused(clock, false);
+        assert!(!circuit.clock_paused(clock));
+        assert_eq!(waveform(&mut circuit, clock, 2), [false, true]);
+    }
+
+    #[test]
+    fn stays_paused_through_edits() {
+        let mut circuit = Circuit::default();
+        let spare = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let clock = circuit.add_gate(
+            Gate::Clock {
+                period: 2,
+                high_ticks: 1,
+                phase: 0,
+            },
+            Vec2::ZERO,
+        );
+        assert_eq!(waveform(&mut circuit, clock, 1), [true]);
+        circuit.set_clock_paused(clock, true);
+
+        // Removing the gate before the clock moves it to a new index
+        circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        circuit.remove_gate(spare);
+        let clock = ElementIdx(0);
+        assert!(circuit.clock_paused(clock));
+        assert_eq!(waveform(&mut circuit, clock, 3), [false, false, false]);
+
+        // Resumes from where it was paused, rather than from the start of its period
+        circuit.set_clock_paused(clock, false);
+        assert_eq!(waveform(&mut circuit, clock, 2), [false, true]);
+    }
+
+    #[test]
+    fn never_settles() {
+        let (mut circuit, _) = clock_circuit(6, 3, 0);
+        assert_eq!(
+            circuit.settle(20),
+            Err(SettleError::Oscillating { period: 6 })
+        );
+    }
+
+    #[test]
+    fn drives_flip_flop() {
+        let (mut circuit, clock) = clock_circuit(2, 1, 0);
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        circuit
+            .add_connection(flip_flop.output(1).to(flip_flop.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(flip_flop.input(1)))
+            .unwrap();
+
+        // Toggles on every rising edge, dividing the clock by two
+        let q: Vec<_> = (0..8)
+            .map(|_| {
+                circuit.step();
+                circuit.output_value(flip_flop.output(0))
+            })
+            .collect();
+        assert_eq!(q, [false, true, true, false, false, true, true, false]);
+    }
+}
+
+#[cfg(test)]
+mod four_valued {
+    use super::*;
+    use crate::logic::{
+        circuit::{
+            connection::{ElementIdx, OutputSpecifier},
+            embedded::{Component, EmbeddedCircuit},
+        },
+        Logic,
+    };
+    use std::sync::Arc;
+
+    fn four_valued_circuit() -> Circuit {
+        Circuit::default().with_solver_mode(SolverMode::FourValued)
+    }
+
+    fn constant(circuit: &mut Circuit, value: bool) -> OutputSpecifier {
+        let gate = if value { Gate::On } else { Gate::Off };
+        circuit.add_gate(gate, Vec2::ZERO).output(0)
+    }
+
+    fn tri_state(circuit: &mut Circuit, data: bool, enable: bool) -> ElementIdx {
+        let buffer = circuit.add_gate(Gate::TriState, Vec2::ZERO);
+        let data = constant(circuit, data);
+        let enable = constant(circuit, enable);
+        circuit.add_connection(data.to(buffer.input(0))).unwrap();
+        circuit.add_connection(enable.to(buffer.input(1))).unwrap();
+        buffer
+    }
+
+    // Two tri-state buffers driving the input of a buffer
+    fn shared_bus(mode: SolverMode, a: (bool, bool), b: (bool, bool)) -> Logic {
+        let mut circuit = Circuit::default().with_solver_mode(mode);
+        let reader = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        for (data, enable) in [a, b] {
+            let driver = tri_state(&mut circuit, data, enable);
+            circuit
+                .add_connection(driver.output(0).to(reader.input(0)))
+                .unwrap();
+        }
+        circuit.settle(10).unwrap();
+        circuit.output_logic(reader.output(0))
+    }
+
+    #[test]
+    fn undriven_inputs() {
+        let mut circuit = four_valued_circuit();
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        let or = circuit.add_gate(Gate::Or(2), Vec2::ZERO);
+        let xor = circuit.add_gate(Gate::Xor(2), Vec2::ZERO);
+        let off = constant(&mut circuit, false);
+        let on = constant(&mut circuit, true);
+        circuit.add_connection(off.to(and.input(0))).unwrap();
+        circuit.add_connection(on.to(or.input(0))).unwrap();
+        circuit.add_connection(on.to(xor.input(0))).unwrap();
+        circuit.settle(10).unwrap();
+
+        // A known input can decide the output even when the other reads Z
+        assert_eq!(circuit.output_logic(and.output(0)), Logic::Zero);
+        assert_eq!(circuit.output_logic(or.output(0)), Logic::One);
+        assert_eq!(circuit.output_logic(xor.output(0)), Logic::X);
+        assert!(!circuit.output_value(xor.output(0)));
+    }
+
+    #[test]
+    fn traces_read_x_and_z() {
+        let mut component = four_valued_circuit();
+        let enable = component.add_gate(Gate::Input(Some("enable".into())), Vec2::ZERO);
+        let inner = component.add_gate(Gate::TriState, Vec2::ZERO);
+        let on = constant(&mut component, true);
+        let y = component.add_gate(Gate::Output(Some("y".into())), Vec2::ZERO);
+        component.add_connection(on.to(inner.input(0))).unwrap();
+        component
+            .add_connection(enable.output(0).to(inner.input(1)))
+            .unwrap();
+        component
+            .add_connection(inner.output(0).to(y.input(0)))
+            .unwrap();
+        let component = Arc::new(Component::named("driver", component));
+
+        let mut circuit = four_valued_circuit();
+        let buffer = tri_state(&mut circuit, true, false);
+        let reader = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(buffer.output(0).to(reader.input(0)))
+            .unwrap();
+        let instance = circuit.add_gate(EmbeddedCircuit::instance(component).into(), Vec2::ZERO);
+        let off = constant(&mut circuit, false);
+        circuit.add_connection(off.to(instance.input(0))).unwrap();
+
+        let nested = circuit
+            .probe_all()
+            .into_iter()
+            .find(|probe| probe.path == [instance, inner])
+            .unwrap();
+        let outputs = [buffer.output(0), reader.output(0)];
+        let mut probes: Vec<_> = outputs.iter().map(|&io| circuit.probe(io)).collect();
+        probes.push(nested);
+        circuit.start_recording(probes);
+        circuit.settle(10).unwrap();
+
+        let sample = circuit.recording().unwrap().samples.back().unwrap().clone();
+        for (bits, io) in sample.iter().zip(outputs) {
+            assert_eq!(*bits, circuit.output_bits(io));
+            assert_eq!(bits.bit(0), circuit.output_logic(io));
+        }
+        let levels: Vec<_> = sample.iter().map(|bits| bits.bit(0)).collect();
+        assert_eq!(levels, [Logic::Z, Logic::X, Logic::Z]);
+        assert!(!circuit.output_value(buffer.output(0)));
+    }
+
+    #[test]
+    fn conflicting_drivers() {
+        let mut circuit = four_valued_circuit();
+        let agree = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        let conflict = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        // Only tri-state buffers can share an input
+        for (reader, value) in [
+            (agree, true),
+            (agree, true),
+            (conflict, true),
+            (conflict, false),
+        ] {
+            let driver = tri_state(&mut circuit, value, true);
+            circuit
+                .add_connection(driver.output(0).to(reader.input(0)))
+                .unwrap();
+        }
+        circuit.settle(10).unwrap();
+
+        assert_eq!(circuit.output_logic(agree.output(0)), Logic::One);
+        assert_eq!(circuit.output_logic(conflict.output(0)), Logic::X);
+    }
+
+    #[test]
+    fn tri_state_bus() {
+        let mode = SolverMode::FourValued;
+        assert_eq!(shared_bus(mode, (true, true), (false, false)), Logic::One);
+        assert_eq!(shared_bus(mode, (true, false), (false, true)), Logic::Zero);
+        assert_eq!(shared_bus(mode, (true, true), (true, true)), Logic::One);
+        assert_eq!(shared_bus(mode, (true, true), (false, true)), Logic::X);
+        // Nothing drives the bus, which the buffer reads as X
+        assert_eq!(shared_bus(mode, (true, false), (true, false)), Logic::X);
+
+        let mut circuit = four_valued_circuit();
+        let released = tri_state(&mut circuit, true, false);
+        circuit.settle(10).unwrap();
+        assert_eq!(circuit.output_logic(released.output(0)), Logic::Z);
+        // Floating nets don't read as high through the two-valued accessors
+        assert!(!circuit.output_value(released.output(0)));
+        assert_eq!(circuit.output_bus_value(released.output(0)), 0);
+    }
+
+    #[test]
+    fn two_valued_tri_state_bus() {
+        let mode = SolverMode::Sweep;
+        assert_eq!(shared_bus(mode, (true, true), (false, false)), Logic::One);
+        assert_eq!(shared_bus(mode, (true, false), (false, true)), Logic::Zero);
+        assert_eq!(shared_bus(mode, (true, false), (true, false)), Logic::Zero);
+    }
+
+    #[test]
+    fn uninitialised_flip_flop() {
+        let mut circuit = four_valued_circuit();
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        let or = circuit.add_gate(Gate::Or(2), Vec2::ZERO);
+        let data = circuit.add_gate(Gate::Const(true), Vec2::ZERO);
+        let clock = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+        let off = constant(&mut circuit, false);
+        circuit
+            .add_connection(data.output(0).to(flip_flop.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(flip_flop.input(1)))
+            .unwrap();
+        for gate in [and, or] {
+            circuit
+                .add_connection(flip_flop.output(0).to(gate.input(0)))
+                .unwrap();
+        }
+        circuit.add_connection(off.to(and.input(1))).unwrap();
+        circuit.add_connection(off.to(or.input(1))).unwrap();
+        circuit.settle(10).unwrap();
+
+        assert_eq!(circuit.output_logic(flip_flop.output(0)), Logic::X);
+        assert_eq!(circuit.output_logic(flip_flop.output(1)), Logic::X);
+        assert_eq!(circuit.output_logic(and.output(0)), Logic::Zero);
+        assert_eq!(circuit.output_logic(or.output(0)), Logic::X);
+
+        // A clock edge loads a known value
+        circuit[clock].gate = Gate::Const(true);
+        circuit.settle(10).unwrap();
+        assert_eq!(circuit.output_logic(flip_flop.output(0)), Logic::One);
+        assert_eq!(circuit.output_logic(flip_flop.output(1)), Logic::Zero);
+        assert_eq!(circuit.output_logic(or.output(0)), Logic::One);
+    }
+
+    #[test]
+    fn matches_two_valued_when_known() {
+        let mut sweep = Circuit::full_adder();
+        let mut four_valued = sweep.clone().with_solver_mode(SolverMode::FourValued);
+        for circuit in [&mut sweep, &mut four_valued] {
+            for (port, value) in circuit.input_ports().into_iter().zip([1, 0, 1]) {
+                circuit.set_input(port, value);
+            }
+        }
+
+        sweep.settle(20).unwrap();
+        four_valued.settle(20).unwrap();
+
+        for (index, element) in sweep.elements.iter().enumerate() {
+            for pin in 0..element.gate.output_count() {
+                let output = ElementIdx(index).output(pin);
+                let expected = match sweep.output_value(output) {
+                    true => Logic::One,
+                    false => Logic::Zero,
+                };
+                assert_eq!(four_valued.output_logic(output), expected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod timing {
+    use super::*;
+    use crate::logic::circuit::{
+        connection::{ElementIdx, OutputSpecifier},
+        element::Delay,
+    };
+
+    fn waveform(circuit: &mut Circuit, output: OutputSpecifier, steps: usize) -> Vec<bool> {
+        (0..steps)
+            .map(|_| {
+                circuit.step();
+                circuit.output_value(output)
+            })
+            .collect()
+    }
+
+    // A input and its inverse into an AND, which glitches while the inverter catches up
+    fn hazard(inverter_delay: u32) -> (Circuit, ElementIdx, OutputSpecifier) {
+        let mut circuit = Circuit::default().with_solver_mode(SolverMode::Timing);
+        let input = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        circuit
+            .add_connection(input.output(0).to(not.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(input.output(0).to(and.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(not.output(0).to(and.input(1)))
+            .unwrap();
+        circuit.set_delay(not, Delay::uniform(inverter_delay));
+        circuit.settle(20).unwrap();
+
+        (circuit, input, and.output(0))
+    }
+
+    #[test]
+    fn glitch_width_follows_delay() {
+        for delay in 1..5 {
+            let (mut circuit, input, output) = hazard(delay);
+            circuit.click_gate(input);
+
+            let wave = waveform(&mut circuit, output, 10);
+            let high = wave.iter().filter(|high| **high).count();
+            assert_eq!(high, delay as usize, "{wave:?}");
+            assert!(!wave.last().unwrap());
+        }
+    }
+
+    #[test]
+    fn rise_and_fall_delays() {
+        let mut circuit = Circuit::default().with_solver_mode(SolverMode::Timing);
+        let input = circuit.add_gate(Gate::Const(false), Vec2::ZERO);
+        let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(input.output(0).to(buf.input(0)))
+            .unwrap();
+        circuit.set_delay(buf, Delay::new(3, 1));
+        circuit.settle(10).unwrap();
+
+        circuit.click_gate(input);
+        assert_eq!(
+            waveform(&mut circuit, buf.output(0), 4),
+            [false, false, false, true]
+        );
+
+        circuit.click_gate(input);
+        assert_eq!(waveform(&mut circuit, buf.output(0), 2), [true, false]);
+    }
+
+    #[test]
+    fn transport_delay_keeps_short_pulses() {
+        let mut circuit = Circuit::default().with_solver_mode(SolverMode::Timing);
+        let button = circuit.add_gate(Gate::Button, Vec2::ZERO);
+        let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(button.output(0).to(buf.input(0)))
+            .unwrap();
+        circuit.set_delay(buf, Delay::uniform(4));
+        circuit.settle(10).unwrap();
+
+        // The button is high for a single step
+        circuit.click_gate(button);
+        assert_eq!(
+            waveform(&mut circuit, buf.output(0), 7),
+            [false, false, false, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn settles_after_pending_changes() {
+        let (mut circuit, input, output) = hazard(3);
+        circuit.click_gate(input);
+
+        assert!(circuit.settle(20).is_ok());
+        assert!(!circuit.output_value(output));
+    }
+
+    #[test]
+    fn delays_round_trip() {
+        let (circuit, _, _) = hazard(3);
+        let loaded = Circuit::from_json(&circuit.to_json()).unwrap();
+        let delays: Vec<_> = loaded
+            .elements
+            .iter()
+            .map(|element| element.delay)
+            .collect();
+        assert_eq!(
+            delays,
+            [Delay::default(), Delay::uniform(3), Delay::default()]
+        );
+    }
+}
+
+#[cfg(test)]
+mod ports {
+    use super::*;
+
+    #[test]
+    fn named_ports() {
+        let adder = Circuit::full_adder().embed();
+        let names = |names: &[Option<String>]| -> Vec<_> {
+            names.iter().map(|name| name.clone().unwrap()).collect()
+        };
+
+        assert_eq!(names(adder.input_names()), ["a", "b", "carry"]);
+        assert_eq!(names(adder.output_names()), ["sum", "carry_out"]);
+    }
+
+    #[test]
+    fn ordered_by_position() {
+        let mut circuit = Circuit::default();
+        let low = circuit.add_gate(Gate::Input(Some("low".into())), Vec2::new(0.0, -1.0));
+        let high = circuit.add_gate(Gate::Input(Some("high".into())), Vec2::new(0.0, 1.0));
+        let right = circuit.add_gate(Gate::Input(Some("right".into())), Vec2::new(1.0, 0.0));
+        let left = circuit.add_gate(Gate::Input(Some("left".into())), Vec2::new(-1.0, 0.0));
+        assert_eq!(circuit.input_ports(), [high, left, right, low]);
+
+        // Moving a port reorders the pins
+        circuit[low].position.y = 2.0;
+        assert_eq!(circuit.input_ports(), [low, high, left, right]);
+    }
+
+    #[test]
+    fn stable_when_editing_inside() {
+        let mut circuit = Circuit::default();
+        let a = circuit.add_gate(Gate::Input(Some("a".into())), Vec2::new(0.0, 1.0));
+        let b = circuit.add_gate(Gate::Input(Some("b".into())), Vec2::new(0.0, 0.0));
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        let out = circuit.add_gate(Gate::Output(None), Vec2::new(2.0, 0.0));
+        circuit
+            .add_connection(a.output(0).to(and.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(b.output(0).to(and.input(1)))
+            .unwrap();
+        circuit
+            .add_connection(and.output(0).to(out.input(0)))
+            .unwrap();
+
+        // Disconnected pins of other gates don't become ports
+        circuit.add_gate(Gate::Not, Vec2::ZERO);
+        circuit.remove_gate(and);
+        circuit.add_gate(Gate::Or(2), Vec2::ZERO);
+
+        let embed = circuit.embed();
+        assert_eq!(embed.input_count(), 2);
+        assert_eq!(embed.output_count(), 1);
+        assert_eq!(embed.input_names()[0].as_deref(), Some("a"));
+        assert_eq!(embed.input_names()[1].as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn drives_top_level_inputs() {
+        let mut circuit = Circuit::full_adder();
+        let [a, b, carry] = circuit.input_ports()[..] else {
+            panic!("expected three inputs")
+        };
+        let [sum, carry_out] = circuit.output_ports()[..] else {
+            panic!("expected two outputs")
+        };
+
+        circuit.set_input(a, 1);
+        circuit.set_input(b, 1);
+        circuit.set_input(carry, 1);
+        circuit.settle(20).unwrap();
+
+        assert!(circuit.output_value(sum.output(0)));
+        assert!(circuit.output_value(carry_out.output(0)));
+    }
+
+    #[test]
+    fn embedded_round_trip_keeps_order() {
+        let mut circuit = Circuit::default();
+        circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
+
+        let loaded = Circuit::from_json(&circuit.to_json()).unwrap();
+        let Gate::Embedded(embed) = &loaded.elements[0].gate else {
+            panic!("expected an embedded circuit")
+        };
+        assert_eq!(embed.input_names()[2].as_deref(), Some("carry"));
+        assert_eq!(embed.output_names()[1].as_deref(), Some("carry_out"));
+    }
+}
+
+#[cfg(test)]
+mod components {
+    use super::*;
+    use crate::logic::circuit::{connection::ElementIdx, embedded::Component};
+    use std::sync::Arc;
+
+    // A component with one input and one output through `gate`
+    fn wrap(gate: Gate) -> Circuit {
+        let mut circuit = Circuit::default();
+        let input = circuit.add_gate(Gate::Input(None), Vec2::ZERO);
+        let gate = circuit.add_gate(gate, Vec2::new(1.0, 0.0));
+        let output = circuit.add_gate(Gate::Output(None), Vec2::new(2.0, 0.0));
+        circuit
+            .add_connection(input.output(0).to(gate.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(gate.output(0).to(output.input(0)))
+            .unwrap();
+        circuit
+    }
+
+    fn component_of(circuit: &Circuit, element: ElementIdx) -> &Arc<Component> {
+        let Gate::Embedded(embed) = &circuit[element].gate else {
+            panic!("expected an embedded circuit")
+        };
+        embed.component()
+    }
+
+    #[test]
+    fn instances_share_definition() {
+        let circuit = Circuit::adder_8_bit();
+        let first = component_of(&circuit, ElementIdx(1));
+        for element in 2..circuit.elements.len() {
+            assert!(Arc::ptr_eq(
+                first,
+                component_of(&circuit, ElementIdx(element))
+            ));
+        }
+    }
+
+    #[test]
+    fn instances_keep_separate_state() {
+        // A latch which is set by its input and never reset
+        let mut latch = Circuit::default();
+        let input = latch.add_gate(Gate::Input(None), Vec2::ZERO);
+        let sr = latch.add_gate(Gate::SrLatch, Vec2::new(1.0, 0.0));
+        let output = latch.add_gate(Gate::Output(None), Vec2::new(2.0, 0.0));
+        latch
+            .add_connection(input.output(0).to(sr.input(0)))
+            .unwrap();
+        latch
+            .add_connection(sr.output(0).to(output.input(0)))
+            .unwrap();
+        let latch = latch.embed();
+
+        let mut circuit = Circuit::default();
+        let button = circuit.add_gate(Gate::Button, Vec2::ZERO);
+        let set = circuit.add_gate(latch.clone().into(), Vec2::ZERO);
+        let unset = circuit.add_gate(latch.into(), Vec2::ZERO);
+        circuit
+            .add_connection(button.output(0).to(set.input(0)))
+            .unwrap();
+
+        circuit.click_gate(button);
+        circuit.settle(20).unwrap();
+
+        assert!(circuit.output_value(set.output(0)));
+        assert!(!circuit.output_value(unset.output(0)));
+    }
+
+    #[test]
+    fn redefining_updates_every_instance() {
+        let invert = Arc::new(Component::named("invert", wrap(Gate::Not)));
+
+        let mut circuit = Circuit::default();
+        let off = circuit.add_gate(Gate::Off, Vec2::ZERO).output(0);
+        let instances = [0, 1].map(|_| {
+            let gate = EmbeddedCircuit::instance(invert.clone()).into();
+            let instance = circuit.add_gate(gate, Vec2::ZERO);
+            circuit.add_connection(off.to(instance.input(0))).unwrap();
+            instance
+        });
+        circuit.settle(20).unwrap();
+        assert!(instances.iter().all(|i| circuit.output_value(i.output(0))));
+
+        let invert = Arc::new(invert.redefine(wrap(Gate::Buf)));
+        assert!(circuit.update_component(&invert).is_some());
+        circuit.settle(20).unwrap();
+        assert!(instances.iter().all(|i| !circuit.output_value(i.output(0))));
+        assert!(Arc::ptr_eq(component_of(&circuit, instances[0]), &invert));
+    }
+
+    #[test]
+    fn redefining_updates_nested_instances() {
+        let invert = Arc::new(Component::named("invert", wrap(Gate::Not)));
+        let outer = wrap(EmbeddedCircuit::instance(invert.clone()).into());
+        let outer = Arc::new(Component::named("outer", outer));
+
+        let mut circuit = Circuit::default();
+        let first = circuit.add_gate(EmbeddedCircuit::instance(outer.clone()).into(), Vec2::ZERO);
+        let second = circuit.add_gate(EmbeddedCircuit::instance(outer.clone()).into(), Vec2::ZERO);
+
+        assert!(circuit
+            .update_component(&Arc::new(invert.redefine(wrap(Gate::Buf))))
+            .is_some());
+        circuit.settle(20).unwrap();
+
+        assert!(!circuit.output_value(first.output(0)));
+        // Both instances share the new version of the outer component, which is still itself
+        let updated = component_of(&circuit, first);
+        assert!(Arc::ptr_eq(updated, component_of(&circuit, second)));
+        assert!(!Arc::ptr_eq(updated, &outer));
+        assert_eq!(updated.id(), outer.id());
+    }
+
+    #[test]
+    fn redefining_removes_connections_to_missing_pins() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        let adder = Circuit::full_adder().embed();
+        let instance = circuit.add_gate(adder.clone().into(), Vec2::ZERO);
+        circuit.add_connection(on.to(instance.input(0))).unwrap();
+        circuit.add_connection(on.to(instance.input(2))).unwrap();
+
+        let component = Arc::new(adder.component().redefine(wrap(Gate::Buf)));
+        assert_eq!(
+            circuit.update_component(&component),
+            Some(vec![on.to(instance.input(2))])
+        );
+        assert_eq!(circuit.connections, [on.to(instance.input(0))]);
+    }
+
+    #[test]
+    fn redefining_follows_pins_by_name() {
+        // y = a & !b, with the ports of a and b in either order
+        let definition = |a_y: f32, b_y: f32| {
+            let mut circuit = Circuit::default();
+            let a = circuit.add_gate(Gate::Input(Some("a".into())), Vec2::new(0.0, a_y));
+            let b = circuit.add_gate(Gate::Input(Some("b".into())), Vec2::new(0.0, b_y));
+            let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+            let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+            let y = circuit.add_gate(Gate::Output(Some("y".into())), Vec2::ZERO);
+            for connection in [
+                a.output(0).to(and.input(0)),
+                b.output(0).to(not.input(0)),
+                not.output(0).to(and.input(1)),
+                and.output(0).to(y.input(0)),
+            ] {
+                circuit.add_connection(connection).unwrap();
+            }
+            circuit
+        };
+        let component = Arc::new(Component::named("a and not b", definition(1.0, 0.0)));
+
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        let off = circuit.add_gate(Gate::Off, Vec2::ZERO).output(0);
+        let instance = circuit.add_gate(
+            EmbeddedCircuit::instance(component.clone()).into(),
+            Vec2::ZERO,
+        );
+        circuit.add_connection(on.to(instance.input(0))).unwrap();
+        circuit.add_connection(off.to(instance.input(1))).unwrap();
+        circuit.settle(20).unwrap();
+        assert!(circuit.output_value(instance.output(0)));
+
+        // Moving b above a swaps their pins, and the connections move with them
+        let component = Arc::new(component.redefine(definition(0.0, 1.0)));
+        assert_eq!(circuit.update_component(&component), Some(vec![]));
+        assert_eq!(
+            circuit.connections,
+            [on.to(instance.input(1)), off.to(instance.input(0))]
+        );
+        circuit.settle(20).unwrap();
+        assert!(circuit.output_value(instance.output(0)));
+    }
+
+    #[test]
+    fn file_stores_each_component_once() {
+        let json = Circuit::adder_8_bit().to_json();
+        assert_eq!(json.matches("\"Xor\"").count(), 2);
+
+        let loaded = Circuit::from_json(&json).unwrap();
+        let first = component_of(&loaded, ElementIdx(1));
+        for element in 2..loaded.elements.len() {
+            assert!(Arc::ptr_eq(
+                first,
+                component_of(&loaded, ElementIdx(element))
+            ));
+        }
+    }
+
+    #[test]
+    fn migrates_inline_circuits() {
+        let embedded = r#"{"gate":{"kind":"Embedded","circuit":{
+            "elements":[{"gate":{"kind":"Not"},"position":[0,0]}],
+            "connections":[]
+        }},"position":[0,0]}"#;
+        let json = format!(
+            r#"{{"version":4,"circuit":{{"elements":[{embedded},{embedded}],"connections":[]}}}}"#
+        );
+
+        let mut circuit = Circuit::from_json(&json).unwrap();
+        assert!(Arc::ptr_eq(
+            component_of(&circuit, ElementIdx(0)),
+            component_of(&circuit, ElementIdx(1))
+        ));
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(ElementIdx(0).output(0)));
+    }
+}
+
+#[cfg(test)]
+mod flatten {
+    use super::*;
+    use crate::logic::circuit::connection::ElementIdx;
+
+    fn invert() -> Circuit {
+        let mut circuit = Circuit::default();
+        let input = circuit.add_gate(Gate::Input(None), Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::new(1.0, 0.0));
+        let output = circuit.add_gate(Gate::Output(None), Vec2::new(2.0, 0.0));
+        circuit
+            .add_connection(input.output(0).to(not.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(not.output(0).to(output.input(0)))
+            .unwrap();
+        circuit
+    }
+
+    #[test]
+    fn inlines_every_instance() {
+        let circuit = Circuit::adder_8_bit();
+        let flat = circuit.flatten();
+
+        // The carry constant and five gates per adder, without any ports
+        assert_eq!(flat.circuit().elements.len(), 1 + 8 * 5);
+        assert!(!flat.circuit().has_embedded());
+
+        let xor = flat.element(&[ElementIdx(3), ElementIdx(3)]).unwrap();
+        assert_eq!(flat.path(xor), [ElementIdx(3), ElementIdx(3)]);
+        assert!(matches!(flat.circuit()[xor].gate, Gate::Xor(2)));
+        assert_eq!(flat.element(&[ElementIdx(3), ElementIdx(0)]), None);
+    }
+
+    #[test]
+    fn connects_through_ports() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let outer = circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        circuit
+            .add_connection(on.output(0).to(outer.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(outer.output(0).to(not.input(0)))
+            .unwrap();
+
+        let flat = circuit.flatten();
+        let on = flat.element(&[on]).unwrap();
+        let not = flat.element(&[not]).unwrap();
+        // The sum comes from the second XOR of the adder
+        let sum = flat.element(&[outer, ElementIdx(5)]).unwrap();
+        assert!(flat
+            .circuit()
+            .connections
+            .contains(&sum.output(0).to(not.input(0))));
+        assert_eq!(flat.sources(outer.output(0)), [sum.output(0)]);
+        assert!(flat
+            .circuit()
+            .connections
+            .iter()
+            .any(|connection| connection.from == on.output(0)));
+    }
+
+    #[test]
+    fn pins_add_no_delay() {
+        let inner = invert().embed();
+        let mut outer = Circuit::default();
+        let input = outer.add_gate(Gate::Input(None), Vec2::ZERO);
+        let instance = outer.add_gate(inner.into(), Vec2::new(1.0, 0.0));
+        let output = outer.add_gate(Gate::Output(None), Vec2::new(2.0, 0.0));
+        outer
+            .add_connection(input.output(0).to(instance.input(0)))
+            .unwrap();
+        outer
+            .add_connection(instance.output(0).to(output.input(0)))
+            .unwrap();
+
+        let mut circuit = Circuit::default();
+        let off = circuit.add_gate(Gate::Off, Vec2::ZERO);
+        let nested = circuit.add_gate(outer.embed().into(), Vec2::ZERO);
+        circuit
+            .add_connection(off.output(0).to(nested.input(0)))
+            .unwrap();
+
+        // A single gate, however deeply it's nested, takes a single step
+        let mut flattened = circuit.clone();
+        flattened.step_n(2);
+        assert!(flattened.output_value(nested.output(0)));
+
+        let mut hierarchical = circuit;
+        hierarchical.set_hierarchical(true);
+        hierarchical.step_n(2);
+        assert!(!hierarchical.output_value(nested.output(0)));
+        hierarchical.settle(20).unwrap();
+        assert!(hierarchical.output_value(nested.output(0)));
+    }
+
+    #[test]
+    fn matches_hierarchical() {
+        for inputs in 0..8 {
+            let mut circuit = Circuit::default();
+            let adder = circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
+            for pin in 0..3 {
+                let value = circuit.add_gate(Gate::Const(inputs >> pin & 1 == 1), Vec2::ZERO);
+                circuit
+                    .add_connection(value.output(0).to(adder.input(pin)))
+                    .unwrap();
+            }
+
+            let mut hierarchical = circuit.clone();
+            hierarchical.set_hierarchical(true);
+            circuit.settle(20).unwrap();
+            hierarchical.settle(20).unwrap();
+
+            for pin in 0..2 {
+                assert_eq!(
+                    circuit.output_value(adder.output(pin)),
+                    hierarchical.output_value(adder.output(pin))
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod lanes {
+    use super::*;
+    use crate::logic::circuit::{exhaustive_lanes, LaneError};
+
+    #[test]
+    fn exhaustive_patterns() {
+        assert_eq!(
+            exhaustive_lanes(3),
+            [
+                0xaaaa_aaaa_aaaa_aaaa,
+                0xcccc_cccc_cccc_cccc,
+                0xf0f0_f0f0_f0f0_f0f0
+            ]
+        );
+        assert_eq!(exhaustive_lanes(7)[6], 0);
+    }
+
+    #[test]
+    fn full_adder_in_one_pass() {
+        let mut circuit = Circuit::full_adder();
+        let inputs = exhaustive_lanes(3);
+        let outputs = circuit.simulate_lanes(&inputs, 20).unwrap();
+
+        for lane in 0..8 {
+            let bit = |value: u64| (value >> lane) & 1;
+            let total = bit(inputs[0]) + bit(inputs[1]) + bit(inputs[2]);
+            assert_eq!(bit(outputs[0]), total & 1);
+            assert_eq!(bit(outputs[1]), total >> 1);
+        }
+    }
+
+    #[test]
+    fn lanes_keep_separate_state() {
+        let mut circuit = Circuit::default();
+        let data = circuit.add_gate(Gate::Input(Some("d".into())), Vec2::new(0.0, 1.0));
+        let clock = circuit.add_gate(Gate::Input(Some("clk".into())), Vec2::ZERO);
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        circuit
+            .add_connection(data.output(0).to(flip_flop.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(flip_flop.input(1)))
+            .unwrap();
+
+        circuit.set_input_lanes(data, 0b0110);
+        circuit.set_input_lanes(clock, 0b1100);
+        circuit.settle(10).unwrap();
+        assert_eq!(circuit.output_lanes(flip_flop.output(0)) & 0b1111, 0b0100);
+    }
+
+    #[test]
+    fn rejects_buses() {
+        let mut circuit = Circuit::default();
+        circuit.add_gate(Gate::Merge(2), Vec2::ZERO);
+        assert_eq!(
+            circuit.simulate_lanes(&[], 10),
+            Err(LaneError::NotLaneParallel)
+        );
+
+        let mut circuit = Circuit::full_adder();
+        assert_eq!(
+            circuit.simulate_lanes(&[0; 4], 10),
+            Err(LaneError::TooManyInputs {
+                expected: 3,
+                found: 4
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod truth_table {
+    use super::*;
+    use crate::logic::circuit::{Column, TruthTableError};
+
+    #[test]
+    fn full_adder() {
+        let table = Circuit::full_adder().embed().truth_table().unwrap();
+        let names: Vec<_> = table.inputs.iter().map(|column| &column.name).collect();
+        assert_eq!(names, ["a", "b", "carry"]);
+        assert_eq!(table.rows.len(), 8);
+
+        for (row, values) in table.rows.iter().enumerate() {
+            let total = values.inputs.iter().sum::<u64>();
+            assert_eq!(values.inputs[0], row as u64 >> 2);
+            assert_eq!(values.outputs, [total & 1, total >> 1]);
+        }
+        assert_eq!(table.lookup(&[1, 0, 1]), Some(&[0, 1][..]));
+        assert_eq!(table.lookup(&[2, 0, 1]), None);
+    }
+
+    #[test]
+    fn buses_and_state() {
+        // Registers aren't lane parallel, so each row is simulated on its own
+        let mut circuit = Circuit::default();
+        let data = circuit.add_gate(Gate::Input(None), Vec2::new(0.0, 1.0));
+        let enable = circuit.add_gate(Gate::Input(Some("en".into())), Vec2::ZERO);
+        let clock = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let register = circuit.add_gate(Gate::Register(2), Vec2::ZERO);
+        let out = circuit.add_gate(Gate::Output(None), Vec2::ZERO);
+        circuit
+            .add_connection(data.output(0).to(register.input(0)).with_width(2))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(register.input(1)))
+            .unwrap();
+        circuit
+            .add_connection(enable.output(0).to(register.input(2)))
+            .unwrap();
+        circuit
+            .add_connection(register.output(0).to(out.input(0)).with_width(2))
+            .unwrap();
+
+        let table = circuit.truth_table().unwrap();
+        assert_eq!(
+            table.inputs,
+            [
+                Column {
+                    name: "in0".into(),
+                    width: 2
+                },
+                Column {
+                    name: "en".into(),
+                    width: 1
+                }
+            ]
+        );
+        assert_eq!(table.lookup(&[0b10, 1]), Some(&[0b10][..]));
+        assert_eq!(table.lookup(&[0b10, 0]), Some(&[0][..]));
+    }
+
+    #[test]
+    fn formats() {
+        let mut circuit = Circuit::default();
+        let a = circuit.add_gate(Gate::Input(Some("a".into())), Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let out = circuit.add_gate(Gate::Output(Some("y".into())), Vec2::ZERO);
+        circuit
+            .add_connection(a.output(0).to(not.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(not.output(0).to(out.input(0)))
+            .unwrap();
+
+        let table = circuit.truth_table().unwrap();
+        assert_eq!(table.to_csv(), "a,y\n0,1\n1,0\n");
+        assert_eq!(
+            table.to_markdown(),
+            "| a | y |\n| --- | --- |\n| 0 | 1 |\n| 1 | 0 |\n"
+        );
+        assert_eq!(table.to_string(), " a | y\n 0 | 1\n 1 | 0\n");
+    }
+
+    #[test]
+    fn rejects_wide_inputs() {
+        let mut circuit = Circuit::default();
+        let wide = circuit.add_gate(Gate::Input(None), Vec2::ZERO);
+        let out = circuit.add_gate(Gate::Output(None), Vec2::ZERO);
+        circuit
+            .add_connection(wide.output(0).to(out.input(0)).with_width(32))
+            .unwrap();
+        assert_eq!(
+            circuit.truth_table(),
+            Err(TruthTableError::TooManyInputs(32))
+        );
+    }
+
+    #[test]
+    fn named_ports() {
+        let mut circuit = Circuit::full_adder();
+        assert!(circuit.set_named_input("a", 1));
+        assert!(circuit.set_named_input("carry", 1));
+        assert!(!circuit.set_named_input("sum", 1));
+        circuit.settle(20).unwrap();
+        assert_eq!(
+            circuit.named_outputs(),
+            [("sum".to_string(), 0), ("carry_out".to_string(), 1)]
+        );
+    }
+}
+
+#[cfg(test)]
+mod synth {
+    use super::*;
+    use crate::logic::synth::{minimize, Expr, ParseError, SynthError};
+
+    fn var(name: &str) -> Expr {
+        Expr::Var(name.into())
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        assert_eq!(
+            Expr::parse("a | b & !c ^ d").unwrap(),
+            Expr::Or(vec![
+                var("a"),
+                Expr::Xor(vec![
+                    Expr::And(vec![var("b"), Expr::Not(Box::new(var("c")))]),
+                    var("d")
+                ])
+            ])
+        );
+        assert_eq!(
+            Expr::parse("~(x + 1) * y_2").unwrap(),
+            Expr::And(vec![
+                Expr::Not(Box::new(Expr::Or(vec![var("x"), Expr::Const(true)]))),
+                var("y_2")
+            ])
+        );
+        assert_eq!(Expr::parse("a & "), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            Expr::parse("a b"),
+            Err(ParseError::UnexpectedCharacter(2, 'b'))
+        );
+    }
+
+    #[test]
+    fn minimizes_with_dont_cares() {
+        let minterms = [4, 8, 10, 11, 12, 15];
+        let cover = minimize(4, &minterms, &[9, 14]);
+        assert_eq!(cover.len(), 3);
+
+        for row in 0..16 {
+            let covered = cover.iter().any(|implicant| implicant.covers(row));
+            if minterms.contains(&row) {
+                assert!(covered);
+            } else if ![9, 14].contains(&row) {
+                assert!(!covered);
+            }
+        }
+    }
+
+    #[test]
+    fn minimizes_wide_functions_quickly() {
+        let mut seed = 0x2545_f491u32;
+        let minterms: Vec<u32> = (0..1 << 12)
+            .filter(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                !seed.is_multiple_of(8)
+            })
+            .collect();
+
+        let start = std::time::Instant::now();
+        let cover = minimize(12, &minterms, &[]);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        for row in 0..1 << 12 {
+            let covered = cover.iter().any(|implicant| implicant.covers(row));
+            assert_eq!(covered, minterms.binary_search(&row).is_ok());
+        }
+    }
+
+    #[test]
+    fn expression_circuit_matches_expression() {
+        let source = "a & !b | c ^ (a | d)";
+        let expr = Expr::parse(source).unwrap();
+        let circuit = Circuit::from_expression(&format!("y = {source}")).unwrap();
+
+        let table = circuit.truth_table().unwrap();
+        assert_eq!(table.outputs[0].name, "y");
+        let variables: Vec<_> = table
+            .inputs
+            .iter()
+            .map(|column| column.name.clone())
+            .collect();
+        assert_eq!(variables, expr.variables());
+
+        let minterms = expr.minterms(&variables);
+        for (row, values) in table.rows.iter().enumerate() {
+            assert_eq!(values.outputs[0] == 1, minterms.contains(&(row as u32)));
+        }
+    }
+
+    #[test]
+    fn constants() {
+        for (source, value) in [("1", 1), ("a & !a", 0)] {
+            let table = Circuit::from_expression(source)
+                .unwrap()
+                .truth_table()
+                .unwrap();
+            assert!(table.rows.iter().all(|row| row.outputs == [value]));
+        }
+    }
+
+    #[test]
+    fn round_trips_truth_table() {
+        let table = Circuit::full_adder().truth_table().unwrap();
+        let circuit = Circuit::from_truth_table(&table).unwrap();
+        assert_eq!(circuit.truth_table().unwrap(), table);
+
+        assert!(matches!(
+            Circuit::from_expression("a &"),
+            Err(SynthError::Parse(ParseError::UnexpectedEnd))
+        ));
+    }
+}
+
+#[cfg(test)]
+mod equivalence {
+    use super::*;
+    use crate::logic::equivalence::{check, check_with, Equivalence, EquivalenceError, Method};
+
+    // A ripple carry adder with named bit inputs, built from the given full adder
+    fn ripple_adder(bits: usize, full_adder: &Circuit) -> Circuit {
+        let full_adder = full_adder.embed();
+        let mut circuit = Circuit::default();
+        let mut carry = circuit.add_gate(Gate::Off, Vec2::ZERO).output(0);
+        for bit in 0..bits {
+            let y = -(bit as f32) * 3.0;
+            let a = circuit.add_gate(Gate::Input(Some(format!("a{bit}"))), Vec2::new(0.0, y));
+            let b = circuit.add_gate(
+                Gate::Input(Some(format!("b{bit}"))),
+                Vec2::new(0.0, y - 1.0),
+            );
+            let adder = circuit.add_gate(full_adder.clone().into(), Vec2::ZERO);
+            circuit
+                .add_connection(a.output(0).to(adder.input(0)))
+                .unwrap();
+            circuit
+                .add_connection(b.output(0).to(adder.input(1)))
+                .unwrap();
+            circuit.add_connection(carry.to(adder.input(2))).unwrap();
+
+            let sum = circuit.add_gate(Gate::Output(Some(format!("s{bit}"))), Vec2::new(4.0, y));
+            circuit
+                .add_connection(adder.output(0).to(sum.input(0)))
+                .unwrap();
+            carry = adder.output(1);
+        }
+        circuit
+    }
+
+    // The sum of a full adder, which is wrongly high whenever both inputs are
+    fn broken_full_adder() -> Circuit {
+        Circuit::from_expression(
+            "sum = a & !b & !carry | !a & b & !carry | carry & !a & !b | a & b",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn synthesized_full_adder() {
+        let adder = Circuit::full_adder();
+        let table = adder.truth_table().unwrap();
+        let synthesized = Circuit::from_truth_table(&table).unwrap();
+        for method in [Method::Exhaustive, Method::Sat] {
+            assert_eq!(
+                check_with(&adder, &synthesized, method),
+                Ok(Equivalence::Equivalent)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_ports_by_name() {
+        let a = Circuit::from_expression("y = p & !q").unwrap();
+        // The same function with the inputs in the other order
+        let b = Circuit::from_expression("y = !q & p").unwrap();
+        assert_eq!(a.input_columns()[0].name, "p");
+        assert_eq!(b.input_columns()[0].name, "q");
+        for method in [Method::Exhaustive, Method::Sat] {
+            assert_eq!(check_with(&a, &b, method), Ok(Equivalence::Equivalent));
+        }
+    }
+
+    #[test]
+    fn finds_counterexamples() {
+        let mut adder = Circuit::full_adder();
+        // Drop the carry out, so both circuits only have a sum
+        let carry_out = adder.output_ports()[1];
+        adder.remove_gate(carry_out);
+        let broken = broken_full_adder();
+
+        for method in [Method::Exhaustive, Method::Sat] {
+            let Ok(Equivalence::Different(counterexample)) = check_with(&adder, &broken, method)
+            else {
+                panic!("expected a counterexample");
+            };
+            let inputs: Vec<_> = counterexample
+                .inputs
+                .iter()
+                .map(|(_, value)| *value)
+                .collect();
+            assert_eq!(inputs, [1, 1, 0]);
+            let mismatches: Vec<_> = counterexample.mismatches().collect();
+            assert_eq!(mismatches.len(), 1);
+            assert_eq!((mismatches[0].a, mismatches[0].b), (0, 1));
+        }
+    }
+
+    #[test]
+    fn large_circuits_use_sat() {
+        let full_adder = Circuit::full_adder();
+        let synthesized = Circuit::from_truth_table(&full_adder.truth_table().unwrap()).unwrap();
+        let a = ripple_adder(8, &full_adder);
+        let b = ripple_adder(8, &synthesized);
+        assert_eq!(check(&a, &b), Ok(Equivalence::Equivalent));
+
+        // Only the sum of the top bit is broken
+        let mut broken = ripple_adder(8, &synthesized);
+        let top = broken
+            .elements
+            .iter()
+            .rposition(|element| matches!(element.gate, Gate::Embedded(_)))
+            .unwrap();
+        broken.elements[top].gate = Circuit::from_expression("sum = a ^ b")
+            .map(|mut circuit| {
+                circuit.add_gate(Gate::Input(Some("carry".into())), Vec2::new(0.0, -5.0));
+                circuit.add_gate(Gate::Output(Some("carry_out".into())), Vec2::new(8.0, -5.0));
+                circuit
+            })
+            .unwrap()
+            .embed()
+            .into();
+
+        let Ok(Equivalence::Different(counterexample)) = check(&a, &broken) else {
+            panic!("expected a counterexample");
+        };
+        let mismatches: Vec<_> = counterexample.mismatches().collect();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].name, "s7");
+    }
+
+    #[test]
+    fn proves_wide_adders_quickly() {
+        let full_adder = Circuit::full_adder();
+        let synthesized = Circuit::from_truth_table(&full_adder.truth_table().unwrap()).unwrap();
+        let a = ripple_adder(32, &full_adder);
+        let b = ripple_adder(32, &synthesized);
+
+        // Enumerating 64 input bits would never finish, so this needs learned clauses
+        let start = std::time::Instant::now();
+        assert_eq!(check(&a, &b), Ok(Equivalence::Equivalent));
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn rejects_mismatched_circuits() {
+        let a = Circuit::from_expression("y = p & q").unwrap();
+        let b = Circuit::from_expression("y = p & r").unwrap();
+        assert_eq!(
+            check(&a, &b),
+            Err(EquivalenceError::MissingPort("q".into()))
+        );
+
+        let mut sequential = Circuit::from_expression("y = p & q").unwrap();
+        sequential.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        let index = sequential.elements.len() - 1;
+        assert_eq!(
+            check(&a, &sequential),
+            Err(EquivalenceError::NotCombinational(vec![
+                crate::logic::circuit::connection::ElementIdx(index)
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_ports() {
+        let a = Circuit::from_expression("y = p & q").unwrap();
+        let mut b = a.clone();
+        b.add_gate(Gate::Output(Some("y".into())), Vec2::new(8.0, -8.0));
+        assert_eq!(
+            check(&a, &b),
+            Err(EquivalenceError::DuplicatePort("y".into()))
+        );
+
+        // A label can also collide with the name of an unnamed port
+        let mut b = a.clone();
+        b.add_gate(Gate::Input(Some("in3".into())), Vec2::new(0.0, -8.0));
+        b.add_gate(Gate::Input(None), Vec2::new(0.0, -9.0));
+        assert_eq!(b.input_columns()[3].name, "in3");
+        assert_eq!(
+            check(&a, &b),
+            Err(EquivalenceError::DuplicatePort("in3".into()))
+        );
+    }
+
+    #[test]
+    fn rejects_loops_with_every_method() {
+        // y = p | (y & q), which settles, but only because the loop starts low
+        let mut looped = Circuit::default();
+        let p = looped.add_gate(Gate::Input(Some("p".into())), Vec2::new(0.0, 1.0));
+        let q = looped.add_gate(Gate::Input(Some("q".into())), Vec2::ZERO);
+        let or = looped.add_gate(Gate::Or(2), Vec2::ZERO);
+        let and = looped.add_gate(Gate::And(2), Vec2::ZERO);
+        let y = looped.add_gate(Gate::Output(Some("y".into())), Vec2::ZERO);
+        for connection in [
+            p.output(0).to(or.input(0)),
+            and.output(0).to(or.input(1)),
+            or.output(0).to(and.input(0)),
+            q.output(0).to(and.input(1)),
+            or.output(0).to(y.input(0)),
+        ] {
+            looped.add_connection(connection).unwrap();
+        }
+        looped.truth_table().unwrap();
+
+        let a = Circuit::from_expression("y = p").unwrap();
+        let mut b = a.clone();
+        b.add_gate(Gate::Input(Some("q".into())), Vec2::new(0.0, -8.0));
+        for method in [Method::Auto, Method::Exhaustive, Method::Sat] {
+            assert_eq!(
+                check_with(&b, &looped, method),
+                Err(EquivalenceError::Cyclic)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod verilog {
+    use super::*;
+    use crate::logic::{
+        circuit::connection::ElementIdx,
+        equivalence::{check_with, Equivalence, Method},
+        verilog::{ExportError, ImportError},
+    };
+
+    fn round_trip(circuit: &Circuit) -> Circuit {
+        let verilog = circuit.to_verilog("top").unwrap();
+        Circuit::from_verilog(&verilog).unwrap()
+    }
+
+    // Both circuits settle to the same outputs for every input, simulated by the solver
+    fn assert_same_outputs(a: &Circuit, b: &Circuit) {
+        let a = a.truth_table().unwrap();
+        let b = b.truth_table().unwrap();
+        assert_eq!(a.rows, b.rows);
+    }
+
+    #[test]
+    fn full_adder() {
+        let adder = Circuit::full_adder();
+        let verilog = adder.to_verilog("full_adder").unwrap();
+        assert!(verilog.starts_with("module full_adder (a, b, carry, sum, carry_out);"));
+        assert!(verilog.contains("xor g3 (w3, a, b);"));
+
+        let imported = Circuit::from_verilog(&verilog).unwrap();
+        assert_same_outputs(&adder, &imported);
+    }
+
+    #[test]
+    fn embedded_circuits_become_submodules() {
+        let full_adder = Circuit::full_adder().embed();
+        let mut circuit = Circuit::default();
+        let mut carry = circuit.add_gate(Gate::Off, Vec2::ZERO).output(0);
+        for bit in 0..3 {
+            let y = -(bit as f32) * 2.0;
+            let a = circuit.add_gate(Gate::Input(Some(format!("a{bit}"))), Vec2::new(0.0, y));
+            let b = circuit.add_gate(
+                Gate::Input(Some(format!("b{bit}"))),
+                Vec2::new(0.0, y - 1.0),
+            );
+            let adder = circuit.add_gate(full_adder.clone().into(), Vec2::ZERO);
+            circuit
+                .add_connection(a.output(0).to(adder.input(0)))
+                .unwrap();
+            circuit
+                .add_connection(b.output(0).to(adder.input(1)))
+                .unwrap();
+            circuit.add_connection(carry.to(adder.input(2))).unwrap();
+
+            let sum = circuit.add_gate(Gate::Output(Some(format!("s{bit}"))), Vec2::new(4.0, y));
+            circuit
+                .add_connection(adder.output(0).to(sum.input(0)))
+                .unwrap();
+            carry = adder.output(1);
+        }
+
+        // The component is written once, before the module using it
+        let verilog = circuit.to_verilog("adder").unwrap();
+        assert_eq!(verilog.matches("module component ").count(), 1);
+        assert!(verilog.find("module component ") < verilog.find("module adder "));
+        assert_eq!(verilog.matches("component u").count(), 3);
+
+        let imported = Circuit::from_verilog(&verilog).unwrap();
+        let instances: Vec<_> = imported
+            .elements
+            .iter()
+            .filter_map(|element| match &element.gate {
+                Gate::Embedded(embed) => Some(embed.component().clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(instances.len(), 3);
+        assert!(instances
+            .iter()
+            .all(|component| std::sync::Arc::ptr_eq(component, &instances[0])));
+        assert_same_outputs(&circuit, &imported);
+        assert_eq!(
+            check_with(&circuit, &imported, Method::Exhaustive),
+            Ok(Equivalence::Equivalent)
+        );
+    }
+
+    #[test]
+    fn buses() {
+        let mut circuit = Circuit::default();
+        let a = circuit.add_gate(Gate::Input(Some("a".into())), Vec2::new(0.0, 0.0));
+        let b = circuit.add_gate(Gate::Input(Some("b".into())), Vec2::new(0.0, -1.0));
+        let enable = circuit.add_gate(Gate::Input(Some("en".into())), Vec2::new(0.0, -2.0));
+        let output = |circuit: &mut Circuit, name: &str, y: f32| {
+            circuit.add_gate(Gate::Output(Some(name.into())), Vec2::new(4.0, y))
+        };
+
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        circuit
+            .add_connection(a.output(0).to(and.input(0)).with_width(4))
+            .unwrap();
+        circuit
+            .add_connection(b.output(0).to(and.input(1)).with_width(4))
+            .unwrap();
+        let and_port = output(&mut circuit, "and", 0.0);
+        circuit
+            .add_connection(and.output(0).to(and_port.input(0)).with_width(4))
+            .unwrap();
+
+        // The bits of a NAND, reversed
+        let nand = circuit.add_gate(Gate::Nand(2), Vec2::ZERO);
+        circuit
+            .add_connection(a.output(0).to(nand.input(0)).with_width(4))
+            .unwrap();
+        circuit
+            .add_connection(b.output(0).to(nand.input(1)).with_width(4))
+            .unwrap();
+        let split = circuit.add_gate(Gate::Split(4), Vec2::ZERO);
+        circuit
+            .add_connection(nand.output(0).to(split.input(0)).with_width(4))
+            .unwrap();
+        let merge = circuit.add_gate(Gate::Merge(4), Vec2::ZERO);
+        for bit in 0..4 {
+            circuit
+                .add_connection(split.output(bit).to(merge.input(3 - bit)))
+                .unwrap();
+        }
+        let reversed = output(&mut circuit, "reversed", -1.0);
+        circuit
+            .add_connection(merge.output(0).to(reversed.input(0)).with_width(4))
+            .unwrap();
+
+        // Tri-state buffers sharing an output pick one of the inputs
+        let not_enable = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        circuit
+            .add_connection(enable.output(0).to(not_enable.input(0)))
+            .unwrap();
+        let mux = output(&mut circuit, "mux", -2.0);
+        for (data, enable) in [(a, enable.output(0)), (b, not_enable.output(0))] {
+            let buffer = circuit.add_gate(Gate::TriState, Vec2::ZERO);
+            circuit
+                .add_connection(data.output(0).to(buffer.input(0)).with_width(4))
+                .unwrap();
+            circuit.add_connection(enable.to(buffer.input(1))).unwrap();
+            circuit
+                .add_connection(buffer.output(0).to(mux.input(0)).with_width(4))
+                .unwrap();
+        }
+
+        let constant = circuit.add_gate(Gate::Const(true), Vec2::ZERO);
+        let xor = circuit.add_gate(Gate::Xor(2), Vec2::ZERO);
+        circuit
+            .add_connection(a.output(0).to(xor.input(0)).with_width(4))
+            .unwrap();
+        circuit
+            .add_connection(constant.output(0).to(xor.input(1)).with_width(4))
+            .unwrap();
+        let inverted = output(&mut circuit, "inverted", -3.0);
+        circuit
+            .add_connection(xor.output(0).to(inverted.input(0)).with_width(4))
+            .unwrap();
+
+        let verilog = circuit.to_verilog("buses").unwrap();
+        assert!(verilog.contains("input [3:0] a;"));
+        assert!(verilog.contains("output [3:0] and_;"));
+
+        let imported = round_trip(&circuit);
+        assert_eq!(imported.output_columns()[0].name, "and_");
+        assert_same_outputs(&circuit, &imported);
+    }
+
+    #[test]
+    fn sequential_gates_are_always_blocks() {
+        let mut circuit = Circuit::default();
+        let data = circuit.add_gate(Gate::Input(Some("d".into())), Vec2::new(0.0, 0.0));
+        let clock = circuit.add_gate(Gate::Input(Some("clk".into())), Vec2::new(0.0, -1.0));
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        circuit
+            .add_connection(data.output(0).to(flip_flop.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(flip_flop.input(1)))
+            .unwrap();
+        let q = circuit.add_gate(Gate::Output(Some("q".into())), Vec2::new(4.0, 0.0));
+        circuit
+            .add_connection(flip_flop.output(0).to(q.input(0)))
+            .unwrap();
+
+        let verilog = circuit.to_verilog("flip_flop").unwrap();
+        assert!(verilog.contains("reg w2_0;"));
+        assert!(verilog.contains("always @(posedge clk) w2_0 <= d;"));
+        assert!(matches!(
+            Circuit::from_verilog(&verilog),
+            Err(ImportError::Unsupported { construct, .. }) if construct == "always"
+        ));
+    }
+
+    #[test]
+    fn clocks_are_unsupported() {
+        let mut circuit = Circuit::default();
+        circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let clock = Gate::Clock {
+            period: 2,
+            high_ticks: 1,
+            phase: 0,
+        };
+        circuit.add_gate(clock, Vec2::ZERO);
+
+        let mut outer = Circuit::default();
+        outer.add_gate(Gate::Buf, Vec2::ZERO);
+        outer.add_gate(circuit.embed().into(), Vec2::ZERO);
+        assert_eq!(
+            outer.to_verilog("outer"),
+            Err(ExportError::Unsupported(vec![ElementIdx(1), ElementIdx(1)]))
+        );
+    }
+
+    #[test]
+    fn gate_level_netlist() {
+        let verilog = "
+            // A two bit comparator from a course netlist
+            module bit_equal (input a, input b, output eq);
+              xnor (eq, a, b);
+            endmodule
+
+            module compare (input [1:0] x, input [1:0] y, output equal, output differ);
+              wire e0, e1;
+              bit_equal low (x[0], y[0], e0);
+              bit_equal high (.a(x[1]), .b(y[1]), .eq(e1));
+              and (equal, e0, e1);
+              wire parity = ^x ~^ y;
+              assign differ = ~equal;
+            endmodule
+        ";
+        assert!(matches!(
+            Circuit::from_verilog(verilog),
+            Err(ImportError::Syntax { line: 12, .. })
+        ));
+
+        let verilog = verilog.replace("^x ~^ y", "x[0] ~^ x[1]");
+        let circuit = Circuit::from_verilog(&verilog).unwrap();
+        let table = circuit.truth_table().unwrap();
+        for x in 0..4 {
+            for y in 0..4 {
+                let equal = (x == y) as u64;
+                assert_eq!(table.lookup(&[x, y]), Some(&[equal, 1 - equal][..]));
+            }
+        }
+
+        // Inputs are on the left and outputs on the right, with nothing overlapping
+        let columns = |is_column: fn(&Gate) -> bool| {
+            circuit
+                .elements
+                .iter()
+                .filter(|element| is_column(&element.gate))
+                .map(|element| element.position.x)
+                .collect::<Vec<_>>()
+        };
+        let inputs = columns(|gate| matches!(gate, Gate::Input(_)));
+        let outputs = columns(|gate| matches!(gate, Gate::Output(_)));
+        let gates = columns(|gate| !matches!(gate, Gate::Input(_) | Gate::Output(_)));
+        let max = |xs: &[f32]| xs.iter().copied().fold(f32::MIN, f32::max);
+        let min = |xs: &[f32]| xs.iter().copied().fold(f32::MAX, f32::min);
+        assert!(max(&inputs) < min(&gates) && max(&gates) < min(&outputs));
+        for (index, a) in circuit.elements.iter().enumerate() {
+            for b in &circuit.elements[index + 1..] {
+                assert_ne!(a.position, b.position);
+            }
+        }
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(
+            Circuit::from_verilog("").unwrap_err(),
+            ImportError::NoModules
+        );
+        assert_eq!(
+            Circuit::from_verilog("module m (a);\n  input a;\n  assign a = 1'b1;\nendmodule")
+                .unwrap_err(),
+            ImportError::MultipleDrivers {
+                line: 3,
+                net: "a".into()
+            }
+        );
+        assert_eq!(
+            Circuit::from_verilog("module m (y);\n  output y;\n  missing u (y);\nendmodule")
+                .unwrap_err(),
+            ImportError::UnknownModule {
+                line: 3,
+                module: "missing".into()
+            }
+        );
+        assert_eq!(
+            Circuit::from_verilog("module m (y);\n  output y;\n  assign y = ;\nendmodule")
+                .unwrap_err(),
+            ImportError::Syntax {
+                line: 3,
+                found: ";".into()
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod blif {
+    use super::*;
+    use crate::logic::{
+        blif::BlifError,
+        equivalence::{check, Equivalence},
+    };
+
+    const FULL_ADDER: &str = "
+        # A full adder, as written by a synthesis tool
+        .model full_adder
+        .inputs a b carry
+        .outputs sum carry_out
+        .names a b carry sum
+        100 1
+        010 1
+        001 1
+        111 1
+        .names a b carry \\
+          carry_out
+        11- 1
+        1-1 1
+        -11 1
+        .end
+    ";
+
+    #[test]
+    fn covers() {
+        let adder = Circuit::from_blif(FULL_ADDER).unwrap();
+        assert_eq!(
+            check(&adder, &Circuit::full_adder()),
+            Ok(Equivalence::Equivalent)
+        );
+    }
+
+    #[test]
+    fn off_sets_and_constants() {
+        let circuit = Circuit::from_blif(
+            "
+            .model constants
+            .inputs a b
+            .outputs nand zero one
+            .names a b nand
+            11 0
+            .names zero
+            .names one
+            1
+            .end
+            ",
+        )
+        .unwrap();
+        let table = circuit.truth_table().unwrap();
+        for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let nand = 1 - (a & b);
+            assert_eq!(table.lookup(&[a, b]), Some(&[nand, 0, 1][..]));
+        }
+    }
+
+    #[test]
+    fn subcircuits_are_embedded() {
+        let source = format!(
+            "
+            .model adder
+            .inputs a0 a1 b0 b1
+            .outputs s0 s1 s2
+            .names zero
+            .subckt full_adder a=a0 b=b0 carry=zero sum=s0 carry_out=c0
+            .subckt full_adder a=a1 b=b1 carry=c0 sum=s1 carry_out=s2
+            .end
+            {FULL_ADDER}"
+        );
+        let circuit = Circuit::from_blif(&source).unwrap();
+        let components: Vec<_> = circuit
+            .elements
+            .iter()
+            .filter_map(|element| match &element.gate {
+                Gate::Embedded(embed) => Some(embed.component().clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(components.len(), 2);
+        assert!(std::sync::Arc::ptr_eq(&components[0], &components[1]));
+        assert_eq!(components[0].name(), Some("full_adder"));
+
+        let table = circuit.truth_table().unwrap();
+        for a in 0..4u64 {
+            for b in 0..4u64 {
+                let sum = a + b;
+                let inputs = [a & 1, a >> 1, b & 1, b >> 1];
+                let outputs = [sum & 1, (sum >> 1) & 1, sum >> 2];
+                assert_eq!(table.lookup(&inputs), Some(&outputs[..]));
+            }
+        }
+    }
+
+    #[test]
+    fn latches() {
+        let mut circuit = Circuit::from_blif(
+            "
+            .model toggle
+            .outputs q
+            .names q d
+            0 1
+            .latch d q re clk 0
+            .inputs clk
+            .end
+            ",
+        )
+        .unwrap();
+        let clock = circuit.input_ports()[0];
+        let q = circuit.output_ports()[0];
+        circuit.settle(100).unwrap();
+        let mut values = vec![];
+        for _ in 0..3 {
+            circuit.set_input(clock, 1);
+            circuit.settle(100).unwrap();
+            values.push(circuit.output_bus_value(q.output(0)));
+            circuit.set_input(clock, 0);
+            circuit.settle(100).unwrap();
+        }
+        assert_eq!(values, [1, 0, 1]);
+
+        // Latches on the global clock get a clock input
+        let circuit = Circuit::from_blif(".inputs d\n.outputs q\n.latch d q 2\n").unwrap();
+        assert_eq!(circuit.input_columns()[1].name, "clock");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Circuit::from_blif("").unwrap_err(), BlifError::NoModels);
+        assert_eq!(
+            Circuit::from_blif(".inputs a\n.outputs y\n.names a y\n1 1\n.names a y\n0 1")
+                .unwrap_err(),
+            BlifError::MultipleDrivers {
+                line: 5,
+                net: "y".into()
+            }
+        );
+        assert_eq!(
+            Circuit::from_blif(".inputs a\n.names a y\n10 1").unwrap_err(),
+            BlifError::Syntax {
+                line: 3,
+                found: "10 1".into()
+            }
+        );
+        assert_eq!(
+            Circuit::from_blif(".subckt missing a=b").unwrap_err(),
+            BlifError::UnknownModel {
+                line: 1,
+                model: "missing".into()
+            }
+        );
+        assert!(matches!(
+            Circuit::from_blif(".gate nand2 A=a B=b O=y"),
+            Err(BlifError::Unsupported { line: 1, .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod trace {
+    use super::*;
+    use crate::logic::{
+        circuit::embedded::{Component, EmbeddedCircuit},
+        LogicBits,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn clock() {
+        let mut circuit = Circuit::default();
+        let clock = Gate::Clock {
+            period: 2,
+            high_ticks: 1,
+            phase: 0,
+        };
+        let clock = circuit.add_gate(clock, Vec2::ZERO);
+        let bus = circuit.add_gate(Gate::Input(Some("bus".into())), Vec2::ZERO);
+        let split = circuit.add_gate(Gate::Split(4), Vec2::ZERO);
+        circuit
+            .add_connection(bus.output(0).to(split.input(0)).with_width(4))
+            .unwrap();
+
+        circuit.start_recording(vec![
+            circuit.probe(clock.output(0)),
+            circuit.probe(bus.output(0)),
+        ]);
+        circuit.step_n(2);
+        circuit.set_input(bus, 0b1010);
+        circuit.step();
+        let trace = circuit.stop_recording().unwrap();
+        circuit.step();
+
+        assert_eq!(trace.probes[0].name, "clock0");
+        assert_eq!(trace.probes[1].width, 4);
+        assert_eq!(
+            trace.samples,
+            [[0, 0], [1, 0], [0, 0], [1, 0b1010]].map(|sample| sample.map(LogicBits::from))
+        );
+
+        let vcd = trace.to_vcd("top");
+        let body = vcd.split_once("$timescale").unwrap().1;
+        assert_eq!(
+            body,
+            " 1ns $end
+$scope module top $end
+$var wire 1 ! clock0 $end
+$var wire 4 \" bus [3:0] $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b0000 \"
+$end
+#1
+1!
+#2
+0!
+#3
+1!
+b1010 \"
+#4
+"
+        );
+    }
+
+    #[test]
+    fn limited() {
+        let mut circuit = Circuit::default();
+        let clock = Gate::Clock {
+            period: 2,
+            high_ticks: 1,
+            phase: 0,
+        };
+        let clock = circuit.add_gate(clock, Vec2::ZERO);
+
+        circuit.start_recording(vec![circuit.probe(clock.output(0))]);
+        circuit.step_n(2);
+        circuit.limit_recording(Some(2));
+        circuit.step_n(3);
+        let trace = circuit.stop_recording().unwrap();
+
+        assert_eq!(trace.first_step, 4);
+        assert_eq!(trace.end_step(), 6);
+        assert_eq!(trace.at(3), None);
+        assert_eq!(trace.at(5), Some(&[LogicBits::from(1)][..]));
+        assert_eq!(
+            trace.samples,
+            [[0], [1]].map(|sample| sample.map(LogicBits::from))
+        );
+
+        let vcd = trace.to_vcd("top");
+        let body = vcd.split_once("$enddefinitions $end").unwrap().1;
+        assert_eq!(
+            body,
+            "
+#4
+$dumpvars
+0!
+$end
+#5
+1!
+#6
+"
+        );
+    }
+
+    #[test]
+    fn tri_state() {
+        let mut circuit = Circuit::default().with_solver_mode(SolverMode::FourValued);
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let enable = circuit.add_gate(Gate::Input(Some("enable".into())), Vec2::ZERO);
+        let buffer = circuit.add_gate(Gate::TriState, Vec2::ZERO);
+        let merge = circuit.add_gate(Gate::Merge(2), Vec2::ZERO);
+        for connection in [
+            on.output(0).to(buffer.input(0)),
+            enable.output(0).to(buffer.input(1)),
+            buffer.output(0).to(merge.input(0)),
+            on.output(0).to(merge.input(1)),
+        ] {
+            circuit.add_connection(connection).unwrap();
+        }
+
+        circuit.start_recording(vec![
+            circuit.probe(buffer.output(0)),
+            circuit.probe(merge.output(0)),
+        ]);
+        circuit.step_n(2);
+        circuit.set_input(enable, 1);
+        circuit.step_n(2);
+        let trace = circuit.stop_recording().unwrap();
+
+        let vcd = trace.to_vcd("top");
+        let body = vcd.split_once("$enddefinitions $end\n").unwrap().1;
+        // Outputs start unknown, and the merge reads the released buffer as X
+        assert_eq!(
+            body,
+            "#0
+$dumpvars
+x!
+bxx \"
+$end
+#2
+z!
+b1x \"
+#3
+1!
+#4
+b11 \"
+#5
+"
+        );
+    }
+
+    #[test]
+    fn embedded_circuits_are_scopes() {
+        let component = Arc::new(Component::named("full adder", Circuit::full_adder()));
+        let mut circuit = Circuit::default();
+        let adder = circuit.add_gate(EmbeddedCircuit::instance(component).into(), Vec2::ZERO);
+        let one = circuit.add_gate(Gate::Const(true), Vec2::ZERO);
+        circuit
+            .add_connection(one.output(0).to(adder.input(0)))
+            .unwrap();
+
+        let probes = circuit.probe_all();
+        let names: Vec<_> = probes
+            .iter()
+            .map(|probe| format!("{}.{}", probe.scope.join("."), probe.name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "full adder0.a",
+                "full adder0.b",
+                "full adder0.carry",
+                "full adder0.xor3",
+                "full adder0.and4",
+                "full adder0.xor5",
+                "full adder0.and6",
+                "full adder0.or7",
+                "full adder0.sum",
+                "full adder0.carry_out",
+                ".const1",
+            ]
+        );
+
+        // Signals within instances read the same whether the circuit is flattened or not
+        let mut hierarchical = circuit.clone();
+        hierarchical.set_hierarchical(true);
+        for circuit in [&mut circuit, &mut hierarchical] {
+            circuit.start_recording(probes.clone());
+            circuit.settle(20).unwrap();
+            let trace = circuit.recording().unwrap();
+            assert_eq!(
+                trace.samples.back().unwrap(),
+                &[1, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1].map(LogicBits::from)
+            );
+        }
+
+        let vcd = circuit.stop_recording().unwrap().to_vcd("top");
+        assert!(vcd.contains("$scope module top $end\n$var wire 1 + const1 $end\n$scope module full_adder0 $end\n$var wire 1 ! a $end"));
+        assert_eq!(circuit.probe(adder.output(1)).name, "full adder0_1");
+    }
+}
+
+#[cfg(test)]
+mod vectors {
+    use super::*;
+    use crate::logic::{
+        vectors::{Command, Failure, TestVectors, VectorError},
+        SettleError,
+    };
+
+    #[test]
+    fn full_adder() {
+        let vectors = TestVectors::parse(
+            "
+            # Every carry
+            set a=1 b=1 carry=0
+            settle
+            expect sum=0 carry_out=1
+            set carry=1   # Inputs keep their values
+            settle
+            expect sum=1 carry_out=1
+            ",
+        )
+        .unwrap();
+        assert_eq!(vectors.commands[1], (4, Command::Settle(1000)));
+
+        let results = vectors.run(&Circuit::full_adder()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.passed()));
+        assert_eq!(results[1].line, 8);
+    }
+
+    #[test]
+    fn failures() {
+        let vectors =
+            TestVectors::parse("set a=1\nsettle\nexpect sum=0 carry_out=0\nreset\nexpect sum=0\n")
+                .unwrap();
+        let results = vectors.run(&Circuit::full_adder()).unwrap();
+        assert_eq!(
+            results[0].failures,
+            [Failure::Mismatch {
+                output: "sum".into(),
+                expected: 0,
+                actual: 1,
+            }]
+        );
+        assert_eq!(results[0].failures[0].to_string(), "sum: expected 0, got 1");
+        assert!(results[1].passed());
+
+        // An inverter feeding itself through a buffer never settles
+        let mut circuit = Circuit::default();
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(not.output(0).to(buf.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(buf.output(0).to(not.input(0)))
+            .unwrap();
+        let output = circuit.add_gate(Gate::Output(Some("y".into())), Vec2::ZERO);
+        circuit
+            .add_connection(not.output(0).to(output.input(0)))
+            .unwrap();
+        let results = TestVectors::parse("settle\nexpect y=1")
+            .unwrap()
+            .run(&circuit)
+            .unwrap();
+        assert_eq!(
+            results[0].failures[0],
+            Failure::Settle {
+                line: 1,
+                error: SettleError::Oscillating { period: 4 },
+            }
+        );
+    }
+
+    #[test]
+    fn sequential() {
+        let mut circuit = Circuit::default();
+        let data = circuit.add_gate(Gate::Input(Some("d".into())), Vec2::new(0.0, 1.0));
+        let clock = circuit.add_gate(Gate::Input(Some("clk".into())), Vec2::ZERO);
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        let q = circuit.add_gate(Gate::Output(Some("q".into())), Vec2::ZERO);
+        circuit
+            .add_connection(data.output(0).to(flip_flop.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(clock.output(0).to(flip_flop.input(1)))
+            .unwrap();
+        circuit
+            .add_connection(flip_flop.output(0).to(q.input(0)))
+            .unwrap();
+
+        let vectors = TestVectors::parse(
+            "set d=1\nstep 2\nexpect q=0\nset clk=1\nstep 2\nexpect q=1\nset d=0 clk=0\nstep 2\nexpect q=1",
+        )
+        .unwrap();
+        let results = vectors.run(&circuit).unwrap();
+        assert!(results.iter().all(|result| result.passed()), "{results:?}");
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |source: &str| TestVectors::parse(source).unwrap_err();
+        assert_eq!(
+            parse("set a=1\nwait 3"),
+            VectorError::Syntax {
+                line: 2,
+                found: "wait".into()
+            }
+        );
+        assert_eq!(
+            parse("expect sum"),
+            VectorError::Syntax {
+                line: 1,
+                found: "sum".into()
+            }
+        );
+        assert_eq!(
+            parse("step two"),
+            VectorError::Syntax {
+                line: 1,
+                found: "two".into()
+            }
+        );
+
+        let run = |source: &str| {
+            TestVectors::parse(source)
+                .unwrap()
+                .run(&Circuit::full_adder())
+                .unwrap_err()
+        };
+        assert_eq!(
+            run("set c=1"),
+            VectorError::UnknownInput {
+                line: 1,
+                name: "c".into()
+            }
+        );
+        assert_eq!(
+            run("settle\nexpect a=1"),
+            VectorError::UnknownOutput {
+                line: 2,
+                name: "a".into()
+            }
+        );
+        assert_eq!(
+            run("set a=0b10"),
+            VectorError::TooWide {
+                line: 1,
+                name: "a".into()
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod ids {
+    use super::*;
+    use crate::logic::circuit::connection::{ConnectionIdx, ElementIdx};
+
+    #[test]
+    fn removal_keeps_other_ids() {
+        let mut circuit = Circuit::default();
+        let a = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let b = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let c = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        circuit.add_connection(a.output(0).to(b.input(0))).unwrap();
+        circuit.add_connection(b.output(0).to(c.input(0))).unwrap();
+
+        let [a_id, b_id, c_id] = [a, b, c].map(|element| circuit.element_id(element));
+        let [ab_id, bc_id] = [0, 1].map(|index| circuit.connection_id(ConnectionIdx(index)));
+
+        circuit.remove_gate(a);
+        assert_eq!(circuit.element_idx(a_id), None);
+        assert_eq!(circuit.element_idx(b_id), Some(ElementIdx(0)));
+        assert_eq!(circuit.element_idx(c_id), Some(ElementIdx(1)));
+        assert_eq!(circuit.connection_idx(ab_id), None);
+
+        let bc = circuit.connection_idx(bc_id).unwrap();
+        assert_eq!(circuit[bc].from.0, circuit.element_idx(b_id).unwrap());
+        assert_eq!(circuit.connection_id(bc), bc_id);
+
+        circuit.remove_connections(circuit.element_idx(c_id).unwrap().input(0));
+        assert_eq!(circuit.connection_idx(bc_id), None);
+        assert_eq!(circuit.element_idx(c_id), Some(ElementIdx(1)));
+    }
+
+    #[test]
+    fn reused_ids_are_stale() {
+        let mut circuit = Circuit::default();
+        let a = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let a_id = circuit.element_id(a);
+        circuit.remove_gate(a);
+
+        // The new gate takes the same position and slot, but not the same ID
+        let b = circuit.add_gate(Gate::Off, Vec2::ZERO);
+        let b_id = circuit.element_id(b);
+        assert_eq!(a, b);
+        assert_eq!(a_id.index, b_id.index);
+        assert_ne!(a_id, b_id);
+        assert_eq!(circuit.element_idx(a_id), None);
+        assert_eq!(circuit.element_idx(b_id), Some(b));
+    }
+
+    #[test]
+    fn items_added_directly() {
+        let full_adder = Circuit::full_adder();
+        let mut circuit = Circuit {
+            elements: full_adder.elements.clone(),
+            connections: full_adder.connections.clone(),
+            ..Default::default()
+        };
+
+        // Items without IDs yet are given them in order, so IDs handed out early stay valid
+        let ids: Vec<_> = (0..circuit.elements.len())
+            .map(|index| circuit.element_id(ElementIdx(index)))
+            .collect();
+        let last = circuit.connections.len() - 1;
+        let last_id = circuit.connection_id(ConnectionIdx(last));
+        circuit.elements.extend(full_adder.elements.iter().cloned());
+
+        circuit.remove_gate(ElementIdx(0));
+        assert_eq!(circuit.element_idx(ids[0]), None);
+        for (index, id) in ids.iter().enumerate().skip(1) {
+            assert_eq!(circuit.element_idx(*id), Some(ElementIdx(index - 1)));
+        }
+        let last = circuit.connection_idx(last_id).unwrap();
+        assert_eq!(last.0, circuit.connections.len() - 1);
+
+        let pasted = circuit.element_id(ElementIdx(circuit.elements.len() - 1));
+        assert_eq!(
+            circuit.element_idx(pasted),
+            Some(ElementIdx(circuit.elements.len() - 1))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "gui"))]
+mod history {
+    use super::*;
+    use crate::logic::{
+        circuit::{connection::ElementIdx, EditCircuit},
+        hit_test::HitTestResult,
+    };
+
+    // An On gate driving a Not gate driving an Output gate, connected through the editor
+    fn chain() -> (EditCircuit, [ElementIdx; 3]) {
+        let mut edit = EditCircuit::default();
+        let on = edit.circuit.add_gate(Gate::On, Vec2::ZERO);
+        let not = edit.circuit.add_gate(Gate::Not, Vec2::X);
+        let out = edit.circuit.add_gate(Gate::Output(None), Vec2::X * 2.0);
+        edit.connect(on.output(0).to(not.input(0))).unwrap();
+        edit.connect(not.output(0).to(out.input(0))).unwrap();
+        (edit, [on, not, out])
+    }
+
+    #[test]
+    fn moves_are_coalesced() {
+        let mut edit = EditCircuit::default();
+        let gate = edit.circuit.add_gate(Gate::Not, Vec2::ZERO);
+
+        // A drag over several frames is a single edit
+        edit.move_elements(&[gate], Vec2::X);
+        edit.move_elements(&[gate], Vec2::Y);
+        edit.finish_move();
+        edit.move_elements(&[gate], Vec2::X);
+        edit.finish_move();
+        assert_eq!(edit.circuit[gate].position, Vec2::new(2.0, 1.0));
+
+        assert!(edit.undo());
+        assert_eq!(edit.circuit[gate].position, Vec2::new(1.0, 1.0));
+        assert!(edit.undo());
+        assert_eq!(edit.circuit[gate].position, Vec2::ZERO);
+        assert!(!edit.undo());
+
+        assert!(edit.redo());
+        assert!(edit.redo());
+        assert_eq!(edit.circuit[gate].position, Vec2::new(2.0, 1.0));
+        assert!(!edit.redo());
+    }
+
+    #[test]
+    fn delete_and_connect() {
+        let (mut edit, [on, not, _]) = chain();
+        let connections = edit.circuit.connections.clone();
+
+        edit.selection
+            .insert(&edit.circuit, HitTestResult::Element(not));
+        edit.delete_selection();
+        assert_eq!(edit.circuit.elements.len(), 2);
+        assert!(edit.circuit.connections.is_empty());
+
+        // The gate is put back where it was along with its connections
+        assert!(edit.undo());
+        assert!(matches!(edit.circuit[not].gate, Gate::Not));
+        assert_eq!(edit.circuit.connections.len(), 2);
+        assert!(connections
+            .iter()
+            .all(|connection| edit.circuit.connections.contains(connection)));
+        edit.circuit.settle(10).unwrap();
+        assert!(!edit.circuit.output_value(not.output(0)));
+
+        assert!(edit.undo());
+        assert!(edit.undo());
+        assert!(edit.circuit.connections.is_empty());
+        assert!(!edit.undo());
+
+        // Redoing the connections and the deletion refers to the gate which was put back
+        assert!(edit.redo());
+        assert!(edit.redo());
+        assert_eq!(edit.circuit.connections, connections);
+        assert!(edit.redo());
+        assert_eq!(edit.circuit.elements.len(), 2);
+        assert!(edit.circuit.connections.is_empty());
+        assert!(matches!(edit.circuit[on].gate, Gate::On));
+        assert!(matches!(edit.circuit[not].gate, Gate::Output(_)));
+    }
+
+    #[test]
+    fn undo_through_a_deletion() {
+        let (mut edit, [_, not, _]) = chain();
+        edit.move_elements(&[not], Vec2::Y);
+        edit.finish_move();
+        edit.selection
+            .insert(&edit.circuit, HitTestResult::Element(not));
+        edit.delete_selection();
+
+        // Undoing the deletion gives the gate a new ID, which the earlier move has to follow
+        assert!(edit.undo());
+        assert!(edit.undo());
+        assert_eq!(edit.circuit[not].position, Vec2::X);
+        assert!(edit.redo());
+        assert_eq!(edit.circuit[not].position, Vec2::X + Vec2::Y);
+    }
+
+    #[test]
+    fn paste_and_embed() {
+        let (mut edit, elements) = chain();
+        for element in elements {
+            edit.selection
+                .insert(&edit.circuit, HitTestResult::Element(element));
+        }
+        for connection in 0..2 {
+            edit.selection.insert(
+                &edit.circuit,
+                HitTestResult::Connection(crate::logic::circuit::connection::ConnectionIdx(
+                    connection,
+                )),
+            );
+        }
+        edit.copy_selection();
+        edit.paste(Vec2::Y * 4.0);
+        assert_eq!(edit.circuit.elements.len(), 6);
+        assert_eq!(edit.circuit.connections.len(), 4);
+
+        assert!(edit.undo());
+        assert_eq!(edit.circuit.elements.len(), 3);
+        assert_eq!(edit.circuit.connections.len(), 2);
+        assert!(edit.redo());
+        assert_eq!(edit.circuit.elements.len(), 6);
+        assert_eq!(edit.circuit.connections.len(), 4);
+
+        for element in elements {
+            edit.selection
+                .insert(&edit.circuit, HitTestResult::Element(element));
+        }
+        edit.embed_selection();
+        assert_eq!(edit.circuit.elements.len(), 7);
+        assert!(matches!(
+            edit.circuit[ElementIdx(6)].gate,
+            Gate::Embedded(_)
+        ));
+        assert!(edit.undo());
+        assert_eq!(edit.circuit.elements.len(), 6);
+
+        // A new edit replaces whatever could have been redone
+        edit.move_elements(&[ElementIdx(0)], Vec2::X);
+        assert!(!edit.redo());
+    }
+}
+
+#[cfg(test)]
+mod connections {
+    use super::*;
+    use crate::logic::circuit::connection::{
+        ConnectionError, ElementIdx, IOSpecifier, InputIdx, InputSpecifier, OutputIdx,
+        OutputSpecifier,
+    };
+
+    #[test]
+    fn invalid_pins() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+
+        let missing_output = OutputSpecifier(on, OutputIdx(1));
+        assert_eq!(
+            circuit.add_connection(missing_output.to(not.input(0))),
+            Err(ConnectionError::InvalidPin(IOSpecifier::Output(
+                missing_output
+            )))
+        );
+        let missing_input = InputSpecifier(not, InputIdx(1));
+        assert_eq!(
+            circuit.add_connection(on.output(0).to(missing_input)),
+            Err(ConnectionError::InvalidPin(IOSpecifier::Input(
+                missing_input
+            )))
+        );
+        let missing_element = InputSpecifier(ElementIdx(2), InputIdx(0));
+        assert!(circuit
+            .add_connection(on.output(0).to(missing_element))
+            .is_err());
+        assert!(circuit.connections.is_empty());
+    }
+
+    #[test]
+    fn single_driver() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let off = circuit.add_gate(Gate::Off, Vec2::ZERO);
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+
+        circuit
+            .add_connection(on.output(0).to(not.input(0)))
+            .unwrap();
+        // Adding the same connection again changes nothing
+        circuit
+            .add_connection(on.output(0).to(not.input(0)))
+            .unwrap();
+        assert_eq!(
+            circuit.add_connection(off.output(0).to(not.input(0))),
+            Err(ConnectionError::AlreadyDriven(on.output(0)))
+        );
+        assert_eq!(circuit.connections.len(), 1);
+        assert_eq!(
+            circuit.drivers(not.input(0)).collect::<Vec<_>>(),
+            [on.output(0)]
+        );
+
+        // Tri-state buffers share a bus, but nothing else can join it
+        let bus = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        for _ in 0..2 {
+            let buffer = circuit.add_gate(Gate::TriState, Vec2::ZERO);
+            circuit
+                .add_connection(buffer.output(0).to(bus.input(0)))
+                .unwrap();
+        }
+        assert!(matches!(
+            circuit.add_connection(on.output(0).to(bus.input(0))),
+            Err(ConnectionError::AlreadyDriven(_))
+        ));
+    }
+
+    #[test]
+    fn self_loops() {
+        let mut circuit = Circuit::default();
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        assert_eq!(
+            circuit.add_connection(not.output(0).to(not.input(0))),
+            Err(ConnectionError::SelfLoop)
+        );
+
+        // A flip-flop holds its value in between
+        let flip_flop = circuit.add_gate(Gate::DFlipFlop, Vec2::ZERO);
+        circuit
+            .add_connection(flip_flop.output(1).to(flip_flop.input(0)))
+            .unwrap();
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn editor_refuses_second_driver() {
+        use crate::logic::circuit::EditCircuit;
+
+        let mut edit = EditCircuit::default();
+        let on = edit.circuit.add_gate(Gate::On, Vec2::ZERO);
+        let off = edit.circuit.add_gate(Gate::Off, Vec2::ZERO);
+        let not = edit.circuit.add_gate(Gate::Not, Vec2::ZERO);
+        edit.connect(on.output(0).to(not.input(0))).unwrap();
+        assert_eq!(
+            edit.connect(off.output(0).to(not.input(0))),
+            Err(ConnectionError::AlreadyDriven(on.output(0)))
+        );
+
+        // Only the first connection was made, so it's all there is to undo
+        assert!(edit.undo());
+        assert!(edit.circuit.connections.is_empty());
+        assert!(!edit.undo());
+    }
+}
+
+#[cfg(test)]
+mod edits {
+    use super::*;
+    use crate::logic::circuit::{connection::ElementIdx, ElementId};
+
+    // A register which stored 0b1011, after which its data changed without a clock edge, along
+    // with a gate before it which isn't connected to anything
+    fn stored_register() -> (Circuit, ElementId, ElementIdx) {
+        let mut circuit = Circuit::default();
+        let spare = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let register = circuit.add_gate(Gate::Register(4), Vec2::ZERO);
+        let merge = circuit.add_gate(Gate::Merge(4), Vec2::ZERO);
+        circuit
+            .add_connection(merge.output(0).to(register.input(0)).with_width(4))
+            .unwrap();
+
+        let data: Vec<_> = (0..4)
+            .map(|bit| {
+                let driver = circuit.add_gate(Gate::Const(bit != 2), Vec2::ZERO);
+                circuit
+                    .add_connection(driver.output(0).to(merge.input(bit)))
+                    .unwrap();
+                driver
+            })
+            .collect();
+        let [clock, _] = [(1, false), (2, true)].map(|(pin, value)| {
+            let driver = circuit.add_gate(Gate::Const(value), Vec2::ZERO);
+            circuit
+                .add_connection(driver.output(0).to(register.input(pin)))
+                .unwrap();
+            driver
+        });
+
+        circuit.settle(10).unwrap();
+        circuit[clock].gate = Gate::Const(true);
+        circuit.settle(10).unwrap();
+        for driver in data {
+            circuit[driver].gate = Gate::Const(false);
+        }
+        circuit.settle(10).unwrap();
+        assert_eq!(circuit.output_bus_value(register.output(0)), 0b1011);
+
+        let register = circuit.element_id(register);
+        (circuit, register, spare)
+    }
+
+    fn register_value(circuit: &mut Circuit, register: ElementId) -> u64 {
+        circuit.settle(10).unwrap();
+        let register = circuit.element_idx(register).unwrap();
+        circuit.output_bus_value(register.output(0))
+    }
+
+    #[test]
+    fn adding_a_gate_keeps_state() {
+        for mode in [
+            SolverMode::Sweep,
+            SolverMode::EventDriven,
+            SolverMode::Timing,
+        ] {
+            let (mut circuit, register, _) = stored_register();
+            circuit.set_solver_mode(mode);
+            circuit.add_gate(Gate::And(2), Vec2::ZERO);
+            assert_eq!(register_value(&mut circuit, register), 0b1011, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn removing_an_earlier_gate_keeps_state() {
+        let (mut circuit, register, spare) = stored_register();
+        circuit.remove_gate(spare);
+        assert_eq!(register_value(&mut circuit, register), 0b1011);
+    }
+
+    // An SR latch in a component, which was set and then released
+    fn set_latch(hierarchical: bool) -> (Circuit, ElementId) {
+        let mut inner = Circuit::default();
+        let set = inner.add_gate(Gate::Input(Some("set".into())), Vec2::new(0.0, 0.0));
+        let latch = inner.add_gate(Gate::SrLatch, Vec2::new(1.0, 0.0));
+        let q = inner.add_gate(Gate::Output(Some("q".into())), Vec2::new(2.0, 0.0));
+        inner
+            .add_connection(set.output(0).to(latch.input(0)))
+            .unwrap();
+        inner
+            .add_connection(latch.output(0).to(q.input(0)))
+            .unwrap();
+
+        let mut circuit = Circuit::default();
+        circuit.set_hierarchical(hierarchical);
+        let set = circuit.add_gate(Gate::Const(true), Vec2::ZERO);
+        let instance = circuit.add_gate(Gate::Embedded(EmbeddedCircuit::new(inner)), Vec2::ZERO);
+        circuit
+            .add_connection(set.output(0).to(instance.input(0)))
+            .unwrap();
+
+        circuit.settle(10).unwrap();
+        circuit[set].gate = Gate::Const(false);
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(instance.output(0)));
+
+        let instance = circuit.element_id(instance);
+        (circuit, instance)
+    }
+
+    #[test]
+    fn embedded_state_survives_edits() {
+        for hierarchical in [false, true] {
+            let (mut circuit, instance) = set_latch(hierarchical);
+            circuit.add_gate(Gate::Or(2), Vec2::ZERO);
+            circuit.remove_gate(ElementIdx(0));
+            circuit.settle(10).unwrap();
+
+            let instance = circuit.element_idx(instance).unwrap();
+            assert!(
+                circuit.output_value(instance.output(0)),
+                "hierarchical: {hierarchical}"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod bus {
+    use super::*;
+    use crate::logic::circuit::connection::{ConnectionError, OutputSpecifier};
+
+    fn constant_bus(circuit: &mut Circuit, value: u64, width: u8) -> OutputSpecifier {
+        let merge = circuit.add_gate(Gate::Merge(width), Vec2::ZERO);
+        for bit in 0..width as usize {
+            let source = if (value >> bit) & 1 == 1 {
+                Gate::On
+            } else {
+                Gate::Off
+            };
+            let source = circuit.add_gate(source, Vec2::ZERO).output(0);
+            circuit.add_connection(source.to(merge.input(bit))).unwrap();
+        }
+        merge.output(0)
+    }
+
+    fn eval_bus_gate(gate: Gate, a: u64, b: u64) -> u64 {
+        let mut circuit = Circuit::default();
+        let gate = circuit.add_gate(gate, Vec2::ZERO);
+        let a = constant_bus(&mut circuit, a, 8);
+        let b = constant_bus(&mut circuit, b, 8);
+
+        circuit
+            .add_connection(a.to(gate.input(0)).with_width(8))
+            .unwrap();
+        circuit
+            .add_connection(b.to(gate.input(1)).with_width(8))
+            .unwrap();
+        circuit.settle(10).unwrap();
+
+        circuit.output_bus_value(gate.output(0))
+    }
+
+    #[test]
+    fn embeds_full_width_split() {
+        // Every pin of a 64 bit Split is disconnected, so the component gets a port for each
+        let mut inner = Circuit::default();
+        inner.add_gate(Gate::Split(64), Vec2::ZERO);
+        let embedded = EmbeddedCircuit::new(inner);
+        assert_eq!(embedded.input_count(), 1);
+        assert_eq!(embedded.output_count(), 64);
+
+        let mut circuit = Circuit::default();
+        let split = circuit.add_gate(Gate::Embedded(embedded), Vec2::ZERO);
+        let bus = constant_bus(&mut circuit, 1 << 63, 64);
+        circuit
+            .add_connection(bus.to(split.input(0)).with_width(64))
+            .unwrap();
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(split.output(63)));
+        assert!(!circuit.output_value(split.output(62)));
+    }
+
+    #[test]
+    fn bitwise_gates() {
+        assert_eq!(
+            eval_bus_gate(Gate::And(2), 0b1100_1010, 0b1010_0110),
+            0b1000_0010
+        );
+        assert_eq!(
+            eval_bus_gate(Gate::Or(2), 0b1100_1010, 0b1010_0110),
+            0b1110_1110
+        );
+        assert_eq!(
+            eval_bus_gate(Gate::Xor(2), 0b1100_1010, 0b1010_0110),
+            0b0110_1100
+        );
+        assert_eq!(
+            eval_bus_gate(Gate::Nand(2), 0b1100_1010, 0b1010_0110),
+            0b0111_1101
+        );
+        assert_eq!(
+            eval_bus_gate(Gate::Nor(2), 0b1100_1010, 0b1010_0110),
+            0b0001_0001
+        );
+        assert_eq!(
+            eval_bus_gate(Gate::Xnor(2), 0b1100_1010, 0b1010_0110),
+            0b1001_0011
+        );
+    }
+
+    #[test]
+    fn split() {
+        let mut circuit = Circuit::default();
+        let value = constant_bus(&mut circuit, 0b0110, 4);
+        let split = circuit.add_gate(Gate::Split(4), Vec2::ZERO);
+        circuit
+            .add_connection(value.to(split.input(0)).with_width(4))
+            .unwrap();
+        circuit.settle(10).unwrap();
+
+        let bits: Vec<bool> = (0..4)
+            .map(|bit| circuit.output_value(split.output(bit)))
+            .collect();
+        assert_eq!(bits, [false, true, true, false]);
+    }
+
+    #[test]
+    fn rejects_width_mismatches() {
+        let mut circuit = Circuit::default();
+        let value = constant_bus(&mut circuit, 0, 8);
+        let split = circuit.add_gate(Gate::Split(4), Vec2::ZERO);
+        let and = circuit.add_gate(Gate::And(2), Vec2::ZERO);
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+
+        assert_eq!(
+            circuit.add_connection(value.to(split.input(0)).with_width(8)),
+            Err(ConnectionError::WidthMismatch {
+                expected: 4,
+                found: 8
+            })
+        );
+        assert_eq!(
+            circuit.add_connection(value.to(and.input(0))),
+            Err(ConnectionError::WidthMismatch {
+                expected: 8,
+                found: 1
+            })
+        );
+        assert_eq!(
+            circuit.add_connection(value.to(and.input(0)).with_width(65)),
+            Err(ConnectionError::InvalidWidth(65))
+        );
+
+        // Once connected, gates without declared widths only accept connections of the same width
+        circuit
+            .add_connection(value.to(and.input(0)).with_width(8))
+            .unwrap();
+        assert_eq!(
+            circuit.add_connection(on.output(0).to(and.input(1))),
+            Err(ConnectionError::WidthMismatch {
+                expected: 8,
+                found: 1
+            })
+        );
+        circuit
+            .add_connection(on.output(0).to(and.input(1)).with_width(8))
+            .unwrap();
+    }
+
+    #[test]
+    fn embedded_bus_pins() {
+        let mut inner = Circuit::default();
+        let merge = inner.add_gate(Gate::Merge(4), Vec2::ZERO);
+        let not = inner.add_gate(Gate::Not, Vec2::ZERO);
+        inner
+            .add_connection(merge.output(0).to(not.input(0)).with_width(4))
+            .unwrap();
+        let embed = inner.embed();
+        assert_eq!(embed.input_widths(), [1, 1, 1, 1]);
+        assert_eq!(embed.output_widths(), [4]);
+
+        let mut circuit = Circuit::default();
+        let embedded = circuit.add_gate(embed.into(), Vec2::ZERO);
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        circuit.add_connection(on.to(embedded.input(0))).unwrap();
+        circuit.add_connection(on.to(embedded.input(3))).unwrap();
+        circuit.settle(10).unwrap();
+
+        assert_eq!(circuit.output_bus_value(embedded.output(0)), 0b0110);
+    }
+}
+
+#[cfg(test)]
+mod settle {
+    use super::*;
+    use crate::logic::circuit::connection::ElementIdx;
+
+    #[test]
+    fn counts_propagation_depth() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let mut prev = on;
+        for _ in 0..4 {
+            let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+            circuit
+                .add_connection(prev.output(0).to(buf.input(0)))
+                .unwrap();
+            prev = buf;
+        }
+
+        assert_eq!(circuit.settle(10), Ok(5));
+        assert!(circuit.output_value(prev.output(0)));
+        assert_eq!(circuit.settle(10), Ok(0));
+    }
+
+    #[test]
+    fn detects_oscillation() {
+        let mut circuit = Circuit::default();
+        let not = circuit.add_gate(Gate::Not, Vec2::ZERO);
+        let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(not.output(0).to(buf.input(0)))
+            .unwrap();
+        circuit
+            .add_connection(buf.output(0).to(not.input(0)))
+            .unwrap();
+
+        assert_eq!(
+            circuit.settle(10),
+            Err(SettleError::Oscillating { period: 4 })
+        );
+    }
+
+    #[test]
+    fn step_limit() {
+        let mut circuit = Circuit::default();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO);
+        let buf = circuit.add_gate(Gate::Buf, Vec2::ZERO);
+        circuit
+            .add_connection(on.output(0).to(buf.input(0)))
+            .unwrap();
+
+        assert_eq!(circuit.settle(1), Err(SettleError::StepLimit));
+        assert_eq!(circuit.settle(2), Ok(1));
+    }
+
+    #[test]
+    fn settles_embedded_circuits() {
+        let mut circuit = Circuit::adder_8_bit();
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        circuit
+            .add_connection(on.to(ElementIdx(1).input(0)))
+            .unwrap();
+
+        assert!(circuit.settle(100).is_ok());
+        assert!(circuit.output_value(ElementIdx(1).output(0)));
+    }
+}
+
+#[cfg(test)]
+mod file {
+    use super::*;
+    use crate::logic::{circuit::connection::ElementIdx, file::FileError};
+
+    #[test]
+    fn round_trip() {
+        let mut circuit = Circuit::adder_8_bit();
+        circuit.add_gate(Gate::Input(Some("a".into())), Vec2::new(1.0, 2.0));
+        circuit.add_gate(Gate::Output(None), Vec2::new(-1.0, 0.5));
+        circuit.add_gate(Gate::Const(true), Vec2::ZERO);
+        circuit.add_gate(Gate::JkFlipFlop, Vec2::ZERO);
+        circuit.add_gate(Gate::Register(16), Vec2::ZERO);
+        circuit.add_gate(
+            Gate::Clock {
+                period: 10,
+                high_ticks: 3,
+                phase: 2,
+            },
+            Vec2::ZERO,
+        );
+
+        let json = circuit.to_json();
+        let loaded = Circuit::from_json(&json).unwrap();
+
+        assert_eq!(loaded.to_json(), json);
+        assert_eq!(loaded.elements.len(), circuit.elements.len());
+        assert_eq!(loaded.connections, circuit.connections);
+    }
+
+    #[test]
+    fn embedded_round_trip() {
+        let mut circuit = Circuit::default();
+        let adder = circuit.add_gate(Circuit::full_adder().embed().into(), Vec2::ZERO);
+        let on = circuit.add_gate(Gate::On, Vec2::ZERO).output(0);
+        circuit.add_connection(on.to(adder.input(0))).unwrap();
+        circuit.add_connection(on.to(adder.input(2))).unwrap();
+
+        let mut loaded = Circuit::from_json(&circuit.to_json()).unwrap();
+        loaded.settle(20).unwrap();
+
+        assert!(!loaded.output_value(adder.output(0)));
+        assert!(loaded.output_value(adder.output(1)));
+    }
+
+    #[test]
+    fn bus_round_trip() {
+        let mut circuit = Circuit::default();
+        let merge = circuit.add_gate(Gate::Merge(8), Vec2::ZERO);
+        let split = circuit.add_gate(Gate::Split(8), Vec2::ZERO);
+        circuit
+            .add_connection(merge.output(0).to(split.input(0)).with_width(8))
+            .unwrap();
+
+        let loaded = Circuit::from_json(&circuit.to_json()).unwrap();
+        assert_eq!(loaded.connections, circuit.connections);
+        assert_eq!(loaded.connections[0].width, 8);
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let json = r#"{"version":1,"circuit":{
+            "elements":[
+                {"gate":{"kind":"On"},"position":[0,0]},
+                {"gate":{"kind":"Embedded","circuit":{
+                    "elements":[{"gate":{"kind":"Nand"},"position":[0,0]}],
+                    "connections":[]
+                }},"position":[1,0]}
+            ],
+            "connections":[{"from":[0,0],"to":[1,0]}]
+        }}"#;
+
+        let mut circuit = Circuit::from_json(json).unwrap();
+        assert_eq!(circuit.connections[0].width, 1);
+        assert_eq!(circuit[ElementIdx(1)].gate.input_count(), 2);
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(ElementIdx(1).output(0)));
+    }
+
+    #[test]
+    fn migrates_embedded_ports() {
+        // Before version 4, the Input gate was an ordinary element and the Output gate's pins
+        // were both disconnected, so the embedded circuit had two inputs and two outputs
+        // The Output gate's output was always low, whatever drove it
+        let json = r#"{"version":3,"circuit":{
+            "elements":[
+                {"gate":{"kind":"Embedded","circuit":{
+                    "elements":[
+                        {"gate":{"kind":"Input","label":"a"},"position":[0,0]},
+                        {"gate":{"kind":"Or","inputs":2},"position":[1,0]},
+                        {"gate":{"kind":"Output","label":"b"},"position":[2,0]}
+                    ],
+                    "connections":[{"from":[0,0],"to":[1,0],"width":1}]
+                }},"position":[0,0]},
+                {"gate":{"kind":"On"},"position":[0,0]}
+            ],
+            "connections":[
+                {"from":[1,0],"to":[0,0],"width":1},
+                {"from":[1,0],"to":[0,1],"width":1}
+            ]
+        }}"#;
+
+        let mut circuit = Circuit::from_json(json).unwrap();
+        let embedded = ElementIdx(0);
+        assert_eq!(circuit[embedded].gate.input_count(), 2);
+        assert_eq!(circuit[embedded].gate.output_count(), 2);
+
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(embedded.output(0)));
+        assert!(!circuit.output_value(embedded.output(1)));
+
+        // Saving and loading again keeps the same outputs
+        let mut reloaded = Circuit::from_json(&circuit.to_json()).unwrap();
+        reloaded.settle(10).unwrap();
+        assert!(reloaded.output_value(embedded.output(0)));
+        assert!(!reloaded.output_value(embedded.output(1)));
+    }
+
+    #[test]
+    fn migrates_several_drivers() {
+        // Before version 6 an input with several drivers took the OR of them
+        let json = r#"{"version":5,"components":[],"circuit":{
+            "elements":[
+                {"gate":{"kind":"On"},"position":[0,0]},
+                {"gate":{"kind":"Off"},"position":[0,1]},
+                {"gate":{"kind":"Buf"},"position":[2,0]}
+            ],
+            "connections":[
+                {"from":[0,0],"to":[2,0],"width":1},
+                {"from":[1,0],"to":[2,0],"width":1}
+            ]
+        }}"#;
+
+        let mut circuit = Circuit::from_json(json).unwrap();
+        assert_eq!(circuit.elements.len(), 4);
+        assert!(matches!(circuit[ElementIdx(3)].gate, Gate::Or(2)));
+        assert_eq!(circuit.drivers(ElementIdx(2).input(0)).count(), 1);
+
+        circuit.settle(10).unwrap();
+        assert!(circuit.output_value(ElementIdx(2).output(0)));
+    }
+
+    #[test]
+    fn migrates_self_loops() {
+        // The clock from before version 6, a Not gate feeding itself
+        let json = r#"{"version":5,"components":[],"circuit":{
+            "elements":[{"gate":{"kind":"Not"},"position":[0,0]}],
+            "connections":[{"from":[0,0],"to":[0,0],"width":1}]
+        }}"#;
+
+        let mut circuit = Circuit::from_json(json).unwrap();
+        assert!(matches!(circuit[ElementIdx(1)].gate, Gate::Buf));
+        assert_eq!(
+            circuit.settle(20),
+            Err(SettleError::Oscillating { period: 4 })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let json = r#"{"version":999,"circuit":{"elements":[],"connections":[]}}"#;
+        assert!(matches!(
+            Circuit::from_json(json),
+            Err(FileError::UnsupportedVersion(999))
+        ));
+
+        let json = r#"{"circuit":{"elements":[],"connections":[]}}"#;
+        assert!(matches!(
+            Circuit::from_json(json),
+            Err(FileError::MissingVersion)
+        ));
+    }
+
+    #[test]
+    fn errors_are_readable() {
+        let json = r#"{"version":6,"components":[],"circuit":{
+            "elements":[{"gate":{"kind":"Flux"},"position":[0,0]}],
+            "connections":[]
+        }}"#;
+        let error = Circuit::from_json(json).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown gate kind \"Flux\"");
+
+        let error = Circuit::load("/nonexistent/circuit.json").unwrap_err();
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_widths() {
+        for (kind, width) in [("Merge", 0), ("Split", 65), ("Register", 255)] {
+            let json = format!(
+                r#"{{"version":6,"components":[],"circuit":{{
+                    "elements":[{{"gate":{{"kind":"{kind}","width":{width}}},"position":[0,0]}}],
+                    "connections":[]
+                }}}}"#
+            );
+            assert!(matches!(
+                Circuit::from_json(&json),
+                Err(FileError::InvalidWidth(w)) if w == width
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_connections() {
+        let json = r#"{"version":1,"circuit":{
+            "elements":[{"gate":{"kind":"Not"},"position":[0,0]}],
+            "connections":[{"from":[0,0],"to":[1,0]}]
+        }}"#;
+        assert!(matches!(
+            Circuit::from_json(json),
+            Err(FileError::InvalidConnection(0))
+        ));
+    }
+}
+
+#[cfg(test)]
+mod solver {
+    use super::*;
+    use crate::logic::circuit::connection::{ConnectionIdx, ElementIdx};
+
+    fn random_circuit(gates: usize, connections: usize) -> Circuit {
+        let mut circuit = Circuit::default();
+        for _ in 0..gates {
+            circuit.add_random_component();
+        }
+        for _ in 0..connections {
+            circuit.add_random_connection();
+        }
+        circuit
+    }
+
+    fn assert_modes_match(sweep: &mut Circuit, event: &mut Circuit, steps: usize) {
+        assert_mode_matches_sweep(sweep, event, SolverMode::EventDriven, steps);
+    }
+
+    fn assert_mode_matches_sweep(
+        sweep: &mut Circuit,
+        other: &mut Circuit,
+        mode: SolverMode,
+        steps: usize,
+    ) {
+        sweep.set_solver_mode(SolverMode::Sweep);
+        other.set_solver_mode(mode);
+
+        for step in 0..steps {
+            sweep.step();
+            other.step();
+            assert_eq!(
+                sweep.solver.output_results.inner, other.solver.output_results.inner,
+                "Solvers diverged at step {step}"
+            );
+        }
+    }
+
+    #[test]
+    fn timing_with_unit_delays_matches_sweep() {
+        for _ in 0..20 {
+            let mut sweep = random_circuit(200, 300);
+            let mut timing = sweep.clone();
+            assert_mode_matches_sweep(&mut sweep, &mut timing, SolverMode::Timing, 50);
+        }
+    }
+
+    #[test]
+    fn event_driven_matches_sweep() {
+        for _ in 0..20 {
+            let mut sweep = random_circuit(200, 300);
+            let mut event = sweep.clone();
+            assert_modes_match(&mut sweep, &mut event, 50);
+        }
+    }
+
+    #[test]
+    fn event_driven_matches_sweep_embedded() {
+        let mut sweep = Circuit::adder_8_bit();
+        let on = sweep.add_gate(Gate::On, Vec2::ZERO).output(0);
+        sweep.add_connection(on.to(ElementIdx(1).input(0))).unwrap();
+        sweep.add_connection(on.to(ElementIdx(3).input(1))).unwrap();
+        let mut event = sweep.clone();
+
+        assert_modes_match(&mut sweep, &mut event, 50);
+    }
+
+    #[test]
+    fn event_driven_matches_sweep_after_edits() {
+        let mut sweep = random_circuit(100, 150);
+        let mut event = sweep.clone();
+        assert_modes_match(&mut sweep, &mut event, 10);
+
+        for circuit in [&mut sweep, &mut event] {
+            circuit.remove_gate(ElementIdx(3));
+            circuit.remove_connection(ConnectionIdx(0));
+            circuit.remove_connections(ElementIdx(0).input(0));
+            let button = circuit.add_gate(Gate::Button, Vec2::ZERO);
+            circuit
+                .add_connection(button.output(0).to(ElementIdx(0).input(0)))
+                .unwrap();
+            circuit.click_gate(button);
+        }
+        assert_modes_match(&mut sweep, &mut event, 20);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::logic::{gate::Gate, LogicBits};
+
+use super::{
+    connection::{ElementIdx, OutputIdx, OutputSpecifier},
+    Circuit,
+};
+
+// An output pin to record, by the path of embedded instances leading to its element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    pub path: Vec<ElementIdx>,
+    pub output: OutputIdx,
+    // Names of the instances leading to the element, which become nested scopes in a VCD file
+    pub scope: Vec<String>,
+    pub name: String,
+    pub width: u8,
+}
+
+// The values of probed outputs, sampled when recording started and after every step since
+// With a limit, only the latest samples are kept
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub probes: Vec<Probe>,
+    // One value per probe for each sample, including which bits are X or Z
+    pub samples: VecDeque<Vec<LogicBits>>,
+    // The step of the first sample kept, counting from the start of the recording
+    pub first_step: usize,
+    limit: Option<usize>,
+}
+
+impl Trace {
+    pub fn new(probes: Vec<Probe>) -> Self {
+        Self {
+            probes,
+            ..Default::default()
+        }
+    }
+
+    // Keeps at most `limit` samples, dropping the oldest ones
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        if let Some(limit) = limit {
+            let dropped = self.samples.len().saturating_sub(limit);
+            self.samples.drain(..dropped);
+            self.first_step += dropped;
+        }
+    }
+
+    // The step after the last sample
+    pub fn end_step(&self) -> usize {
+        self.first_step + self.samples.len()
+    }
+
+    // The values at a step of the recording, if they're still kept
+    pub fn at(&self, step: usize) -> Option<&[LogicBits]> {
+        let sample = self.samples.get(step.checked_sub(self.first_step)?)?;
+        Some(sample)
+    }
+
+    pub fn sample(&mut self, circuit: &Circuit) {
+        let values = self
+            .probes
+            .iter()
+            .map(|probe| {
+                let value = circuit.solver.nested_output(&probe.path, probe.output);
+                value.unwrap_or_default().masked(probe.width)
+            })
+            .collect();
+        self.samples.push_back(values);
+        self.set_limit(self.limit);
+    }
+
+    // The trace as a Value Change Dump, with one time unit per step and every scope nested within
+    // one named `module`
+    pub fn to_vcd(&self, module: &str) -> String {
+        let mut vcd = String::new();
+        writeln!(
+            vcd,
+            "$version {} {} $end",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )
+        .unwrap();
+        writeln!(vcd, "$timescale 1ns $end").unwrap();
+        writeln!(vcd, "$scope module {} $end", vcd_name(module)).unwrap();
+
+        // Probes sharing a scope are declared together, so each scope is only entered once
+        let mut order: Vec<usize> = (0..self.probes.len()).collect();
+        order.sort_by(|a, b| self.probes[*a].scope.cmp(&self.probes[*b].scope));
+        let mut scope: &[String] = &[];
+        for &index in &order {
+            let probe = &self.probes[index];
+            let common = scope
+                .iter()
+                .zip(&probe.scope)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for _ in common..scope.len() {
+                writeln!(vcd, "$upscope $end").unwrap();
+            }
+            for name in &probe.scope[common..] {
+                writeln!(vcd, "$scope module {} $end", vcd_name(name)).unwrap();
+            }
+            scope = &probe.scope;
+
+            let name = vcd_name(&probe.name);
+            let code = code(index);
+            match probe.width {
+                1 => writeln!(vcd, "$var wire 1 {code} {name} $end"),
+                width => writeln!(
+                    vcd,
+                    "$var wire {width} {code} {name} [{}:0] $end",
+                    width - 1
+                ),
+            }
+            .unwrap();
+        }
+        for _ in 0..scope.len() {
+            writeln!(vcd, "$upscope $end").unwrap();
+        }
+        writeln!(vcd, "$upscope $end").unwrap();
+        writeln!(vcd, "$enddefinitions $end").unwrap();
+
+        let mut previous: Option<&Vec<LogicBits>> = None;
+        for (time, values) in (self.first_step..).zip(&self.samples) {
+            let changed: Vec<usize> = (0..self.probes.len())
+                .filter(|&index| previous.is_none_or(|previous| previous[index] != values[index]))
+                .collect();
+            if !changed.is_empty() {
+                writeln!(vcd, "#{time}").unwrap();
+                if previous.is_none() {
+                    writeln!(vcd, "$dumpvars").unwrap();
+                }
+                for index in changed {
+                    let code = code(index);
+                    let bits = values[index].symbols(self.probes[index].width);
+                    match self.probes[index].width {
+                        1 => writeln!(vcd, "{bits}{code}"),
+                        _ => writeln!(vcd, "b{bits} {code}"),
+                    }
+                    .unwrap();
+                }
+                if previous.is_none() {
+                    writeln!(vcd, "$end").unwrap();
+                }
+            }
+            previous = Some(values);
+        }
+        // Marks the end of the run, so the last values are shown for a step too
+        writeln!(vcd, "#{}", self.end_step()).unwrap();
+        vcd
+    }
+}
+
+// Identifier codes are written in base 94, using every printable character
+fn code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+// References can't contain whitespace
+fn vcd_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|char| if char.is_whitespace() { '_' } else { char })
+        .collect();
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+impl Circuit {
+    // Samples the probed outputs now and after every step, until the recording is stopped
+    // Starting a new recording discards the previous one
+    pub fn start_recording(&mut self, probes: Vec<Probe>) {
+        self.right_size_solver();
+        let mut trace = Trace::new(probes);
+        trace.sample(self);
+        self.recording = Some(Box::new(trace));
+    }
+
+    pub fn recording(&self) -> Option<&Trace> {
+        self.recording.as_deref()
+    }
+
+    // Keeps at most `limit` of the latest samples of the current recording
+    pub fn limit_recording(&mut self, limit: Option<usize>) {
+        if let Some(trace) = &mut self.recording {
+            trace.set_limit(limit);
+        }
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Trace> {
+        self.recording.take().map(|trace| *trace)
+    }
+
+    // A probe on an output of a top level element, named after the element
+    pub fn probe(&self, output: OutputSpecifier) -> Probe {
+        let names = self.output_names(output.0);
+        Probe {
+            path: vec![output.0],
+            output: output.1,
+            scope: vec![],
+            name: names[output.1 .0].clone(),
+            width: self.pin_width(output).unwrap_or(1),
+        }
+    }
+
+    // Probes on every output, including those within embedded instances, which are scoped by
+    // instance
+    // The outputs of the instances themselves are left out, as the Output gates within them are the
+    // same signals
+    pub fn probe_all(&self) -> Vec<Probe> {
+        let mut probes = vec![];
+        self.add_probes(&[], &[], &mut probes);
+        probes
+    }
+
+    fn add_probes(&self, path: &[ElementIdx], scope: &[String], probes: &mut Vec<Probe>) {
+        let mut taken = HashSet::new();
+        let mut unique = |name: String| {
+            let mut candidate = name.clone();
+            let mut number = 1;
+            while !taken.insert(candidate.clone()) {
+                candidate = format!("{name}_{number}");
+                number += 1;
+            }
+            candidate
+        };
+
+        for (index, circuit_element) in self.elements.iter().enumerate() {
+            let element = ElementIdx(index);
+            let mut element_path = path.to_vec();
+            element_path.push(element);
+
+            if let Gate::Embedded(embed) = &circuit_element.gate {
+                let component = embed.component();
+                let name = component.name().unwrap_or("component");
+                let mut instance_scope = scope.to_vec();
+                instance_scope.push(unique(format!("{name}{index}")));
+                component
+                    .circuit()
+                    .add_probes(&element_path, &instance_scope, probes);
+                continue;
+            }
+
+            for (pin, name) in self.output_names(element).into_iter().enumerate() {
+                probes.push(Probe {
+                    path: element_path.clone(),
+                    output: OutputIdx(pin),
+                    scope: scope.to_vec(),
+                    name: unique(name),
+                    width: self.pin_width(element.output(pin)).unwrap_or(1),
+                });
+            }
+        }
+    }
+
+    // Ports are named like truth table columns, and other gates after their kind and index
+    fn output_names(&self, element: ElementIdx) -> Vec<String> {
+        let gate = &self[element].gate;
+        let columns = match gate {
+            Gate::Input(_) => Some((self.input_ports(), self.input_columns())),
+            Gate::Output(_) => Some((self.output_ports(), self.output_columns())),
+            _ => None,
+        };
+        if let Some((ports, columns)) = columns {
+            let port = ports.iter().position(|&port| port == element).unwrap();
+            return vec![columns[port].name.clone()];
+        }
+
+        let kind = match gate {
+            Gate::Button => "button",
+            Gate::Const(_) => "const",
+            Gate::And(_) => "and",
+            Gate::Or(_) => "or",
+            Gate::Not => "not",
+            Gate::Buf => "buf",
+            Gate::Xor(_) => "xor",
+            Gate::Nand(_) => "nand",
+            Gate::Nor(_) => "nor",
+            Gate::Xnor(_) => "xnor",
+            Gate::On => "on",
+            Gate::Off => "off",
+            Gate::Merge(_) => "merge",
+            Gate::Split(_) => "split",
+            Gate::SrLatch => "sr_latch",
+            Gate::DLatch => "d_latch",
+            Gate::DFlipFlop => "d_flip_flop",
+            Gate::JkFlipFlop => "jk_flip_flop",
+            Gate::Register(_) => "register",
+            Gate::TriState => "tri_state",
+            Gate::Clock { .. } => "clock",
+            Gate::Embedded(embed) => embed.component().name().unwrap_or("component"),
+            Gate::Input(_) | Gate::Output(_) => unreachable!(),
+        };
+        let name = format!("{kind}{}", element.0);
+        match gate {
+            // Latches and flip-flops output Q and its complement
+            Gate::SrLatch | Gate::DLatch | Gate::DFlipFlop | Gate::JkFlipFlop => {
+                vec![format!("{name}_q"), format!("{name}_q_n")]
+            }
+            _ if gate.output_count() == 1 => vec![name],
+            _ => (0..gate.output_count())
+                .map(|pin| format!("{name}_{pin}"))
+                .collect(),
+        }
+    }
+}
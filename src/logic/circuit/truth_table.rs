@@ -0,0 +1,278 @@
+use std::fmt;
+
+use crate::logic::solver::SettleError;
+
+use super::{
+    connection::{width_mask, ElementIdx, IOSpecifier},
+    embedded::EmbeddedCircuit,
+    lanes::LANES,
+    Circuit,
+};
+
+// Larger tables are too slow to build and too big to read
+pub const MAX_TRUTH_TABLE_BITS: u32 = 16;
+
+// Circuits are expected to settle long before this
+const SETTLE_STEPS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TruthTableError {
+    // The inputs have more bits between them than `MAX_TRUTH_TABLE_BITS`
+    TooManyInputs(u32),
+    Settle { row: usize, error: SettleError },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub width: u8,
+}
+
+// The settled outputs of a circuit for every combination of its inputs
+// Rows count up with the first input as the most significant bits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TruthTable {
+    pub inputs: Vec<Column>,
+    pub outputs: Vec<Column>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub inputs: Vec<u64>,
+    pub outputs: Vec<u64>,
+}
+
+impl Circuit {
+    // Every row starts from a freshly reset circuit, driven through its Input gates
+    pub fn truth_table(&self) -> Result<TruthTable, TruthTableError> {
+        let input_ports = self.input_ports();
+        let output_ports = self.output_ports();
+        let inputs = self.input_columns();
+        let outputs = self.output_columns();
+
+        let bits: u32 = inputs.iter().map(|column| column.width as u32).sum();
+        if bits > MAX_TRUTH_TABLE_BITS {
+            return Err(TruthTableError::TooManyInputs(bits));
+        }
+
+        // The values of each input in a row, split out of the row number
+        let row_inputs = |row: usize| {
+            let mut shift = bits;
+            inputs
+                .iter()
+                .map(|column| {
+                    shift -= column.width as u32;
+                    (row as u64 >> shift) & width_mask(column.width)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let fresh = || Circuit {
+            elements: self.elements.clone(),
+            connections: self.connections.clone(),
+            ..Default::default()
+        };
+
+        let row_count = 1 << bits;
+        let mut rows = Vec::with_capacity(row_count);
+        if self.is_lane_parallel() {
+            // Each pass settles 64 rows at once, one per lane
+            for start in (0..row_count).step_by(LANES) {
+                let lanes = LANES.min(row_count - start);
+                let mut circuit = fresh();
+                let mut values = vec![0; input_ports.len()];
+                for lane in 0..lanes {
+                    for (value, input) in values.iter_mut().zip(row_inputs(start + lane)) {
+                        *value |= input << lane;
+                    }
+                }
+
+                for (port, value) in input_ports.iter().zip(&values) {
+                    circuit.set_input_lanes(*port, *value);
+                }
+                circuit
+                    .settle(SETTLE_STEPS)
+                    .map_err(|error| TruthTableError::Settle { row: start, error })?;
+
+                for lane in 0..lanes {
+                    rows.push(Row {
+                        inputs: row_inputs(start + lane),
+                        outputs: output_ports
+                            .iter()
+                            .map(|port| (circuit.output_lanes(port.output(0)) >> lane) & 1)
+                            .collect(),
+                    });
+                }
+            }
+        } else {
+            for row in 0..row_count {
+                let mut circuit = fresh();
+                let values = row_inputs(row);
+                for (port, value) in input_ports.iter().zip(&values) {
+                    circuit.set_input(*port, *value);
+                }
+                circuit
+                    .settle(SETTLE_STEPS)
+                    .map_err(|error| TruthTableError::Settle { row, error })?;
+
+                rows.push(Row {
+                    inputs: values,
+                    outputs: output_ports
+                        .iter()
+                        .map(|port| circuit.output_bus_value(port.output(0)))
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(TruthTable {
+            inputs,
+            outputs,
+            rows,
+        })
+    }
+
+    // The name and width of each Input gate, in port order
+    pub fn input_columns(&self) -> Vec<Column> {
+        self.columns(&self.input_ports(), "in", |port| port.output(0).into())
+    }
+
+    pub fn output_columns(&self) -> Vec<Column> {
+        self.columns(&self.output_ports(), "out", |port| port.input(0).into())
+    }
+
+    // Drives the Input gate with this column name, returning false if there isn't one
+    pub fn set_named_input(&mut self, name: &str, value: u64) -> bool {
+        let columns = self.input_columns();
+        let Some(index) = columns.iter().position(|column| column.name == name) else {
+            return false;
+        };
+        let port = self.input_ports()[index];
+        self.set_input(port, value & width_mask(columns[index].width));
+        true
+    }
+
+    // The value of each Output gate, named like its column
+    pub fn named_outputs(&self) -> Vec<(String, u64)> {
+        self.output_ports()
+            .into_iter()
+            .zip(self.output_columns())
+            .map(|(port, column)| (column.name, self.output_bus_value(port.output(0))))
+            .collect()
+    }
+
+    // Unnamed ports are named after their position
+    fn columns(
+        &self,
+        ports: &[ElementIdx],
+        prefix: &str,
+        pin: impl Fn(ElementIdx) -> IOSpecifier,
+    ) -> Vec<Column> {
+        ports
+            .iter()
+            .enumerate()
+            .map(|(index, port)| Column {
+                name: self[*port]
+                    .gate
+                    .port_name()
+                    .map_or_else(|| format!("{prefix}{index}"), str::to_string),
+                width: self.pin_width(pin(*port)).unwrap_or(1),
+            })
+            .collect()
+    }
+}
+
+impl EmbeddedCircuit {
+    pub fn truth_table(&self) -> Result<TruthTable, TruthTableError> {
+        self.component().circuit().truth_table()
+    }
+}
+
+impl TruthTable {
+    // The outputs for the given input values, if they're in range
+    pub fn lookup(&self, inputs: &[u64]) -> Option<&[u64]> {
+        if inputs.len() != self.inputs.len() {
+            return None;
+        }
+
+        let mut row = 0;
+        for (value, column) in inputs.iter().zip(&self.inputs) {
+            if value & !width_mask(column.width) != 0 {
+                return None;
+            }
+            row = (row << column.width) | value;
+        }
+        self.rows.get(row as usize).map(|row| &row.outputs[..])
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.header().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            csv.push_str(&self.cells(row).join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+        let header = self.header();
+        let mut markdown = line(header.clone());
+        markdown.push_str(&line(header.iter().map(|_| "---".to_string()).collect()));
+        for row in &self.rows {
+            markdown.push_str(&line(self.cells(row)));
+        }
+        markdown
+    }
+
+    fn header(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .chain(&self.outputs)
+            .map(|column| column.name.clone())
+            .collect()
+    }
+
+    // Values are written in binary, padded to the width of their column
+    fn cells(&self, row: &Row) -> Vec<String> {
+        let inputs = self.inputs.iter().zip(&row.inputs);
+        let outputs = self.outputs.iter().zip(&row.outputs);
+        inputs
+            .chain(outputs)
+            .map(|(column, value)| format!("{value:0width$b}", width = column.width as usize))
+            .collect()
+    }
+}
+
+// Columns are aligned, with inputs and outputs separated by a bar
+impl fmt::Display for TruthTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = self.header();
+        let widths: Vec<_> = self
+            .inputs
+            .iter()
+            .chain(&self.outputs)
+            .zip(&header)
+            .map(|(column, name)| name.len().max(column.width as usize))
+            .collect();
+
+        let write_line = |f: &mut fmt::Formatter<'_>, cells: &[String]| {
+            for (index, (cell, width)) in cells.iter().zip(&widths).enumerate() {
+                if index == self.inputs.len() {
+                    write!(f, " |")?;
+                }
+                write!(f, " {cell:>width$}")?;
+            }
+            writeln!(f)
+        };
+
+        write_line(f, &header)?;
+        for row in &self.rows {
+            write_line(f, &self.cells(row))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,443 @@
+//! Combinational equivalence checking.
+//!
+//! Two circuits are equivalent when every Output gate settles to the same value
+//! as the Output gate with the same name in the other circuit, for every value
+//! of the Input gates, which are also matched by name. Unnamed ports are named
+//! after their position, like the columns of a truth table.
+//!
+//! Port names have to be unique within each circuit, and circuits with loops
+//! are rejected whichever way they're compared.
+//!
+//! Circuits with few input bits are compared by simulating every combination.
+//! Larger ones are encoded as a miter, which is satisfiable only if some input
+//! makes an output differ, and handed to a small SAT solver.
+
+mod sat;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use sat::{negate, Lit, Sat};
+
+use super::{
+    circuit::{
+        connection::{ElementIdx, IOSpecifier, OutputIdx},
+        Circuit, Column, TruthTableError,
+    },
+    gate::Gate,
+    SettleError,
+};
+
+// Inputs with at most this many bits between them are simulated exhaustively
+pub const EXHAUSTIVE_BITS: u32 = 10;
+
+// Circuits are expected to settle long before this
+const SETTLE_STEPS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    // Simulate small circuits exhaustively and use SAT for the rest
+    Auto,
+    Exhaustive,
+    Sat,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EquivalenceError {
+    // A port of one circuit has no port with the same name in the other
+    MissingPort(String),
+    // Two ports of one circuit have the same name, so they can't be matched
+    DuplicatePort(String),
+    WidthMismatch(String),
+    // An element of a circuit, by its path through embedded circuits, keeps state
+    NotCombinational(Vec<ElementIdx>),
+    // A circuit has a loop without any state in it
+    Cyclic,
+    TruthTable(TruthTableError),
+    // A circuit didn't settle while finding the outputs of a counterexample
+    Settle(SettleError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Equivalence {
+    Equivalent,
+    Different(Counterexample),
+}
+
+// Input values which make the circuits disagree, along with every output of both
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    pub inputs: Vec<(String, u64)>,
+    pub outputs: Vec<OutputValues>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputValues {
+    pub name: String,
+    pub a: u64,
+    pub b: u64,
+}
+
+impl Counterexample {
+    // The outputs which differ
+    pub fn mismatches(&self) -> impl Iterator<Item = &OutputValues> {
+        self.outputs.iter().filter(|output| output.a != output.b)
+    }
+}
+
+pub fn check(a: &Circuit, b: &Circuit) -> Result<Equivalence, EquivalenceError> {
+    check_with(a, b, Method::Auto)
+}
+
+pub fn check_with(
+    a: &Circuit,
+    b: &Circuit,
+    method: Method,
+) -> Result<Equivalence, EquivalenceError> {
+    let inputs = matching_columns(a.input_columns(), b.input_columns())?;
+    let outputs = matching_columns(a.output_columns(), b.output_columns())?;
+
+    let flat_a = a.flatten();
+    let flat_b = b.flatten();
+    for flat in [&flat_a, &flat_b] {
+        if let Some(element) = flat
+            .circuit()
+            .elements
+            .iter()
+            .position(|element| !is_combinational(&element.gate))
+        {
+            return Err(EquivalenceError::NotCombinational(
+                flat.path(ElementIdx(element)).to_vec(),
+            ));
+        }
+        // A loop could still settle when simulated, but the circuits would no longer be
+        // compared the same way with every method
+        if topological_order(flat.circuit()).is_none() {
+            return Err(EquivalenceError::Cyclic);
+        }
+    }
+
+    let bits: u32 = inputs.iter().map(|column| column.width as u32).sum();
+    let exhaustive = match method {
+        Method::Auto => bits <= EXHAUSTIVE_BITS,
+        Method::Exhaustive => true,
+        Method::Sat => false,
+    };
+
+    let differing_inputs = if exhaustive {
+        exhaustive_difference(a, b, &inputs)?
+    } else {
+        sat_difference(flat_a.circuit(), flat_b.circuit(), &inputs, &outputs)?
+    };
+
+    Ok(match differing_inputs {
+        None => Equivalence::Equivalent,
+        Some(inputs) => {
+            let a_outputs = simulate(a, &inputs)?;
+            let b_outputs = simulate(b, &inputs)?;
+            Equivalence::Different(Counterexample {
+                inputs,
+                outputs: outputs
+                    .iter()
+                    .map(|column| OutputValues {
+                        name: column.name.clone(),
+                        a: a_outputs[&column.name],
+                        b: b_outputs[&column.name],
+                    })
+                    .collect(),
+            })
+        }
+    })
+}
+
+// The columns of `a`, after checking `b` has the same ones in any order
+// Columns are found by name, so names must be unique, including those made up for unnamed ports
+fn matching_columns(a: Vec<Column>, b: Vec<Column>) -> Result<Vec<Column>, EquivalenceError> {
+    for columns in [&a, &b] {
+        let mut names = HashSet::new();
+        if let Some(column) = columns.iter().find(|column| !names.insert(&column.name)) {
+            return Err(EquivalenceError::DuplicatePort(column.name.clone()));
+        }
+    }
+
+    for (column, others) in a
+        .iter()
+        .map(|column| (column, &b))
+        .chain(b.iter().map(|column| (column, &a)))
+    {
+        match others.iter().find(|other| other.name == column.name) {
+            None => return Err(EquivalenceError::MissingPort(column.name.clone())),
+            Some(other) if other.width != column.width => {
+                return Err(EquivalenceError::WidthMismatch(column.name.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(a)
+}
+
+fn is_combinational(gate: &Gate) -> bool {
+    !matches!(
+        gate,
+        Gate::Button
+            | Gate::SrLatch
+            | Gate::DLatch
+            | Gate::DFlipFlop
+            | Gate::JkFlipFlop
+            | Gate::Register(_)
+            | Gate::Clock { .. }
+    )
+}
+
+// Settles a fresh copy of the circuit with the named inputs, returning its outputs by name
+fn simulate(
+    circuit: &Circuit,
+    inputs: &[(String, u64)],
+) -> Result<HashMap<String, u64>, EquivalenceError> {
+    let mut circuit = Circuit {
+        elements: circuit.elements.clone(),
+        connections: circuit.connections.clone(),
+        ..Default::default()
+    };
+
+    for (port, column) in circuit
+        .input_ports()
+        .into_iter()
+        .zip(circuit.input_columns())
+    {
+        if let Some((_, value)) = inputs.iter().find(|(name, _)| *name == column.name) {
+            circuit.set_input(port, *value);
+        }
+    }
+    circuit
+        .settle(SETTLE_STEPS)
+        .map_err(EquivalenceError::Settle)?;
+
+    Ok(circuit
+        .output_ports()
+        .into_iter()
+        .zip(circuit.output_columns())
+        .map(|(port, column)| (column.name, circuit.output_bus_value(port.output(0))))
+        .collect())
+}
+
+fn exhaustive_difference(
+    a: &Circuit,
+    b: &Circuit,
+    inputs: &[Column],
+) -> Result<Option<Vec<(String, u64)>>, EquivalenceError> {
+    let table_a = a.truth_table().map_err(EquivalenceError::TruthTable)?;
+    let table_b = b.truth_table().map_err(EquivalenceError::TruthTable)?;
+
+    let named = |values: &[u64], columns: &[Column]| {
+        columns
+            .iter()
+            .map(|column| column.name.clone())
+            .zip(values.iter().copied())
+            .collect::<HashMap<_, _>>()
+    };
+
+    for row in &table_a.rows {
+        let row_inputs = named(&row.inputs, &table_a.inputs);
+        let b_inputs: Vec<_> = table_b
+            .inputs
+            .iter()
+            .map(|column| row_inputs[&column.name])
+            .collect();
+        let b_outputs = named(table_b.lookup(&b_inputs).unwrap(), &table_b.outputs);
+
+        let same = table_a
+            .outputs
+            .iter()
+            .zip(&row.outputs)
+            .all(|(column, value)| b_outputs[&column.name] == *value);
+        if !same {
+            return Ok(Some(
+                inputs
+                    .iter()
+                    .map(|column| (column.name.clone(), row_inputs[&column.name]))
+                    .collect(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+fn sat_difference(
+    a: &Circuit,
+    b: &Circuit,
+    inputs: &[Column],
+    outputs: &[Column],
+) -> Result<Option<Vec<(String, u64)>>, EquivalenceError> {
+    let mut sat = Sat::default();
+
+    // Both circuits share a variable for each input bit
+    let input_bits: HashMap<_, _> = inputs
+        .iter()
+        .map(|column| {
+            let bits: Vec<_> = (0..column.width).map(|_| sat.new_var()).collect();
+            (column.name.clone(), bits)
+        })
+        .collect();
+
+    let outputs_a = encode(&mut sat, a, &input_bits)?;
+    let outputs_b = encode(&mut sat, b, &input_bits)?;
+
+    // The miter is true when any output bit differs
+    let mut differences = vec![];
+    for column in outputs {
+        for (bit_a, bit_b) in outputs_a[&column.name].iter().zip(&outputs_b[&column.name]) {
+            differences.push(sat.xor(*bit_a, *bit_b));
+        }
+    }
+    let miter = sat.or(&differences);
+    sat.add_clause(&[miter]);
+
+    Ok(sat.solve().map(|model| {
+        inputs
+            .iter()
+            .map(|column| {
+                let value = input_bits[&column.name]
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (bit, &lit)| {
+                        acc | ((Sat::model_value(&model, lit) as u64) << bit)
+                    });
+                (column.name.clone(), value)
+            })
+            .collect()
+    }))
+}
+
+fn parity(sat: &mut Sat, operands: &[Lit]) -> Lit {
+    let low = sat.constant(false);
+    operands
+        .iter()
+        .fold(low, |acc, &operand| sat.xor(acc, operand))
+}
+
+// Elements in an order where every element comes after those driving it, if there are no loops
+fn topological_order(circuit: &Circuit) -> Option<Vec<usize>> {
+    let count = circuit.elements.len();
+    let mut fan_in = vec![0; count];
+    let mut fan_out = vec![vec![]; count];
+    for connection in &circuit.connections {
+        fan_in[connection.to.0 .0] += 1;
+        fan_out[connection.from.0 .0].push(connection.to.0 .0);
+    }
+    let mut ready: VecDeque<_> = (0..count).filter(|&element| fan_in[element] == 0).collect();
+    let mut order = vec![];
+    while let Some(element) = ready.pop_front() {
+        order.push(element);
+        for &next in &fan_out[element] {
+            fan_in[next] -= 1;
+            if fan_in[next] == 0 {
+                ready.push_back(next);
+            }
+        }
+    }
+    (order.len() == count).then_some(order)
+}
+
+// Encodes a flattened, combinational circuit, returning the bits of each Output gate by name
+fn encode(
+    sat: &mut Sat,
+    circuit: &Circuit,
+    input_bits: &HashMap<String, Vec<Lit>>,
+) -> Result<HashMap<String, Vec<Lit>>, EquivalenceError> {
+    let width = |pin: IOSpecifier| circuit.pin_width(pin).unwrap_or(1) as usize;
+    let order = topological_order(circuit).ok_or(EquivalenceError::Cyclic)?;
+    let count = circuit.elements.len();
+
+    let input_names: HashMap<_, _> = circuit
+        .input_ports()
+        .into_iter()
+        .zip(circuit.input_columns())
+        .map(|(port, column)| (port, column.name))
+        .collect();
+
+    let low = sat.constant(false);
+    let high = sat.constant(true);
+
+    // The bits of every output pin, indexed by element and pin
+    let mut output_bits: Vec<Vec<Vec<Lit>>> = vec![vec![]; count];
+    for index in order {
+        let element = ElementIdx(index);
+        let gate = &circuit[element].gate;
+
+        // Several drivers of one input combine as a wired OR, bits without a driver are low
+        let inputs: Vec<Vec<Lit>> = (0..gate.input_count())
+            .map(|pin| {
+                let bits = width(element.input(pin).into());
+                (0..bits)
+                    .map(|bit| {
+                        let drivers: Vec<_> = circuit
+                            .connections
+                            .iter()
+                            .filter(|connection| {
+                                connection.to == element.input(pin)
+                                    && bit < connection.width as usize
+                            })
+                            .filter_map(|connection| {
+                                let OutputIdx(from_pin) = connection.from.1;
+                                output_bits[connection.from.0 .0][from_pin]
+                                    .get(bit)
+                                    .copied()
+                            })
+                            .collect();
+                        sat.or(&drivers)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let output_width = |pin: usize| width(element.output(pin).into());
+        let bit_of = |pin: &Vec<Lit>, bit: usize| pin.get(bit).copied().unwrap_or(low);
+        let bitwise = |sat: &mut Sat, combine: fn(&mut Sat, &[Lit]) -> Lit, invert: bool| {
+            let bits = (0..output_width(0))
+                .map(|bit| {
+                    let operands: Vec<_> = inputs.iter().map(|pin| bit_of(pin, bit)).collect();
+                    let result = combine(sat, &operands);
+                    if invert {
+                        negate(result)
+                    } else {
+                        result
+                    }
+                })
+                .collect();
+            vec![bits]
+        };
+        let outputs = match gate {
+            Gate::And(_) => bitwise(sat, Sat::and, false),
+            Gate::Nand(_) => bitwise(sat, Sat::and, true),
+            Gate::Or(_) => bitwise(sat, Sat::or, false),
+            Gate::Nor(_) => bitwise(sat, Sat::or, true),
+            Gate::Xor(_) => bitwise(sat, parity, false),
+            Gate::Xnor(_) => bitwise(sat, parity, true),
+            Gate::Not => vec![inputs[0].iter().map(|&bit| negate(bit)).collect()],
+            Gate::Buf | Gate::Output(_) => vec![inputs[0].clone()],
+            Gate::Const(value) => vec![vec![if *value { high } else { low }; output_width(0)]],
+            Gate::On => vec![vec![high; output_width(0)]],
+            Gate::Off => vec![vec![low; output_width(0)]],
+            Gate::Input(_) => vec![input_bits[&input_names[&element]].clone()],
+            Gate::Merge(_) => vec![inputs.iter().map(|pin| bit_of(pin, 0)).collect()],
+            Gate::Split(_) => inputs[0].iter().map(|&bit| vec![bit]).collect(),
+            Gate::TriState => {
+                let enable = bit_of(&inputs[1], 0);
+                vec![inputs[0]
+                    .iter()
+                    .map(|&bit| sat.and(&[bit, enable]))
+                    .collect()]
+            }
+            // Flattening removes embedded circuits, and stateful gates were already rejected
+            _ => unreachable!("only combinational gates remain after flattening"),
+        };
+        output_bits[index] = outputs;
+    }
+
+    Ok(circuit
+        .output_ports()
+        .into_iter()
+        .zip(circuit.output_columns())
+        .map(|(port, column)| (column.name, output_bits[port.0][0].clone()))
+        .collect())
+}
@@ -0,0 +1,435 @@
+// A literal is a variable index shifted left once, with the low bit set when it's negated
+pub type Lit = u32;
+
+pub fn negate(lit: Lit) -> Lit {
+    lit ^ 1
+}
+
+fn var(lit: Lit) -> usize {
+    (lit >> 1) as usize
+}
+
+fn value(values: &[Option<bool>], lit: Lit) -> Option<bool> {
+    values[var(lit)].map(|value| value ^ (lit & 1 == 1))
+}
+
+// A CDCL solver: conflicts are analysed into learned clauses, which let it jump back past decisions
+// which didn't cause them, and variables involved in recent conflicts are decided first
+// Clauses are watched by two literals each, and every learned clause is kept
+#[derive(Default)]
+pub struct Sat {
+    clauses: Vec<Vec<Lit>>,
+    // Clauses watching each literal, which must be revisited when it becomes false
+    watches: Vec<Vec<usize>>,
+    units: Vec<Lit>,
+    has_empty_clause: bool,
+    values: Vec<Option<bool>>,
+    // The decision level each variable was assigned at, and the clause which implied it
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    propagated: usize,
+    // Trail length before each decision
+    decisions: Vec<usize>,
+    activity: Vec<f64>,
+    bump: f64,
+    order: VarOrder,
+    // The last value of each variable, which it's given again when it's decided
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    constant_true: Option<Lit>,
+}
+
+// Restarts happen after this many conflicts, scaled by the Luby sequence
+const RESTART_CONFLICTS: u64 = 100;
+const ACTIVITY_DECAY: f64 = 0.95;
+
+impl Sat {
+    pub fn new_var(&mut self) -> Lit {
+        let var = self.values.len();
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        self.seen.push(false);
+        self.order.insert(var, &self.activity);
+        self.watches.extend([vec![], vec![]]);
+        (var as Lit) << 1
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        let mut clause = lits.to_vec();
+        clause.sort_unstable();
+        clause.dedup();
+        // Clauses containing a literal and its negation always hold
+        if clause.windows(2).any(|pair| pair[0] == negate(pair[1])) {
+            return;
+        }
+
+        match clause[..] {
+            [] => self.has_empty_clause = true,
+            [unit] => self.units.push(unit),
+            _ => {
+                let index = self.clauses.len();
+                self.watches[clause[0] as usize].push(index);
+                self.watches[clause[1] as usize].push(index);
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    pub fn constant(&mut self, value: bool) -> Lit {
+        let lit = match self.constant_true {
+            Some(lit) => lit,
+            None => {
+                let lit = self.new_var();
+                self.add_clause(&[lit]);
+                self.constant_true = Some(lit);
+                lit
+            }
+        };
+
+        if value {
+            lit
+        } else {
+            negate(lit)
+        }
+    }
+
+    // A literal which is true when every literal is
+    pub fn and(&mut self, lits: &[Lit]) -> Lit {
+        match lits {
+            [] => self.constant(true),
+            [lit] => *lit,
+            _ => {
+                let output = self.new_var();
+                let mut any_false = vec![output];
+                for &lit in lits {
+                    self.add_clause(&[negate(output), lit]);
+                    any_false.push(negate(lit));
+                }
+                self.add_clause(&any_false);
+                output
+            }
+        }
+    }
+
+    pub fn or(&mut self, lits: &[Lit]) -> Lit {
+        let negated: Vec<_> = lits.iter().map(|&lit| negate(lit)).collect();
+        negate(self.and(&negated))
+    }
+
+    pub fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let output = self.new_var();
+        let not = negate;
+        self.add_clause(&[not(output), a, b]);
+        self.add_clause(&[not(output), not(a), not(b)]);
+        self.add_clause(&[output, not(a), b]);
+        self.add_clause(&[output, a, not(b)]);
+        output
+    }
+
+    // A value for every variable which satisfies every clause, if there is one
+    pub fn solve(&mut self) -> Option<Vec<bool>> {
+        if self.has_empty_clause {
+            return None;
+        }
+        self.bump = 1.0;
+
+        for unit in std::mem::take(&mut self.units) {
+            match value(&self.values, unit) {
+                Some(false) => return None,
+                Some(true) => {}
+                None => self.assign(unit, None),
+            }
+        }
+
+        let mut restarts = 0;
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decisions.is_empty() {
+                    return None;
+                }
+                conflicts += 1;
+                self.learn(conflict);
+                continue;
+            }
+
+            if conflicts >= luby(restarts) * RESTART_CONFLICTS {
+                restarts += 1;
+                conflicts = 0;
+                self.backjump(0);
+            }
+
+            let Some(var) = self.next_decision() else {
+                return Some(self.values.iter().map(|value| value.unwrap()).collect());
+            };
+            self.decisions.push(self.trail.len());
+            let lit = ((var as Lit) << 1) | !self.phases[var] as Lit;
+            self.assign(lit, None);
+        }
+    }
+
+    pub fn model_value(model: &[bool], lit: Lit) -> bool {
+        model[var(lit)] ^ (lit & 1 == 1)
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = var(lit);
+        self.values[var] = Some(lit & 1 == 0);
+        self.levels[var] = self.decisions.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    // The unassigned variable which was involved in the most recent conflicts
+    fn next_decision(&mut self) -> Option<usize> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(var);
+            }
+        }
+        None
+    }
+
+    // Unassigns everything decided after the given level
+    fn backjump(&mut self, level: usize) {
+        let Some(&length) = self.decisions.get(level) else {
+            return;
+        };
+        for lit in self.trail.drain(length..) {
+            let var = var(lit);
+            self.phases[var] = self.values[var].unwrap();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.order.insert(var, &self.activity);
+        }
+        self.decisions.truncate(level);
+        self.propagated = length;
+    }
+
+    // Learns a clause from a conflict, made of the first literal which alone at the current level
+    // implies the conflict along with the earlier literals which do, then jumps back to where it
+    // has a single unassigned literal
+    fn learn(&mut self, conflict: usize) {
+        let level = self.decisions.len();
+        let mut learned = vec![0];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut implied = None;
+        let mut index = self.trail.len();
+        loop {
+            // The implied literal of a reason clause is its first
+            let skip = implied.is_some() as usize;
+            for position in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][position];
+                let var = var(lit);
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump_activity(var);
+                if self.levels[var] == level {
+                    pending += 1;
+                } else {
+                    learned.push(lit);
+                }
+            }
+
+            let lit = loop {
+                index -= 1;
+                if self.seen[var(self.trail[index])] {
+                    break self.trail[index];
+                }
+            };
+            self.seen[var(lit)] = false;
+            pending -= 1;
+            if pending == 0 {
+                learned[0] = negate(lit);
+                break;
+            }
+            implied = Some(lit);
+            clause = self.reasons[var(lit)].expect("only decisions have no reason");
+        }
+        for &lit in &learned[1..] {
+            self.seen[var(lit)] = false;
+        }
+        self.bump *= 1.0 / ACTIVITY_DECAY;
+
+        // The second watch is the literal which was assigned last, so it's the one to jump back to
+        let target = match (1..learned.len()).max_by_key(|&index| self.levels[var(learned[index])])
+        {
+            Some(latest) => {
+                learned.swap(1, latest);
+                self.levels[var(learned[1])]
+            }
+            None => 0,
+        };
+        self.backjump(target);
+
+        let reason = (learned.len() > 1).then(|| {
+            let index = self.clauses.len();
+            self.watches[learned[0] as usize].push(index);
+            self.watches[learned[1] as usize].push(index);
+            self.clauses.push(learned.clone());
+            index
+        });
+        self.assign(learned[0], reason);
+    }
+
+    fn bump_activity(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        // Activities are scaled down together before they overflow
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+        self.order.increased(var, &self.activity);
+    }
+
+    // Assigns every literal implied by the trail, returning the clause which failed on a conflict
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = negate(self.trail[self.propagated]);
+            self.propagated += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit as usize]);
+            let mut conflict = None;
+            let mut index = 0;
+            while index < watchers.len() {
+                let clause_index = watchers[index];
+                let clause = &mut self.clauses[clause_index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+
+                if value(&self.values, clause[0]) == Some(true) {
+                    index += 1;
+                    continue;
+                }
+
+                // Watch another literal which isn't false, if there is one
+                let replacement = (2..clause.len())
+                    .find(|&other| value(&self.values, clause[other]) != Some(false));
+                if let Some(other) = replacement {
+                    clause.swap(1, other);
+                    self.watches[clause[1] as usize].push(watchers.swap_remove(index));
+                    continue;
+                }
+
+                match value(&self.values, clause[0]) {
+                    None => {
+                        let unit = clause[0];
+                        self.assign(unit, Some(clause_index));
+                    }
+                    _ => {
+                        conflict = Some(clause_index);
+                        break;
+                    }
+                }
+                index += 1;
+            }
+
+            self.watches[false_lit as usize].append(&mut watchers);
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+}
+
+// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+fn luby(index: u64) -> u64 {
+    let (mut size, mut power) = (1, 1);
+    while size < index + 1 {
+        size = 2 * size + 1;
+        power *= 2;
+    }
+    let mut index = index;
+    while size - 1 != index {
+        size = (size - 1) / 2;
+        power /= 2;
+        index %= size;
+    }
+    power
+}
+
+// Unassigned variables as a max-heap on their activity
+#[derive(Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    // Each variable's place in the heap, if it's in it
+    positions: Vec<Option<usize>>,
+}
+
+impl VarOrder {
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.positions.len() <= var {
+            self.positions.resize(var + 1, None);
+        }
+        if self.positions[var].is_some() {
+            return;
+        }
+        self.positions[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, var: usize, activity: &[f64]) {
+        if let Some(position) = self.positions[var] {
+            self.sift_up(position, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let last = self.heap.pop()?;
+        let top = match self.heap.is_empty() {
+            true => last,
+            false => std::mem::replace(&mut self.heap[0], last),
+        };
+        self.positions[top] = None;
+        if !self.heap.is_empty() {
+            self.positions[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn sift_up(&mut self, mut position: usize, activity: &[f64]) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[position]] {
+                break;
+            }
+            self.swap(position, parent);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize, activity: &[f64]) {
+        loop {
+            let larger = [2 * position + 1, 2 * position + 2]
+                .into_iter()
+                .filter(|&child| child < self.heap.len())
+                .max_by(|&a, &b| activity[self.heap[a]].total_cmp(&activity[self.heap[b]]));
+            match larger {
+                Some(child) if activity[self.heap[child]] > activity[self.heap[position]] => {
+                    self.swap(position, child);
+                    position = child;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.positions[self.heap[a]] = Some(a);
+        self.positions[self.heap[b]] = Some(b);
+    }
+}
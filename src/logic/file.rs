@@ -0,0 +1,822 @@
+//! On-disk circuit format.
+//!
+//! Circuits are stored as JSON with a top level version number:
+//!
+//! ```json
+//! {
+//!   "version": 6,
+//!   "components": [],
+//!   "circuit": {
+//!     "elements": [
+//!       { "gate": { "kind": "Input", "label": "a" }, "position": [0.0, 0.0] },
+//!       { "gate": { "kind": "And", "inputs": 2 }, "position": [1.0, 0.0] }
+//!     ],
+//!     "connections": [{ "from": [0, 0], "to": [1, 0], "width": 1 }]
+//!   }
+//! }
+//! ```
+//!
+//! Connections refer to elements by their index in `elements` and to pins by
+//! their index on that element, along with the number of bits they carry.
+//! Elements may also store their `[rise, fall]` delay, which defaults to one
+//! step each. The definitions of embedded circuits are stored once each in
+//! `components`, as a `name` and a `circuit` using the same layout, and
+//! `Gate::Embedded` elements refer to them by index in `gate.component`.
+//! Components only refer to components before them.
+//!
+//! Files written by older versions are upgraded by [`MIGRATIONS`] before being
+//! deserialized, so the typed structures below only ever describe the current
+//! version.
+
+use std::{collections::HashMap, fmt, path::Path, sync::Arc};
+
+use glam::Vec2;
+use miniserde::{
+    json::{self, Number, Object, Value},
+    Deserialize, Serialize,
+};
+
+use super::{
+    circuit::{
+        connection::{
+            ConnectionError, ElementIdx, InputIdx, InputSpecifier, OutputIdx, OutputSpecifier,
+            MAX_BUS_WIDTH,
+        },
+        element::Delay,
+        embedded::{Component, EmbeddedCircuit},
+        Circuit,
+    },
+    gate::{Gate, FAN_IN},
+};
+
+pub const CURRENT_VERSION: u64 = 6;
+
+// Upgrades the root object of a file from version `index + 1` to `index + 2`
+type Migration = fn(&mut Object) -> Result<(), FileError>;
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize - 1] = [
+    migrate_bus_widths,
+    migrate_fan_in,
+    migrate_explicit_ports,
+    migrate_shared_components,
+    migrate_single_drivers,
+];
+
+// Before version 5 every circuit, including embedded ones, was inside the root's `circuit`
+fn root_circuit(root: &mut Object) -> Result<&mut Object, FileError> {
+    match root.get_mut("circuit") {
+        Some(Value::Object(circuit)) => Ok(circuit),
+        _ => Err(FileError::MissingField("circuit")),
+    }
+}
+
+// Version 2 added bus widths, all earlier connections carried a single bit
+fn migrate_bus_widths(root: &mut Object) -> Result<(), FileError> {
+    for_each_circuit(root_circuit(root)?, &mut |circuit| {
+        if let Some(Value::Array(connections)) = circuit.get_mut("connections") {
+            for connection in connections.iter_mut() {
+                if let Value::Object(connection) = connection {
+                    connection.insert("width".into(), Value::Number(Number::U64(1)));
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// Version 3 added configurable fan-in, all earlier basic gates had two inputs
+fn migrate_fan_in(root: &mut Object) -> Result<(), FileError> {
+    const BASIC_GATES: [&str; 6] = ["And", "Or", "Xor", "Nand", "Nor", "Xnor"];
+
+    for_each_circuit(root_circuit(root)?, &mut |circuit| {
+        for gate in gates_mut(circuit) {
+            let basic = matches!(
+                gate.get("kind"),
+                Some(Value::String(kind)) if BASIC_GATES.contains(&kind.as_str())
+            );
+            if basic {
+                gate.insert("inputs".into(), Value::Number(Number::U64(2)));
+            }
+        }
+    });
+    Ok(())
+}
+
+// Version 4 made the Input and Output gates of embedded circuits their pins
+// Earlier embedded circuits used their disconnected pins, and any Input or Output gates were
+// ordinary elements, so they're replaced by gates which behave the same way
+fn migrate_explicit_ports(root: &mut Object) -> Result<(), FileError> {
+    for gate in gates_mut(root_circuit(root)?) {
+        if let Some(Value::Object(embedded)) = gate.get_mut("circuit") {
+            replace_ports(embedded);
+        }
+    }
+    Ok(())
+}
+
+// An Output gate had an input and an output, which was always low, and no gate does the same,
+// so it becomes an embedded circuit which ignores its input and drives its output low
+const ALWAYS_LOW: &str = r#"{"kind":"Embedded","circuit":{
+    "elements":[
+        {"gate":{"kind":"Input"},"position":[0,0]},
+        {"gate":{"kind":"Off"},"position":[0,-1]},
+        {"gate":{"kind":"Output"},"position":[1,0]}
+    ],
+    "connections":[{"from":[1,0],"to":[2,0],"width":1}]
+}}"#;
+
+fn replace_ports(circuit: &mut Object) {
+    for gate in gates_mut(circuit) {
+        match gate.get_mut("kind") {
+            Some(Value::String(kind)) if kind == "Embedded" => {
+                if let Some(Value::Object(embedded)) = gate.get_mut("circuit") {
+                    replace_ports(embedded);
+                }
+            }
+            // Nothing drove an Input gate inside an embedded circuit, so it was always off
+            Some(Value::String(kind)) if kind == "Input" => {
+                *kind = "Off".into();
+                gate.remove("label");
+            }
+            Some(Value::String(kind)) if kind == "Output" => {
+                *gate = json::from_str(ALWAYS_LOW).unwrap();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Version 5 stored each distinct embedded circuit once, in the root's `components`
+// Identical embedded circuits become instances of the same component
+fn migrate_shared_components(root: &mut Object) -> Result<(), FileError> {
+    let mut components = vec![];
+    let mut indices = HashMap::new();
+    hoist_components(root_circuit(root)?, &mut components, &mut indices);
+    root.insert(
+        "components".into(),
+        Value::Array(components.into_iter().collect()),
+    );
+    Ok(())
+}
+
+// Moves embedded circuits into `components`, nested ones first so components only refer backwards
+fn hoist_components(
+    circuit: &mut Object,
+    components: &mut Vec<Value>,
+    indices: &mut HashMap<String, usize>,
+) {
+    for gate in gates_mut(circuit) {
+        let Some(Value::Object(mut embedded)) = gate.remove("circuit") else {
+            continue;
+        };
+        hoist_components(&mut embedded, components, indices);
+
+        let key = json::to_string(&embedded);
+        let index = *indices.entry(key).or_insert_with(|| {
+            let mut component = Object::new();
+            component.insert("circuit".into(), Value::Object(embedded));
+            components.push(Value::Object(component));
+            components.len() - 1
+        });
+        gate.insert("component".into(), Value::Number(Number::U64(index as u64)));
+    }
+}
+
+// Version 6 allowed each input a single driver, unless they're all tri-state buffers sharing a
+// bus, and stopped gates without state from feeding themselves
+// Inputs with several drivers took the OR of them, which an Or gate now does, and self-loops go
+// through a Buf, each of which adds a step of delay
+fn migrate_single_drivers(root: &mut Object) -> Result<(), FileError> {
+    if let Some(Value::Array(components)) = root.get_mut("components") {
+        for component in components.iter_mut() {
+            if let Value::Object(component) = component {
+                if let Some(Value::Object(circuit)) = component.get_mut("circuit") {
+                    split_drivers(circuit);
+                }
+            }
+        }
+    }
+    split_drivers(root_circuit(root)?);
+    Ok(())
+}
+
+fn split_drivers(circuit: &mut Object) {
+    const STATEFUL_GATES: [&str; 6] = [
+        "SrLatch",
+        "DLatch",
+        "DFlipFlop",
+        "JkFlipFlop",
+        "Register",
+        "Embedded",
+    ];
+
+    // Malformed connections are left for loading to report
+    let Some(connections) = circuit
+        .get("connections")
+        .and_then(|value| json::from_str::<Vec<ConnectionFile>>(&json::to_string(value)).ok())
+    else {
+        return;
+    };
+    let Some(Value::Array(elements)) = circuit.get_mut("elements") else {
+        return;
+    };
+
+    let kinds: Vec<Option<String>> = elements
+        .iter()
+        .map(|element| match element {
+            Value::Object(element) => match element.get("gate") {
+                Some(Value::Object(gate)) => match gate.get("kind") {
+                    Some(Value::String(kind)) => Some(kind.clone()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let kind = |index: usize| kinds.get(index).and_then(Option::as_deref);
+
+    // Added gates go just before the input they feed
+    let mut added = vec![];
+    let mut add_gate = |kind: &str, inputs: Option<usize>, before: usize| {
+        let position = match elements.get(before) {
+            Some(Value::Object(element)) => position(element.get("position")),
+            _ => (0.0, 0.0),
+        };
+        let mut gate = Object::new();
+        gate.insert("kind".into(), Value::String(kind.into()));
+        if let Some(inputs) = inputs {
+            gate.insert("inputs".into(), Value::Number(Number::U64(inputs as u64)));
+        }
+        let mut element = Object::new();
+        element.insert("gate".into(), Value::Object(gate));
+        element.insert(
+            "position".into(),
+            Value::Array(
+                [position.0 - 1.0, position.1]
+                    .into_iter()
+                    .map(|coordinate| Value::Number(Number::F64(coordinate)))
+                    .collect(),
+            ),
+        );
+        added.push(Value::Object(element));
+        kinds.len() + added.len() - 1
+    };
+
+    let mut wires: Vec<ConnectionFile> = vec![];
+    for mut connection in connections {
+        let stateful = kind(connection.to.0).is_some_and(|kind| STATEFUL_GATES.contains(&kind));
+        if connection.from.0 == connection.to.0 && !stateful {
+            let buf = add_gate("Buf", None, connection.to.0);
+            wires.push(ConnectionFile {
+                from: connection.from,
+                to: (buf, 0),
+                width: connection.width,
+            });
+            connection.from = (buf, 0);
+        }
+        if !wires.contains(&connection) {
+            wires.push(connection);
+        }
+    }
+
+    let mut inputs = vec![];
+    for wire in &wires {
+        if !inputs.contains(&wire.to) {
+            inputs.push(wire.to);
+        }
+    }
+
+    let mut migrated = vec![];
+    for input in inputs {
+        let drivers: Vec<&ConnectionFile> = wires.iter().filter(|wire| wire.to == input).collect();
+        let shared_bus = drivers
+            .iter()
+            .all(|wire| kind(wire.from.0) == Some("TriState"));
+        if drivers.len() == 1 || shared_bus {
+            migrated.extend(drivers.iter().map(|wire| wire.to_value()));
+            continue;
+        }
+
+        // More drivers than an Or gate takes are combined in stages
+        let width = drivers[0].width;
+        let mut sources: Vec<_> = drivers.iter().map(|wire| wire.from).collect();
+        while sources.len() > 1 {
+            let inputs = sources.len().min(*FAN_IN.end() as usize);
+            let or = add_gate("Or", Some(inputs), input.0);
+            for (pin, from) in sources.drain(..inputs).enumerate() {
+                migrated.push(
+                    ConnectionFile {
+                        from,
+                        to: (or, pin),
+                        width,
+                    }
+                    .to_value(),
+                );
+            }
+            sources.push((or, 0));
+        }
+        migrated.push(
+            ConnectionFile {
+                from: sources[0],
+                to: input,
+                width,
+            }
+            .to_value(),
+        );
+    }
+
+    elements.extend(added);
+    circuit.insert(
+        "connections".into(),
+        Value::Array(migrated.into_iter().collect()),
+    );
+}
+
+// An element's position, or the origin if it's missing
+fn position(position: Option<&Value>) -> (f64, f64) {
+    let coordinate = |value: &Value| match value {
+        Value::Number(Number::U64(value)) => *value as f64,
+        Value::Number(Number::I64(value)) => *value as f64,
+        Value::Number(Number::F64(value)) => *value,
+        _ => 0.0,
+    };
+    match position {
+        Some(Value::Array(position)) if position.len() == 2 => {
+            (coordinate(&position[0]), coordinate(&position[1]))
+        }
+        _ => (0.0, 0.0),
+    }
+}
+
+// Visits a circuit object and every embedded circuit object within it
+fn for_each_circuit(circuit: &mut Object, visit: &mut impl FnMut(&mut Object)) {
+    visit(circuit);
+
+    for gate in gates_mut(circuit) {
+        if let Some(Value::Object(embedded)) = gate.get_mut("circuit") {
+            for_each_circuit(embedded, visit);
+        }
+    }
+}
+
+// The gate objects of a circuit object's elements
+fn gates_mut(circuit: &mut Object) -> impl Iterator<Item = &mut Object> {
+    let elements = match circuit.get_mut("elements") {
+        Some(Value::Array(elements)) => elements.iter_mut(),
+        _ => [].iter_mut(),
+    };
+
+    elements.filter_map(|element| match element {
+        Value::Object(element) => match element.get_mut("gate") {
+            Some(Value::Object(gate)) => Some(gate),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(std::io::Error),
+    Json(miniserde::Error),
+    MissingVersion,
+    UnsupportedVersion(u64),
+    UnknownGate(String),
+    InvalidFanIn(u8),
+    // A bus gate's width was zero or wider than a connection can carry
+    InvalidWidth(u8),
+    InvalidClock { period: u32, high_ticks: u32 },
+    MissingField(&'static str),
+    InvalidConnection(usize),
+    IncompatibleConnection(usize, ConnectionError),
+    // A gate referred to a component which doesn't exist or doesn't come before it
+    UnknownComponent(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CircuitFileRoot {
+    version: u64,
+    components: Vec<ComponentFile>,
+    circuit: CircuitFile,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ComponentFile {
+    name: Option<String>,
+    circuit: CircuitFile,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CircuitFile {
+    elements: Vec<ElementFile>,
+    connections: Vec<ConnectionFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ElementFile {
+    gate: GateFile,
+    position: (f32, f32),
+    // Rise and fall delays, left out when they're the default
+    delay: Option<(u32, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GateFile {
+    kind: String,
+    label: Option<String>,
+    value: Option<bool>,
+    inputs: Option<u8>,
+    width: Option<u8>,
+    period: Option<u32>,
+    high_ticks: Option<u32>,
+    phase: Option<u32>,
+    component: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct ConnectionFile {
+    from: (usize, usize),
+    to: (usize, usize),
+    width: u8,
+}
+
+impl ConnectionFile {
+    fn to_value(self) -> Value {
+        let pin = |(element, pin): (usize, usize)| {
+            Value::Array(
+                [element, pin]
+                    .into_iter()
+                    .map(|index| Value::Number(Number::U64(index as u64)))
+                    .collect(),
+            )
+        };
+        let mut connection = Object::new();
+        connection.insert("from".into(), pin(self.from));
+        connection.insert("to".into(), pin(self.to));
+        connection.insert(
+            "width".into(),
+            Value::Number(Number::U64(self.width.into())),
+        );
+        Value::Object(connection)
+    }
+}
+
+impl Circuit {
+    pub fn to_json(&self) -> String {
+        let mut writer = ComponentWriter::default();
+        let circuit = writer.circuit(self);
+        json::to_string(&CircuitFileRoot {
+            version: CURRENT_VERSION,
+            components: writer.components,
+            circuit,
+        })
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, FileError> {
+        let Value::Object(mut root) = json::from_str::<Value>(source)? else {
+            return Err(FileError::MissingVersion);
+        };
+
+        let version = match root.get("version") {
+            Some(Value::Number(Number::U64(version))) => *version,
+            _ => return Err(FileError::MissingVersion),
+        };
+
+        if version == 0 || version > CURRENT_VERSION {
+            return Err(FileError::UnsupportedVersion(version));
+        }
+
+        for migration in &MIGRATIONS[version as usize - 1..] {
+            migration(&mut root)?;
+        }
+
+        root.insert(
+            "version".into(),
+            Value::Number(Number::U64(CURRENT_VERSION)),
+        );
+
+        let root = json::from_str::<CircuitFileRoot>(&json::to_string(&root))?;
+
+        let mut components = vec![];
+        for component in root.components {
+            let circuit = component.circuit.load(&components)?;
+            components.push(Arc::new(match component.name {
+                Some(name) => Component::named(name, circuit),
+                None => Component::new(circuit),
+            }));
+        }
+
+        root.circuit.load(&components)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        std::fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+// Collects the components used by a circuit while it's written, each only once
+#[derive(Default)]
+struct ComponentWriter {
+    components: Vec<ComponentFile>,
+    // Circuits hold on to their components while they're written, so their addresses are stable
+    indices: HashMap<*const Component, usize>,
+}
+
+impl ComponentWriter {
+    // Components used by another component are written before it
+    fn component(&mut self, component: &Arc<Component>) -> usize {
+        if let Some(index) = self.indices.get(&Arc::as_ptr(component)) {
+            return *index;
+        }
+
+        let circuit = self.circuit(component.circuit());
+        self.components.push(ComponentFile {
+            name: component.name().map(str::to_string),
+            circuit,
+        });
+
+        let index = self.components.len() - 1;
+        self.indices.insert(Arc::as_ptr(component), index);
+        index
+    }
+
+    fn circuit(&mut self, circuit: &Circuit) -> CircuitFile {
+        let elements = circuit
+            .elements
+            .iter()
+            .map(|element| ElementFile {
+                gate: self.gate(&element.gate),
+                position: (element.position.x, element.position.y),
+                delay: (element.delay != Delay::default())
+                    .then_some((element.delay.rise, element.delay.fall)),
+            })
+            .collect();
+
+        let connections = circuit
+            .connections
+            .iter()
+            .map(|connection| ConnectionFile {
+                from: (connection.from.0 .0, connection.from.1 .0),
+                to: (connection.to.0 .0, connection.to.1 .0),
+                width: connection.width,
+            })
+            .collect();
+
+        CircuitFile {
+            elements,
+            connections,
+        }
+    }
+}
+
+impl CircuitFile {
+    // Embedded gates may only refer to `components`
+    fn load(self, components: &[Arc<Component>]) -> Result<Circuit, FileError> {
+        let mut circuit = Circuit::default();
+
+        for element in self.elements {
+            let (x, y) = element.position;
+            let index = circuit.add_gate(element.gate.load(components)?, Vec2::new(x, y));
+            if let Some((rise, fall)) = element.delay {
+                circuit.set_delay(index, Delay::new(rise, fall));
+            }
+        }
+
+        for (index, connection) in self.connections.into_iter().enumerate() {
+            let from = OutputSpecifier(ElementIdx(connection.from.0), OutputIdx(connection.from.1));
+            let to = InputSpecifier(ElementIdx(connection.to.0), InputIdx(connection.to.1));
+
+            let in_range = circuit
+                .elements
+                .get(from.0 .0)
+                .is_some_and(|element| from.1 .0 < element.gate.output_count())
+                && circuit
+                    .elements
+                    .get(to.0 .0)
+                    .is_some_and(|element| to.1 .0 < element.gate.input_count());
+
+            if !in_range {
+                return Err(FileError::InvalidConnection(index));
+            }
+
+            circuit
+                .add_connection(from.to(to).with_width(connection.width))
+                .map_err(|err| FileError::IncompatibleConnection(index, err))?;
+        }
+
+        Ok(circuit)
+    }
+}
+
+impl GateFile {
+    fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            label: None,
+            value: None,
+            inputs: None,
+            width: None,
+            period: None,
+            high_ticks: None,
+            phase: None,
+            component: None,
+        }
+    }
+
+    fn basic(kind: &str, inputs: u8) -> Self {
+        Self {
+            inputs: Some(inputs),
+            ..GateFile::new(kind)
+        }
+    }
+
+    fn fan_in(&self) -> Result<u8, FileError> {
+        let inputs = self.inputs.ok_or(FileError::MissingField("inputs"))?;
+        if !FAN_IN.contains(&inputs) {
+            return Err(FileError::InvalidFanIn(inputs));
+        }
+        Ok(inputs)
+    }
+
+    fn width(&self) -> Result<u8, FileError> {
+        let width = self.width.ok_or(FileError::MissingField("width"))?;
+        if !(1..=MAX_BUS_WIDTH).contains(&width) {
+            return Err(FileError::InvalidWidth(width));
+        }
+        Ok(width)
+    }
+}
+
+impl ComponentWriter {
+    fn gate(&mut self, gate: &Gate) -> GateFile {
+        match gate {
+            Gate::Button => GateFile::new("Button"),
+            Gate::Const(value) => GateFile {
+                value: Some(*value),
+                ..GateFile::new("Const")
+            },
+            Gate::And(inputs) => GateFile::basic("And", *inputs),
+            Gate::Or(inputs) => GateFile::basic("Or", *inputs),
+            Gate::Not => GateFile::new("Not"),
+            Gate::Buf => GateFile::new("Buf"),
+            Gate::Xor(inputs) => GateFile::basic("Xor", *inputs),
+            Gate::Nand(inputs) => GateFile::basic("Nand", *inputs),
+            Gate::Nor(inputs) => GateFile::basic("Nor", *inputs),
+            Gate::Xnor(inputs) => GateFile::basic("Xnor", *inputs),
+            Gate::On => GateFile::new("On"),
+            Gate::Off => GateFile::new("Off"),
+            Gate::Input(label) => GateFile {
+                label: label.clone(),
+                ..GateFile::new("Input")
+            },
+            Gate::Output(label) => GateFile {
+                label: label.clone(),
+                ..GateFile::new("Output")
+            },
+            Gate::Merge(width) => GateFile {
+                width: Some(*width),
+                ..GateFile::new("Merge")
+            },
+            Gate::Split(width) => GateFile {
+                width: Some(*width),
+                ..GateFile::new("Split")
+            },
+            Gate::SrLatch => GateFile::new("SrLatch"),
+            Gate::DLatch => GateFile::new("DLatch"),
+            Gate::DFlipFlop => GateFile::new("DFlipFlop"),
+            Gate::JkFlipFlop => GateFile::new("JkFlipFlop"),
+            Gate::Register(width) => GateFile {
+                width: Some(*width),
+                ..GateFile::new("Register")
+            },
+            Gate::TriState => GateFile::new("TriState"),
+            Gate::Clock {
+                period,
+                high_ticks,
+                phase,
+            } => GateFile {
+                period: Some(*period),
+                high_ticks: Some(*high_ticks),
+                phase: Some(*phase),
+                ..GateFile::new("Clock")
+            },
+            Gate::Embedded(embed) => GateFile {
+                component: Some(self.component(embed.component())),
+                ..GateFile::new("Embedded")
+            },
+        }
+    }
+}
+
+impl GateFile {
+    fn load(self, components: &[Arc<Component>]) -> Result<Gate, FileError> {
+        let file = self;
+        let gate = match file.kind.as_str() {
+            "Button" => Gate::Button,
+            "Const" => Gate::Const(file.value.ok_or(FileError::MissingField("value"))?),
+            "And" => Gate::And(file.fan_in()?),
+            "Or" => Gate::Or(file.fan_in()?),
+            "Not" => Gate::Not,
+            "Buf" => Gate::Buf,
+            "Xor" => Gate::Xor(file.fan_in()?),
+            "Nand" => Gate::Nand(file.fan_in()?),
+            "Nor" => Gate::Nor(file.fan_in()?),
+            "Xnor" => Gate::Xnor(file.fan_in()?),
+            "On" => Gate::On,
+            "Off" => Gate::Off,
+            "Input" => Gate::Input(file.label),
+            "Output" => Gate::Output(file.label),
+            "Merge" => Gate::Merge(file.width()?),
+            "Split" => Gate::Split(file.width()?),
+            "SrLatch" => Gate::SrLatch,
+            "DLatch" => Gate::DLatch,
+            "DFlipFlop" => Gate::DFlipFlop,
+            "JkFlipFlop" => Gate::JkFlipFlop,
+            "Register" => Gate::Register(file.width()?),
+            "TriState" => Gate::TriState,
+            "Clock" => {
+                let period = file.period.ok_or(FileError::MissingField("period"))?;
+                let high_ticks = file
+                    .high_ticks
+                    .ok_or(FileError::MissingField("high_ticks"))?;
+                if period == 0 || high_ticks > period {
+                    return Err(FileError::InvalidClock { period, high_ticks });
+                }
+                Gate::Clock {
+                    period,
+                    high_ticks,
+                    phase: file.phase.unwrap_or(0),
+                }
+            }
+            "Embedded" => {
+                let index = file.component.ok_or(FileError::MissingField("component"))?;
+                let component = components
+                    .get(index)
+                    .ok_or(FileError::UnknownComponent(index))?;
+                EmbeddedCircuit::instance(component.clone()).into()
+            }
+            _ => return Err(FileError::UnknownGate(file.kind)),
+        };
+
+        Ok(gate)
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "{error}"),
+            FileError::Json(_) => write!(f, "The file isn't a valid circuit"),
+            FileError::MissingVersion => write!(f, "The file has no version"),
+            FileError::UnsupportedVersion(version) => write!(
+                f,
+                "Version {version} isn't supported, the latest is {CURRENT_VERSION}"
+            ),
+            FileError::UnknownGate(kind) => write!(f, "Unknown gate kind {kind:?}"),
+            FileError::InvalidFanIn(inputs) => {
+                write!(f, "{inputs} isn't a valid number of gate inputs")
+            }
+            FileError::InvalidWidth(width) => write!(f, "{width} isn't a valid bus width"),
+            FileError::InvalidClock { period, high_ticks } => write!(
+                f,
+                "A clock can't be high for {high_ticks} of every {period} steps"
+            ),
+            FileError::MissingField(field) => write!(f, "Missing field {field:?}"),
+            FileError::InvalidConnection(index) => {
+                write!(f, "Connection {index} refers to a pin which doesn't exist")
+            }
+            FileError::IncompatibleConnection(index, error) => {
+                write!(f, "Connection {index} can't be made: {error}")
+            }
+            FileError::UnknownComponent(index) => {
+                write!(f, "Component {index} doesn't exist before it's used")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(error: std::io::Error) -> Self {
+        FileError::Io(error)
+    }
+}
+
+impl From<miniserde::Error> for FileError {
+    fn from(error: miniserde::Error) -> Self {
+        FileError::Json(error)
+    }
+}
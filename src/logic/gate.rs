@@ -5,23 +5,52 @@ use super::circuit::{
     embedded::EmbeddedCircuit,
 };
 use glam::Vec2;
+use std::ops::RangeInclusive;
+
+// Number of inputs supported by the basic gates
+pub const FAN_IN: RangeInclusive<u8> = 2..=16;
 
 #[derive(Clone, Debug)]
 pub enum Gate {
-    Button(bool),
+    Button,
     Const(bool),
-    And,
-    Or,
+    // Basic gates are parameterized by their number of inputs
+    And(u8),
+    Or(u8),
     Not,
     Buf,
-    Xor,
-    Nand,
-    Nor,
-    Xnor,
+    Xor(u8),
+    Nand(u8),
+    Nor(u8),
+    Xnor(u8),
     On,
     Off,
     Input(Option<String>),
     Output(Option<String>),
+    // Combines single bit inputs into a bus, with input 0 as the least significant bit
+    Merge(u8),
+    // Separates a bus into single bit outputs, with output 0 as the least significant bit
+    Split(u8),
+    // Set-dominant latch, inputs are S and R, outputs are Q and !Q
+    SrLatch,
+    // Transparent while E is high, inputs are D and E, outputs are Q and !Q
+    DLatch,
+    // Samples D on the rising edge of CLK, inputs are D and CLK, outputs are Q and !Q
+    DFlipFlop,
+    // Inputs are J, K and CLK, outputs are Q and !Q
+    JkFlipFlop,
+    // Loads D on the rising edge of CLK while EN is high, cleared while RST is high
+    // Inputs are D, CLK, EN and RST, output is Q
+    Register(u8),
+    // Drives its data input through while EN is high, and releases the net (Z) otherwise
+    // Inputs are D and EN
+    TriState,
+    // High for `high_ticks` out of every `period` solver steps, starting `phase` steps into the cycle
+    Clock {
+        period: u32,
+        high_ticks: u32,
+        phase: u32,
+    },
     Embedded(EmbeddedCircuit),
 }
 
@@ -64,9 +93,23 @@ impl Gate {
 
     pub fn input_count(&self) -> usize {
         match self {
-            Self::Const(_) | Self::Button(_) | Self::Off | Self::On | Self::Input(_) => 0,
-            Self::Not | Self::Buf | Self::Output(_) => 1,
-            Self::And | Self::Or | Self::Xor | Self::Nand | Self::Nor | Self::Xnor => 2,
+            Self::Const(_)
+            | Self::Button
+            | Self::Off
+            | Self::On
+            | Self::Input(_)
+            | Self::Clock { .. } => 0,
+            Self::Not | Self::Buf | Self::Output(_) | Self::Split(_) => 1,
+            Self::SrLatch | Self::DLatch | Self::DFlipFlop | Self::TriState => 2,
+            Self::JkFlipFlop => 3,
+            Self::Register(_) => 4,
+            Self::And(inputs)
+            | Self::Or(inputs)
+            | Self::Xor(inputs)
+            | Self::Nand(inputs)
+            | Self::Nor(inputs)
+            | Self::Xnor(inputs) => *inputs as usize,
+            Self::Merge(width) => *width as usize,
             Self::Embedded(embed) => embed.input_count(),
         }
     }
@@ -74,14 +117,102 @@ impl Gate {
     pub fn output_count(&self) -> usize {
         match self {
             Gate::Embedded(embed) => embed.output_count(),
+            Gate::Split(width) => *width as usize,
+            Gate::SrLatch | Gate::DLatch | Gate::DFlipFlop | Gate::JkFlipFlop => 2,
             _ => 1,
         }
     }
 
+    // Number of internal state words kept by the solver for this gate
+    pub fn state_count(&self) -> usize {
+        match self {
+            // The previous clock value, for edge detection
+            Gate::DFlipFlop | Gate::JkFlipFlop | Gate::Register(_) => 1,
+            // Whether it was pressed since the last step
+            Gate::Button => 1,
+            // Tick within the period, whether it's paused and the remaining ticks of a single pulse
+            Gate::Clock { .. } => 3,
+            _ => 0,
+        }
+    }
+
+    // Whether an output can drive an input of the same gate
+    // Other gates would feed back on themselves without any state to hold a value
+    pub fn allows_self_loop(&self) -> bool {
+        matches!(
+            self,
+            Gate::SrLatch
+                | Gate::DLatch
+                | Gate::DFlipFlop
+                | Gate::JkFlipFlop
+                | Gate::Register(_)
+                | Gate::Embedded(_)
+        )
+    }
+
+    // The bit width of an input, or None if it takes the width of the gate's connections
+    pub fn input_width(&self, InputIdx(index): InputIdx) -> Option<u8> {
+        match self {
+            Gate::Merge(_) => Some(1),
+            Gate::Split(width) => Some(*width),
+            Gate::Register(width) if index == 0 => Some(*width),
+            Gate::TriState if index == 1 => Some(1),
+            Gate::SrLatch
+            | Gate::DLatch
+            | Gate::DFlipFlop
+            | Gate::JkFlipFlop
+            | Gate::Register(_) => Some(1),
+            Gate::Embedded(embed) => Some(embed.input_widths()[index]),
+            _ => None,
+        }
+    }
+
+    // The bit width of an output, or None if it takes the width of the gate's connections
+    pub fn output_width(&self, OutputIdx(index): OutputIdx) -> Option<u8> {
+        match self {
+            Gate::Merge(width) | Gate::Register(width) => Some(*width),
+            Gate::Split(_)
+            | Gate::SrLatch
+            | Gate::DLatch
+            | Gate::DFlipFlop
+            | Gate::JkFlipFlop
+            | Gate::Clock { .. } => Some(1),
+            Gate::Embedded(embed) => Some(embed.output_widths()[index]),
+            _ => None,
+        }
+    }
+
+    // The label of an Input or Output gate
+    pub fn port_name(&self) -> Option<&str> {
+        match self {
+            Gate::Input(name) | Gate::Output(name) => name.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Mutable access to the input count of basic gates
+    pub fn fan_in_mut(&mut self) -> Option<&mut u8> {
+        match self {
+            Self::And(inputs)
+            | Self::Or(inputs)
+            | Self::Xor(inputs)
+            | Self::Nand(inputs)
+            | Self::Nor(inputs)
+            | Self::Xnor(inputs) => Some(inputs),
+            _ => None,
+        }
+    }
+
+    // Sprites are drawn for two pins a side, and stretched vertically to fit any more
+    pub fn sprite_scale(&self) -> Vec2 {
+        let pins = self.input_count().max(self.output_count()).max(2);
+        Vec2::new(1.0, (pins - 1) as f32)
+    }
+
     pub fn bounds(&self) -> Bounds {
         let size = 0.25;
         let offset = Vec2::splat(size);
-        Bounds::new(-offset, offset)
+        Bounds::new(-offset, offset).scale(self.sprite_scale())
     }
 }
 
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec2;
+
+use super::{
+    circuit::{
+        connection::{ConnectionError, ElementIdx, InputSpecifier, OutputSpecifier},
+        Circuit,
+    },
+    gate::Gate,
+};
+
+// Where the value of an input comes from, which for nets is only known once the netlist is read
+#[derive(Clone, Debug)]
+pub enum Source {
+    Pin(OutputSpecifier),
+    Net(String),
+    Bit(String, u8),
+}
+
+// Builds a circuit from named nets, which may be used before they're driven
+// Every net has at most one driver, and nets without one read low
+#[derive(Default)]
+pub struct Netlist {
+    circuit: Circuit,
+    drivers: HashMap<String, Source>,
+    // Widths of the outputs of added gates, which mostly don't declare their own
+    pin_widths: HashMap<OutputSpecifier, u8>,
+    // Inputs to connect once every net has a driver
+    pending: Vec<(Source, InputSpecifier)>,
+    // A Split gate for each bus which has its bits selected
+    splits: HashMap<OutputSpecifier, ElementIdx>,
+    inputs: usize,
+    outputs: Vec<String>,
+}
+
+impl Netlist {
+    // An Input gate driving the net of the same name, added after any others
+    pub fn input(&mut self, name: &str, width: u8) -> OutputSpecifier {
+        let gate = Gate::Input(Some(name.to_string()));
+        let position = Vec2::new(0.0, -(self.inputs as f32));
+        self.inputs += 1;
+        let pin = self.circuit.add_gate(gate, position).output(0);
+        self.pin_widths.insert(pin, width);
+        self.drivers.insert(name.to_string(), Source::Pin(pin));
+        pin
+    }
+
+    // An Output gate reading the net of the same name, added after any others
+    pub fn output(&mut self, name: &str) {
+        self.outputs.push(name.to_string());
+    }
+
+    // A gate whose outputs have the given widths
+    pub fn add(&mut self, gate: Gate, widths: &[u8]) -> ElementIdx {
+        let element = self.circuit.add_gate(gate, Vec2::ZERO);
+        for (output, width) in widths.iter().enumerate() {
+            self.pin_widths.insert(element.output(output), *width);
+        }
+        element
+    }
+
+    pub fn connect(&mut self, source: Source, input: InputSpecifier) {
+        self.pending.push((source, input));
+    }
+
+    pub fn is_driven(&self, net: &str) -> bool {
+        self.drivers.contains_key(net)
+    }
+
+    // Returns false without changing the driver if the net already has one
+    pub fn drive(&mut self, net: &str, source: Source) -> bool {
+        if self.is_driven(net) {
+            return false;
+        }
+        self.drivers.insert(net.to_string(), source);
+        true
+    }
+
+    // The single bit sources of a value, least significant first
+    pub fn bits(&mut self, source: Source, width: u8) -> Vec<Source> {
+        match source {
+            _ if width == 1 => vec![source],
+            Source::Net(net) => (0..width)
+                .map(|bit| Source::Bit(net.clone(), bit))
+                .collect(),
+            Source::Pin(pin) => {
+                let split = self.split(pin, width);
+                (0..width as usize)
+                    .map(|bit| Source::Pin(split.output(bit)))
+                    .collect()
+            }
+            Source::Bit(..) => unreachable!("bits are single bit"),
+        }
+    }
+
+    fn split(&mut self, pin: OutputSpecifier, width: u8) -> ElementIdx {
+        if let Some(split) = self.splits.get(&pin) {
+            return *split;
+        }
+        let split = self.add(Gate::Split(width), &vec![1; width as usize]);
+        self.circuit
+            .add_connection(pin.to(split.input(0)).with_width(width))
+            .unwrap();
+        self.splits.insert(pin, split);
+        split
+    }
+
+    // The output pin driving a source, if anything drives it
+    fn resolve(&mut self, source: &Source) -> Option<OutputSpecifier> {
+        let mut seen = HashSet::new();
+        let mut source = source.clone();
+        loop {
+            match source {
+                Source::Pin(pin) => return Some(pin),
+                Source::Net(net) => {
+                    // Nets driven by each other in a loop are never driven
+                    if !seen.insert(net.clone()) {
+                        return None;
+                    }
+                    source = self.drivers.get(&net)?.clone();
+                }
+                Source::Bit(net, bit) => {
+                    let bus = self.resolve(&Source::Net(net))?;
+                    let width = self.pin_widths[&bus];
+                    if width == 1 {
+                        return Some(bus);
+                    }
+                    return Some(self.split(bus, width).output(bit as usize));
+                }
+            }
+        }
+    }
+
+    // Connects every input to the net it reads and lays out the circuit
+    // Fails on a gate which reads its own output without any state in between
+    pub fn finish(mut self) -> Result<Circuit, ConnectionError> {
+        for (index, name) in std::mem::take(&mut self.outputs).into_iter().enumerate() {
+            let gate = Gate::Output(Some(name.clone()));
+            let output = self.circuit.add_gate(gate, Vec2::new(1.0, -(index as f32)));
+            self.connect(Source::Net(name), output.input(0));
+        }
+
+        for (source, input) in std::mem::take(&mut self.pending) {
+            if let Some(pin) = self.resolve(&source) {
+                let width = self.pin_widths[&pin];
+                self.circuit
+                    .add_connection(pin.to(input).with_width(width))?;
+            }
+        }
+
+        self.circuit.auto_layout();
+        Ok(self.circuit)
+    }
+}
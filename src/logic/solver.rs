@@ -1,106 +1,480 @@
+mod event;
+mod flattened;
+mod four_valued;
+mod timing;
+
+use event::EventState;
+use four_valued::FourValuedState;
+pub use four_valued::{Logic, LogicBits};
+use timing::TimingState;
+
+use std::collections::HashMap;
+
 use super::{
     circuit::{
-        connection::{ElementIdx, InputSpecifier, OutputSpecifier},
-        Circuit,
+        connection::{width_mask, ElementIdx, InputSpecifier, OutputIdx, OutputSpecifier},
+        Circuit, ElementId, FlatCircuit,
     },
     gate::Gate,
 };
 
-#[derive(Default, Clone, Debug)]
+// One value per pin, grouped by element
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct GateIOValues {
     pub inner: Vec<u64>,
+    // Index of each element's first pin in `inner`, followed by the total pin count
+    offsets: Vec<usize>,
 }
 
 impl GateIOValues {
-    fn new(size: usize) -> Self {
+    pub(super) fn new(pin_counts: impl IntoIterator<Item = usize>) -> Self {
+        let mut offsets = vec![0];
+        for count in pin_counts {
+            offsets.push(offsets.last().unwrap() + count);
+        }
+
         Self {
-            inner: vec![0; size],
+            inner: vec![0; *offsets.last().unwrap()],
+            offsets,
         }
     }
 
-    pub fn write_input(&mut self, InputSpecifier(elm, bit): InputSpecifier, value: bool) {
-        if value {
-            self.inner[elm.0] |= 1 << bit.0;
-        } else {
-            self.inner[elm.0] &= !(1 << bit.0);
-        }
+    pub fn element(&self, ElementIdx(elm): ElementIdx) -> &[u64] {
+        &self.inner[self.offsets[elm]..self.offsets[elm + 1]]
+    }
+
+    pub fn element_mut(&mut self, ElementIdx(elm): ElementIdx) -> &mut [u64] {
+        &mut self.inner[self.offsets[elm]..self.offsets[elm + 1]]
+    }
+
+    // Inputs with several drivers see them combined as a wired OR
+    pub fn drive_input(&mut self, input: InputSpecifier, value: u64) {
+        let index = self.input_index(input);
+        self.inner[index] |= value;
     }
 
-    pub fn read_output(&self, OutputSpecifier(elm, bit): OutputSpecifier) -> bool {
-        (self.inner[elm.0] >> bit.0) & 1 == 1
+    fn input_index(&self, InputSpecifier(elm, pin): InputSpecifier) -> usize {
+        self.offsets[elm.0] + pin.0
     }
+
+    pub fn read_output(&self, OutputSpecifier(elm, pin): OutputSpecifier) -> u64 {
+        self.inner[self.offsets[elm.0] + pin.0]
+    }
+
+    fn same_layout(&self, other: &Self) -> bool {
+        self.offsets == other.offsets
+    }
+
+    // Lays out values for new pin counts, where each element takes the values it had at its
+    // `previous` index if its pin count didn't change, and other pins start as `fill`
+    fn relayout(
+        &self,
+        pin_counts: impl IntoIterator<Item = usize>,
+        previous: &[Option<usize>],
+        fill: u64,
+    ) -> Self {
+        let mut values = Self::new(pin_counts);
+        values.inner.fill(fill);
+        for (element, previous) in previous.iter().enumerate() {
+            let Some(&[start, end]) = previous.and_then(|index| self.offsets.get(index..index + 2))
+            else {
+                continue;
+            };
+            let pins = values.element_mut(ElementIdx(element));
+            if pins.len() == end - start {
+                pins.copy_from_slice(&self.inner[start..end]);
+            }
+        }
+        values
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverMode {
+    // Evaluate every gate on every step
+    #[default]
+    Sweep,
+    // Only evaluate gates downstream of outputs which changed in the previous step
+    EventDriven,
+    // Evaluate every gate on every step, tracking unknown (X) and undriven (Z) bits
+    FourValued,
+    // Event driven, with each element's outputs changing after its rise or fall delay
+    // Delays are transport delays, so pulses shorter than a gate's delay still pass through it
+    Timing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettleError {
+    // A previously seen state recurred after `period` steps
+    Oscillating { period: usize },
+    // The step limit was reached without the state settling or repeating
+    StepLimit,
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct SolverState {
     pub output_results: GateIOValues,
+    pub mode: SolverMode,
+    input_values: GateIOValues,
+    // Internal state of sequential gates, which isn't visible on any pin
+    gate_state: GateIOValues,
+    valid: bool,
+    events: EventState,
+    four_valued: FourValuedState,
+    timing: TimingState,
+    // The state of each embedded circuit instance, which shares its definition with other instances
+    children: Vec<Option<Box<SolverState>>>,
+    // The index each element's values are laid out at, so they follow the element through edits
+    positions: HashMap<ElementId, usize>,
+    // Step each embedded instance with its own solver, rather than simulating the flattened circuit
+    pub hierarchical: bool,
+    flat: Option<Box<FlatCircuit>>,
+    flat_valid: bool,
 }
 
 impl SolverState {
-    pub fn step(mut self, circuit: &mut Circuit) -> Self {
-        self.set_size(circuit.elements.len());
+    pub fn step(mut self, circuit: &Circuit) -> Self {
+        self.prepare(circuit);
+
+        if !self.hierarchical && circuit.has_embedded() {
+            return self.step_flattened(circuit);
+        }
+        self.flat = None;
+
+        match self.mode {
+            SolverMode::Sweep => self.step_sweep(circuit),
+            SolverMode::EventDriven => self.step_event_driven(circuit),
+            SolverMode::FourValued => self.step_four_valued(circuit),
+            SolverMode::Timing => self.step_timing(circuit),
+        }
+    }
 
-        let mut gate_outputs = self.output_results;
-        let mut gate_inputs = GateIOValues::new(circuit.elements.len());
+    fn step_sweep(mut self, circuit: &Circuit) -> Self {
+        let gate_outputs = &mut self.output_results;
+        let gate_inputs = &mut self.input_values;
 
+        gate_inputs.inner.fill(0);
         for connection in &circuit.connections {
             let from = connection.from;
             let to = connection.to;
-            gate_inputs.write_input(to, gate_outputs.read_output(from));
+            gate_inputs.drive_input(to, gate_outputs.read_output(from));
         }
-        gate_outputs.inner.fill(0);
+
         for gate in 0..circuit.elements.len() {
-            Self::eval_gate(circuit, &mut gate_outputs, &gate_inputs, ElementIdx(gate));
+            let gate = ElementIdx(gate);
+            circuit[gate].gate.eval(
+                gate_inputs.element(gate),
+                self.gate_state.element_mut(gate),
+                self.children[gate.0].as_deref_mut(),
+                gate_outputs.element_mut(gate),
+            );
         }
 
-        self.output_results = gate_outputs;
-
         self
     }
 
-    #[inline(always)]
-    fn eval_gate(
-        circuit: &mut Circuit,
-        gate_outputs: &mut GateIOValues,
-        gate_inputs: &GateIOValues,
-        gate: ElementIdx,
-    ) {
-        let inputs = &gate_inputs.inner[gate.0];
-        let result = circuit[gate].gate.eval(inputs);
-        gate_outputs.inner[gate.0] = result;
+    // Lays out pin values for the current circuit structure
+    // Elements which are still in the circuit keep their values, wherever they've moved to
+    pub fn prepare(&mut self, circuit: &Circuit) {
+        if self.valid {
+            return;
+        }
+
+        let elements = &circuit.elements;
+        let ids: Vec<ElementId> = (0..elements.len())
+            .map(|index| circuit.element_id(ElementIdx(index)))
+            .collect();
+        let previous: Vec<Option<usize>> = ids
+            .iter()
+            .map(|id| self.positions.get(id).copied())
+            .collect();
+
+        self.output_results = self.output_results.relayout(
+            elements.iter().map(|elm| elm.gate.output_count()),
+            &previous,
+            0,
+        );
+        self.gate_state = self.gate_state.relayout(
+            elements.iter().map(|elm| elm.gate.state_count()),
+            &previous,
+            0,
+        );
+
+        let mut children = std::mem::take(&mut self.children);
+        self.children = elements
+            .iter()
+            .zip(&previous)
+            .map(|(element, previous)| {
+                let Gate::Embedded(_) = element.gate else {
+                    return None;
+                };
+                let mut child = previous
+                    .and_then(|index| children.get_mut(index)?.take())
+                    .unwrap_or_default();
+                // Its definition may have changed
+                child.invalidate();
+                child.hierarchical = self.hierarchical;
+                Some(child)
+            })
+            .collect();
+
+        self.input_values = GateIOValues::new(elements.iter().map(|elm| elm.gate.input_count()));
+        self.four_valued.prepare(elements, &previous);
+        self.timing.relayout(&self.output_results, &previous);
+        self.positions = ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index))
+            .collect();
+        self.valid = true;
     }
 
-    pub fn set_size(&mut self, size: usize) {
-        if self.output_results.inner.len() == size {
-            return;
+    // Moves values to a new flattened circuit, following each element by the path of IDs leading
+    // to it, since the elements of a flattened circuit are new each time it's built
+    pub(super) fn follow_flattened(&mut self, previous: &FlatCircuit, flat: &FlatCircuit) {
+        let old: HashMap<_, _> = (0..previous.circuit.elements.len())
+            .map(|index| (previous.id_path(ElementIdx(index)), index))
+            .collect();
+        self.positions = (0..flat.circuit.elements.len())
+            .filter_map(|index| {
+                let element = ElementIdx(index);
+                let position = *old.get(flat.id_path(element))?;
+                Some((flat.circuit.element_id(element), position))
+            })
+            .collect();
+        self.invalidate();
+    }
+
+    // Must be called whenever the structure of the circuit changes
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+        self.flat_valid = false;
+        self.events.invalidate();
+    }
+
+    pub fn gate_state(&self) -> &GateIOValues {
+        &self.gate_state
+    }
+
+    pub fn gate_state_mut(&mut self) -> &mut GateIOValues {
+        &mut self.gate_state
+    }
+
+    // An element's internal state, found by its ID since the circuit may have changed since the
+    // state was laid out
+    pub fn element_state(&self, id: ElementId) -> Option<&[u64]> {
+        let index = *self.positions.get(&id)?;
+        self.gate_state.offsets.get(index + 1)?;
+        Some(self.gate_state.element(ElementIdx(index)))
+    }
+
+    // Every value which can affect future steps, including those of embedded instances
+    pub fn snapshot(&self) -> Vec<u64> {
+        let mut state = self.output_results.inner.clone();
+        state.extend(&self.gate_state.inner);
+        state.extend(&self.four_valued.output_unknown.inner);
+        state.extend(self.timing.snapshot());
+        for child in self.children.iter().flatten() {
+            state.extend(child.snapshot());
+        }
+        if let Some(flat) = &self.flat {
+            state.extend(flat.circuit.solver.snapshot());
         }
-        self.output_results = GateIOValues::new(size);
+        state
+    }
+
+    // The value of an output pin of an element within embedded instances, by the path of instances
+    // leading to it, whether the circuit is simulated flattened or hierarchically
+    pub fn nested_output(&self, path: &[ElementIdx], pin: OutputIdx) -> Option<LogicBits> {
+        let (&element, rest) = path.split_first()?;
+        if rest.is_empty() {
+            self.output_results.offsets.get(element.0 + 1)?;
+            self.output_results.element(element).get(pin.0)?;
+            return Some(self.read_logic(element.output(pin.0)));
+        }
+
+        match &self.flat {
+            Some(flat) => {
+                let solver = &flat.circuit.solver;
+                if let Some(flat_element) = flat.element(path) {
+                    return solver.nested_output(&[flat_element], pin);
+                }
+                let sources = flat.nested_sources(path, pin)?;
+                Some(sources.iter().fold(LogicBits::default(), |bits, &source| {
+                    let source = solver.read_logic(source);
+                    LogicBits {
+                        value: bits.value | source.value,
+                        unknown: bits.unknown | source.unknown,
+                    }
+                }))
+            }
+            None => self
+                .children
+                .get(element.0)?
+                .as_deref()?
+                .nested_output(rest, pin),
+        }
+    }
+
+    // Both planes of an output, where nothing is unknown outside of four-valued mode
+    pub fn read_logic(&self, output: OutputSpecifier) -> LogicBits {
+        let unknown = match self.mode {
+            SolverMode::FourValued => self.four_valued.output_unknown.read_output(output),
+            _ => 0,
+        };
+        LogicBits {
+            value: self.output_results.read_output(output),
+            unknown,
+        }
+    }
+
+    // Overwrites an output from outside of the solver
+    pub fn drive_output(&mut self, output: OutputSpecifier, value: u64) {
+        let OutputSpecifier(element, pin) = output;
+        self.output_results.element_mut(element)[pin.0] = value;
+        self.events.mark_changed(element);
     }
 }
 
+// Stores the new clock value, returning the bits which went from low to high
+fn rising_edge(previous: &mut u64, clock: u64) -> u64 {
+    let rising = clock & !*previous;
+    *previous = clock;
+    rising
+}
+
 impl Gate {
+    // Stateful gates can change their output without their inputs changing
+    pub fn is_stateful(&self) -> bool {
+        matches!(self, Gate::Button | Gate::Clock { .. } | Gate::Embedded(_))
+    }
+
+    // Gates operate on whole words, so each bit of a bus is handled independently
+    // Unused high bits may hold garbage, readers must mask values to the width of the pin
+    // Outputs hold the gate's previous values, which sequential gates use as their stored state
+    // Embedded gates are given the state of their instance as `child`
     #[inline(always)]
-    pub fn eval(&mut self, inputs: &u64) -> u64 {
+    pub fn eval(
+        &self,
+        inputs: &[u64],
+        state: &mut [u64],
+        child: Option<&mut SolverState>,
+        outputs: &mut [u64],
+    ) {
+        fn all(inputs: &[u64]) -> u64 {
+            inputs.iter().fold(!0, |acc, input| acc & input)
+        }
+
+        fn any(inputs: &[u64]) -> u64 {
+            inputs.iter().fold(0, |acc, input| acc | input)
+        }
+
+        fn parity(inputs: &[u64]) -> u64 {
+            inputs.iter().fold(0, |acc, input| acc ^ input)
+        }
+
+        fn broadcast(value: bool) -> u64 {
+            if value {
+                !0
+            } else {
+                0
+            }
+        }
+
         match self {
-            Gate::Embedded(embed) => embed.eval(*inputs),
-            Gate::Const(v) => *v as u64,
-            Gate::Button(v) => {
-                let res = *v as u64;
-                *v = false;
-                res
+            Gate::Embedded(embed) => embed.eval(child.expect("embedded state"), inputs, outputs),
+            Gate::Const(v) => outputs[0] = broadcast(*v),
+            // Presses last for a single step
+            Gate::Button => outputs[0] = std::mem::take(&mut state[0]),
+            Gate::And(_) => outputs[0] = all(inputs),
+            Gate::Or(_) => outputs[0] = any(inputs),
+            Gate::Not => outputs[0] = !inputs[0],
+            Gate::Buf => outputs[0] = inputs[0],
+            Gate::Xor(_) => outputs[0] = parity(inputs),
+            Gate::Nand(_) => outputs[0] = !all(inputs),
+            Gate::Nor(_) => outputs[0] = !any(inputs),
+            Gate::Xnor(_) => outputs[0] = !parity(inputs),
+            Gate::On => outputs[0] = !0,
+            Gate::Off => outputs[0] = 0,
+            // Inputs are driven from outside the circuit, so they keep their value
+            Gate::Input(_) => {}
+            Gate::Output(_) => outputs[0] = inputs[0],
+            Gate::Merge(width) => {
+                outputs[0] = inputs[..*width as usize]
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (bit, input)| acc | ((input & 1) << bit))
+            }
+            Gate::Split(width) => {
+                for (bit, output) in outputs[..*width as usize].iter_mut().enumerate() {
+                    *output = (inputs[0] >> bit) & 1;
+                }
+            }
+            Gate::SrLatch => {
+                let [set, reset] = inputs else { unreachable!() };
+                let q = set | (outputs[0] & !reset);
+                outputs[0] = q;
+                outputs[1] = !q;
+            }
+            Gate::DLatch => {
+                let [data, enable] = inputs else {
+                    unreachable!()
+                };
+                let q = (data & enable) | (outputs[0] & !enable);
+                outputs[0] = q;
+                outputs[1] = !q;
+            }
+            Gate::DFlipFlop => {
+                let [data, clock] = inputs else {
+                    unreachable!()
+                };
+                let rising = rising_edge(&mut state[0], *clock);
+                let q = (data & rising) | (outputs[0] & !rising);
+                outputs[0] = q;
+                outputs[1] = !q;
+            }
+            Gate::JkFlipFlop => {
+                let [j, k, clock] = inputs else {
+                    unreachable!()
+                };
+                let rising = rising_edge(&mut state[0], *clock);
+                let q = outputs[0];
+                let next = (j & !q) | (!k & q);
+                let q = (next & rising) | (q & !rising);
+                outputs[0] = q;
+                outputs[1] = !q;
+            }
+            Gate::Register(width) => {
+                let [data, clock, enable, reset] = inputs else {
+                    unreachable!()
+                };
+                let rising = rising_edge(&mut state[0], *clock);
+                if reset & 1 == 1 {
+                    outputs[0] = 0;
+                } else if rising & enable & 1 == 1 {
+                    outputs[0] = data & width_mask(*width);
+                }
+            }
+            // Disabled outputs read low, so tri-state buffers sharing a net combine as a wired OR
+            Gate::TriState => outputs[0] = inputs[0] & broadcast(inputs[1] & 1 == 1),
+            Gate::Clock {
+                period,
+                high_ticks,
+                phase,
+            } => {
+                let [tick, paused, pulse] = state else {
+                    unreachable!()
+                };
+                if *paused == 0 {
+                    let period = (*period).max(1) as u64;
+                    outputs[0] = broadcast((*tick + *phase as u64) % period < *high_ticks as u64);
+                    *tick = (*tick + 1) % period;
+                } else {
+                    // A paused clock stays low, apart from single pulses
+                    outputs[0] = broadcast(*pulse > 0);
+                    *pulse = pulse.saturating_sub(1);
+                }
             }
-            Gate::And => 1 & (inputs & (inputs >> 1)),
-            Gate::Or => 1 & (inputs | (inputs >> 1)),
-            Gate::Not => 1 & (!inputs),
-            Gate::Buf => *inputs,
-            Gate::Xor => 1 & (inputs ^ (inputs >> 1)),
-            Gate::Nand => !(1 & (inputs & (inputs >> 1))),
-            Gate::Nor => !(1 & (inputs | (inputs >> 1))),
-            Gate::Xnor => 1 & (!(inputs ^ (inputs >> 1))),
-            Gate::On => 1,
-            Gate::Off => 0,
-            Gate::Input(_) => *inputs,
-            Gate::Output(_) => 0,
         }
     }
 }
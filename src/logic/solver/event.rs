@@ -0,0 +1,168 @@
+use crate::logic::circuit::{connection::ElementIdx, Circuit};
+
+use super::{GateIOValues, SolverState};
+
+// Bookkeeping for the event driven solver
+// Only gates whose inputs may have changed since the last step are evaluated
+#[derive(Default, Clone, Debug)]
+pub struct EventState {
+    valid: bool,
+    // Connection indices feeding each element, in connection order
+    fan_in: Vec<Vec<usize>>,
+    // Elements fed by each element's outputs
+    fan_out: Vec<Vec<usize>>,
+    // Elements which must be evaluated every step, regardless of their inputs
+    always_dirty: Vec<usize>,
+    // Elements whose output changed during the last step
+    changed: Vec<usize>,
+    dirty: Vec<bool>,
+    worklist: Vec<usize>,
+    scratch: Vec<u64>,
+}
+
+impl EventState {
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    // Whether the next step will rebuild the bookkeeping and evaluate every gate
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn mark_changed(&mut self, element: ElementIdx) {
+        self.changed.push(element.0);
+    }
+
+    fn rebuild(&mut self, circuit: &Circuit) {
+        let size = circuit.elements.len();
+
+        self.fan_in = vec![vec![]; size];
+        self.fan_out = vec![vec![]; size];
+        for (index, connection) in circuit.connections.iter().enumerate() {
+            let from = connection.from.0 .0;
+            let to = connection.to.0 .0;
+            self.fan_in[to].push(index);
+            if !self.fan_out[from].contains(&to) {
+                self.fan_out[from].push(to);
+            }
+        }
+
+        self.always_dirty = circuit
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| element.gate.is_stateful())
+            .map(|(index, _)| index)
+            .collect();
+
+        self.dirty = vec![false; size];
+        self.changed.clear();
+        self.worklist.clear();
+        self.valid = true;
+    }
+
+    fn mark_dirty(&mut self, element: usize) {
+        if !self.dirty[element] {
+            self.dirty[element] = true;
+            self.worklist.push(element);
+        }
+    }
+
+    // Rebuilds an element's inputs from scratch, returning whether they changed
+    fn gather_inputs(
+        &mut self,
+        circuit: &Circuit,
+        outputs: &GateIOValues,
+        inputs: &mut GateIOValues,
+        element: ElementIdx,
+    ) -> bool {
+        self.scratch.clear();
+        self.scratch.extend_from_slice(inputs.element(element));
+
+        inputs.element_mut(element).fill(0);
+        for &connection in &self.fan_in[element.0] {
+            let connection = circuit.connections[connection];
+            inputs.drive_input(connection.to, outputs.read_output(connection.from));
+        }
+
+        inputs.element(element) != self.scratch.as_slice()
+    }
+}
+
+impl EventState {
+    // Gathers the inputs of every gate which may need evaluating this step, and returns those which do
+    pub(super) fn take_worklist(
+        &mut self,
+        circuit: &Circuit,
+        outputs: &GateIOValues,
+        inputs: &mut GateIOValues,
+    ) -> Vec<usize> {
+        let size = circuit.elements.len();
+
+        let full = !self.valid;
+        if full {
+            self.rebuild(circuit);
+            // Without knowing what changed, every gate has to be evaluated once
+            self.worklist.extend(0..size);
+        } else {
+            for changed in std::mem::take(&mut self.changed) {
+                for index in 0..self.fan_out[changed].len() {
+                    self.mark_dirty(self.fan_out[changed][index]);
+                }
+            }
+
+            for index in 0..self.always_dirty.len() {
+                self.mark_dirty(self.always_dirty[index]);
+            }
+        }
+        self.changed.clear();
+
+        // All inputs must be read from the previous step's outputs before any are overwritten
+        let mut worklist = std::mem::take(&mut self.worklist);
+        worklist.retain(|&element| {
+            self.dirty[element] = false;
+            let changed = self.gather_inputs(circuit, outputs, inputs, ElementIdx(element));
+            full || changed || circuit.elements[element].gate.is_stateful()
+        });
+        worklist
+    }
+
+    // Hands the worklist's allocation back for the next step
+    pub(super) fn return_worklist(&mut self, mut worklist: Vec<usize>) {
+        worklist.clear();
+        self.worklist = worklist;
+    }
+}
+
+impl SolverState {
+    pub(super) fn step_event_driven(mut self, circuit: &Circuit) -> Self {
+        let worklist =
+            self.events
+                .take_worklist(circuit, &self.output_results, &mut self.input_values);
+
+        let events = &mut self.events;
+        for &element in &worklist {
+            let element = ElementIdx(element);
+            let outputs = self.output_results.element_mut(element);
+
+            events.scratch.clear();
+            events.scratch.extend_from_slice(outputs);
+
+            circuit.elements[element.0].gate.eval(
+                self.input_values.element(element),
+                self.gate_state.element_mut(element),
+                self.children[element.0].as_deref_mut(),
+                outputs,
+            );
+
+            if outputs != events.scratch.as_slice() {
+                events.changed.push(element.0);
+            }
+        }
+
+        events.return_worklist(worklist);
+
+        self
+    }
+}
@@ -0,0 +1,74 @@
+use crate::logic::circuit::{connection::ElementIdx, Circuit};
+
+use super::SolverState;
+
+impl SolverState {
+    // Simulates the flattened form of a circuit, keeping the values of its top level elements in sync
+    pub(super) fn step_flattened(mut self, circuit: &Circuit) -> Self {
+        let mut flat = self.flat.take().unwrap_or_default();
+        if !self.flat_valid {
+            // Elements of the flattened circuit keep their state if they're still in it
+            let mut solver = std::mem::take(&mut flat.circuit.solver);
+            let previous = std::mem::replace(&mut *flat, circuit.flatten());
+            solver.follow_flattened(&previous, &flat);
+            flat.circuit.solver = solver;
+            flat.circuit.set_solver_mode(self.mode);
+            self.flat_valid = true;
+        }
+        flat.circuit.right_size_solver();
+
+        // Top level values may have been changed from outside of the solver
+        for index in 0..circuit.elements.len() {
+            let element = ElementIdx(index);
+            let Some(flat_element) = flat.element(&[element]) else {
+                continue;
+            };
+            let flat_solver = &mut flat.circuit.solver;
+
+            for (pin, &value) in self.output_results.element(element).iter().enumerate() {
+                let output = flat_element.output(pin);
+                if flat_solver.output_results.read_output(output) != value {
+                    flat_solver.drive_output(output, value);
+                }
+            }
+            flat_solver
+                .gate_state
+                .element_mut(flat_element)
+                .copy_from_slice(self.gate_state.element(element));
+            flat_solver
+                .four_valued
+                .output_unknown
+                .element_mut(flat_element)
+                .copy_from_slice(self.four_valued.output_unknown.element(element));
+        }
+
+        flat.circuit.step();
+
+        let flat_solver = &flat.circuit.solver;
+        for index in 0..circuit.elements.len() {
+            let element = ElementIdx(index);
+            for pin in 0..circuit.elements[index].gate.output_count() {
+                let (value, unknown) = flat.sources(element.output(pin)).iter().fold(
+                    (0, 0),
+                    |(value, unknown), &source| {
+                        (
+                            value | flat_solver.output_results.read_output(source),
+                            unknown | flat_solver.four_valued.output_unknown.read_output(source),
+                        )
+                    },
+                );
+                self.output_results.element_mut(element)[pin] = value;
+                self.four_valued.output_unknown.element_mut(element)[pin] = unknown;
+            }
+
+            if let Some(flat_element) = flat.element(&[element]) {
+                self.gate_state
+                    .element_mut(element)
+                    .copy_from_slice(flat_solver.gate_state.element(flat_element));
+            }
+        }
+
+        self.flat = Some(flat);
+        self
+    }
+}
@@ -0,0 +1,289 @@
+use crate::logic::{
+    circuit::{
+        connection::{width_mask, ElementIdx},
+        element::CircuitElement,
+        Circuit,
+    },
+    gate::Gate,
+};
+
+use super::{GateIOValues, SolverState};
+
+// The value of a single bit in four-valued mode
+// Each bit is stored as a value bit and an unknown bit:
+// 0 and 1 are known, X is unknown with a clear value bit and Z is unknown with a set value bit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Logic {
+    Zero,
+    One,
+    // Unknown, from uninitialised state or conflicting drivers
+    X,
+    // High impedance, nothing is driving the net
+    Z,
+}
+
+impl Logic {
+    pub fn from_bits(value: u64, unknown: u64) -> Self {
+        match (value & 1 == 1, unknown & 1 == 1) {
+            (false, false) => Logic::Zero,
+            (true, false) => Logic::One,
+            (false, true) => Logic::X,
+            (true, true) => Logic::Z,
+        }
+    }
+
+    // As written in VCD files
+    pub fn symbol(self) -> char {
+        match self {
+            Logic::Zero => '0',
+            Logic::One => '1',
+            Logic::X => 'x',
+            Logic::Z => 'z',
+        }
+    }
+}
+
+// Both planes of a pin, so that X and Z bits can't be read as levels by mistake
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogicBits {
+    pub value: u64,
+    pub unknown: u64,
+}
+
+impl LogicBits {
+    // The value where bits which are X or Z read as low
+    pub fn known(self) -> u64 {
+        self.value & !self.unknown
+    }
+
+    pub fn bit(self, bit: u8) -> Logic {
+        Logic::from_bits(self.value >> bit, self.unknown >> bit)
+    }
+
+    // One symbol per bit, most significant first
+    pub fn symbols(self, width: u8) -> String {
+        (0..width).rev().map(|bit| self.bit(bit).symbol()).collect()
+    }
+
+    pub fn masked(self, width: u8) -> Self {
+        Self {
+            value: self.value & width_mask(width),
+            unknown: self.unknown & width_mask(width),
+        }
+    }
+}
+
+impl From<u64> for LogicBits {
+    fn from(value: u64) -> Self {
+        Self { value, unknown: 0 }
+    }
+}
+
+// The unknown planes of the four-valued solver, the value planes are shared with the other modes
+#[derive(Default, Clone, Debug)]
+pub struct FourValuedState {
+    pub output_unknown: GateIOValues,
+    input_unknown: GateIOValues,
+    // Per input pin, the bits which are driven by anything, driven high, driven low or driven X
+    driven: Vec<u64>,
+    ones: Vec<u64>,
+    zeros: Vec<u64>,
+    conflicts: Vec<u64>,
+}
+
+impl FourValuedState {
+    // New elements start uninitialised
+    pub fn prepare(&mut self, elements: &[CircuitElement], previous: &[Option<usize>]) {
+        self.output_unknown = self.output_unknown.relayout(
+            elements.iter().map(|elm| elm.gate.output_count()),
+            previous,
+            !0,
+        );
+
+        self.input_unknown = GateIOValues::new(elements.iter().map(|elm| elm.gate.input_count()));
+        let pins = self.input_unknown.inner.len();
+        for plane in [
+            &mut self.driven,
+            &mut self.ones,
+            &mut self.zeros,
+            &mut self.conflicts,
+        ] {
+            *plane = vec![0; pins];
+        }
+    }
+
+    // Combines every driver of each input, undriven inputs read Z and disagreeing drivers read X
+    fn resolve_inputs(
+        &mut self,
+        circuit: &Circuit,
+        outputs: &GateIOValues,
+        inputs: &mut GateIOValues,
+    ) {
+        for plane in [
+            &mut self.driven,
+            &mut self.ones,
+            &mut self.zeros,
+            &mut self.conflicts,
+        ] {
+            plane.fill(0);
+        }
+
+        for connection in &circuit.connections {
+            let value = outputs.read_output(connection.from);
+            let unknown = self.output_unknown.read_output(connection.from);
+            let pin = inputs.input_index(connection.to);
+
+            // High impedance drivers don't take part
+            let driving = connection.mask() & !(value & unknown);
+            self.driven[pin] |= driving;
+            self.ones[pin] |= driving & value & !unknown;
+            self.zeros[pin] |= driving & !value & !unknown;
+            self.conflicts[pin] |= driving & unknown;
+        }
+
+        for pin in 0..inputs.inner.len() {
+            let z = !self.driven[pin];
+            let x = self.conflicts[pin] | (self.ones[pin] & self.zeros[pin]);
+            inputs.inner[pin] = z | (self.ones[pin] & !x);
+            self.input_unknown.inner[pin] = z | x;
+        }
+    }
+}
+
+impl SolverState {
+    pub(super) fn step_four_valued(mut self, circuit: &Circuit) -> Self {
+        let four_valued = &mut self.four_valued;
+        four_valued.resolve_inputs(circuit, &self.output_results, &mut self.input_values);
+
+        for (index, element) in circuit.elements.iter().enumerate() {
+            let index = ElementIdx(index);
+            element.gate.eval_four_valued(
+                self.input_values.element(index),
+                four_valued.input_unknown.element(index),
+                self.gate_state.element_mut(index),
+                self.children[index.0].as_deref_mut(),
+                self.output_results.element_mut(index),
+                four_valued.output_unknown.element_mut(index),
+            );
+        }
+
+        self
+    }
+}
+
+impl Gate {
+    // Evaluates both planes, unknown outputs read X unless the gate releases them to Z
+    pub fn eval_four_valued(
+        &self,
+        inputs: &[u64],
+        inputs_unknown: &[u64],
+        state: &mut [u64],
+        child: Option<&mut SolverState>,
+        outputs: &mut [u64],
+        outputs_unknown: &mut [u64],
+    ) {
+        // Sequential gates need their state from before it's updated
+        let high_z = self.eval_unknown(inputs, inputs_unknown, state, outputs_unknown);
+        // Wherever the result is known, two-valued evaluation gives the right value
+        self.eval(inputs, state, child, outputs);
+
+        for (output, unknown) in outputs.iter_mut().zip(outputs_unknown.iter()) {
+            *output &= !unknown;
+        }
+        if let Some(output) = outputs.first_mut() {
+            *output |= high_z;
+        }
+    }
+
+    // Updates which output bits are unknown, returning the bits of output 0 which are Z
+    fn eval_unknown(
+        &self,
+        inputs: &[u64],
+        unknown: &[u64],
+        state: &[u64],
+        outputs_unknown: &mut [u64],
+    ) -> u64 {
+        let known_high = |pin: usize| inputs[pin] & !unknown[pin];
+        let known_low = |pin: usize| !inputs[pin] & !unknown[pin];
+        let any_known_high = || (0..inputs.len()).fold(0, |acc, pin| acc | known_high(pin));
+        let any_known_low = || (0..inputs.len()).fold(0, |acc, pin| acc | known_low(pin));
+        let all_known_high = || (0..inputs.len()).fold(!0, |acc, pin| acc & known_high(pin));
+        let all_known_low = || (0..inputs.len()).fold(!0, |acc, pin| acc & known_low(pin));
+        let any_unknown = || unknown.iter().fold(0, |acc, unknown| acc | unknown);
+        let broadcast_bit = |value: u64| 0u64.wrapping_sub(value & 1);
+        let previous = outputs_unknown.first().copied().unwrap_or(0);
+
+        let mut high_z = 0;
+        let result = match self {
+            Gate::Button
+            | Gate::Const(_)
+            | Gate::On
+            | Gate::Off
+            | Gate::Input(_)
+            | Gate::Clock { .. } => 0,
+            // A known low input decides an AND, a known high input decides an OR
+            Gate::And(_) | Gate::Nand(_) => !(any_known_low() | all_known_high()),
+            Gate::Or(_) | Gate::Nor(_) => !(any_known_high() | all_known_low()),
+            Gate::Xor(_) | Gate::Xnor(_) => any_unknown(),
+            Gate::Not | Gate::Buf | Gate::Output(_) => unknown[0],
+            Gate::TriState => {
+                let enabled = broadcast_bit(known_high(1));
+                let disabled = broadcast_bit(known_low(1));
+                high_z = disabled;
+                broadcast_bit(unknown[1]) | (enabled & unknown[0]) | disabled
+            }
+            Gate::Merge(width) => unknown[..*width as usize]
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (bit, unknown)| acc | ((unknown & 1) << bit)),
+            Gate::Split(_) => {
+                for (bit, output) in outputs_unknown.iter_mut().enumerate() {
+                    *output = (unknown[0] >> bit) & 1;
+                }
+                return 0;
+            }
+            Gate::SrLatch => {
+                let set = known_high(0);
+                let reset = known_low(0) & known_high(1);
+                let hold = known_low(0) & known_low(1) & !previous;
+                !(set | reset | hold)
+            }
+            Gate::DLatch => unknown[1] | (known_high(1) & unknown[0]) | (known_low(1) & previous),
+            Gate::DFlipFlop => {
+                let rising = inputs[1] & !state[0];
+                unknown[1] | (rising & unknown[0]) | (!rising & previous)
+            }
+            Gate::JkFlipFlop => {
+                let rising = inputs[2] & !state[0];
+                unknown[2] | (rising & (unknown[0] | unknown[1] | previous)) | (!rising & previous)
+            }
+            Gate::Register(_) => {
+                let rising = inputs[1] & !state[0];
+                if unknown[3] & 1 == 1 {
+                    !0
+                } else if inputs[3] & 1 == 1 {
+                    0
+                } else if (unknown[1] | unknown[2]) & 1 == 1 {
+                    !0
+                } else if rising & inputs[2] & 1 == 1 {
+                    unknown[0]
+                } else {
+                    previous
+                }
+            }
+            // Embedded circuits only simulate known values, so any unknown input makes every output X
+            Gate::Embedded(embed) => {
+                let any_unknown = unknown
+                    .iter()
+                    .zip(embed.input_widths())
+                    .any(|(unknown, width)| unknown & width_mask(*width) != 0);
+                outputs_unknown.fill(if any_unknown { !0 } else { 0 });
+                return 0;
+            }
+        };
+
+        // Every output of a gate shares the same unknown bits
+        outputs_unknown.fill(result);
+        high_z
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+
+use crate::logic::circuit::{connection::ElementIdx, Circuit};
+
+use super::{GateIOValues, SolverState};
+
+// A scheduled change to some of the bits of an output pin
+#[derive(Clone, Copy, Debug)]
+struct Transaction {
+    time: u64,
+    mask: u64,
+    value: u64,
+}
+
+// Bookkeeping for the timing solver
+// Gates are evaluated like the event driven solver, but their results reach the wires after a delay
+#[derive(Default, Clone, Debug)]
+pub struct TimingState {
+    now: u64,
+    // Each gate's latest outputs, which the wires catch up with once pending transactions apply
+    computed: GateIOValues,
+    // Future changes of each output pin, in time order
+    pending: Vec<VecDeque<Transaction>>,
+    // Output pins with transactions due at each time, indexed by time modulo the wheel length
+    wheel: Vec<Vec<usize>>,
+    // Element owning each output pin
+    pin_elements: Vec<usize>,
+    scratch: Vec<u64>,
+}
+
+impl TimingState {
+    // Moves the computed outputs and pending transactions of each element to where it's laid out
+    // now, where new elements start from their current outputs
+    pub(super) fn relayout(&mut self, outputs: &GateIOValues, previous: &[Option<usize>]) {
+        if self.computed.offsets.is_empty() {
+            return;
+        }
+
+        let mut computed = outputs.clone();
+        let mut pending = vec![VecDeque::new(); outputs.inner.len()];
+        for (element, previous) in previous.iter().enumerate() {
+            let Some(&[start, end]) =
+                previous.and_then(|index| self.computed.offsets.get(index..index + 2))
+            else {
+                continue;
+            };
+            let first_pin = computed.offsets[element];
+            if computed.offsets[element + 1] - first_pin != end - start {
+                continue;
+            }
+            computed.inner[first_pin..first_pin + end - start]
+                .copy_from_slice(&self.computed.inner[start..end]);
+            for pin in 0..end - start {
+                if let Some(queue) = self.pending.get_mut(start + pin) {
+                    pending[first_pin + pin] = std::mem::take(queue);
+                }
+            }
+        }
+        self.computed = computed;
+        self.pending = pending;
+    }
+
+    // Pending transactions are kept if the layout didn't change
+    fn rebuild(&mut self, circuit: &Circuit, outputs: &GateIOValues) {
+        if !self.computed.same_layout(outputs) {
+            self.computed = outputs.clone();
+            self.pending = vec![VecDeque::new(); outputs.inner.len()];
+        }
+
+        // Every delay must land in a different slot than the current time
+        let max_delay = circuit
+            .elements
+            .iter()
+            .map(|element| element.delay.max())
+            .max()
+            .unwrap_or(1);
+        self.wheel = vec![vec![]; max_delay as usize + 1];
+        for (pin, queue) in self.pending.iter_mut().enumerate() {
+            // Delays may have shrunk, so nothing can be left further out than the wheel reaches
+            for transaction in queue.iter_mut() {
+                transaction.time = transaction.time.min(self.now + max_delay as u64);
+            }
+
+            for transaction in queue.iter() {
+                let slot = transaction.time as usize % self.wheel.len();
+                self.wheel[slot].push(pin);
+            }
+        }
+
+        self.pin_elements = outputs
+            .offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(|(element, range)| std::iter::repeat_n(element, range[1] - range[0]))
+            .collect();
+    }
+
+    fn schedule(&mut self, pin: usize, time: u64, mask: u64, value: u64) {
+        if mask == 0 {
+            return;
+        }
+
+        // A transaction replaces any scheduled for the same bits at or after its time
+        let queue = &mut self.pending[pin];
+        for transaction in queue.iter_mut() {
+            if transaction.time >= time {
+                transaction.mask &= !mask;
+            }
+        }
+        queue.retain(|transaction| transaction.mask != 0);
+
+        let index = queue.partition_point(|transaction| transaction.time <= time);
+        queue.insert(
+            index,
+            Transaction {
+                time,
+                mask,
+                value: value & mask,
+            },
+        );
+
+        let slot = time as usize % self.wheel.len();
+        self.wheel[slot].push(pin);
+    }
+
+    // Everything which affects future steps, relative to the current time
+    pub fn snapshot(&self) -> impl Iterator<Item = u64> + '_ {
+        let pending = self
+            .pending
+            .iter()
+            .enumerate()
+            .flat_map(move |(pin, queue)| {
+                queue.iter().flat_map(move |transaction| {
+                    [
+                        pin as u64,
+                        transaction.time - self.now,
+                        transaction.mask,
+                        transaction.value,
+                    ]
+                })
+            });
+
+        self.computed.inner.iter().copied().chain(pending)
+    }
+}
+
+impl SolverState {
+    pub(super) fn step_timing(mut self, circuit: &Circuit) -> Self {
+        if !self.events.is_valid() {
+            self.timing.rebuild(circuit, &self.output_results);
+        }
+
+        let worklist =
+            self.events
+                .take_worklist(circuit, &self.output_results, &mut self.input_values);
+
+        let timing = &mut self.timing;
+        for &element in &worklist {
+            let element = ElementIdx(element);
+            let outputs = timing.computed.element_mut(element);
+
+            timing.scratch.clear();
+            timing.scratch.extend_from_slice(outputs);
+
+            circuit.elements[element.0].gate.eval(
+                self.input_values.element(element),
+                self.gate_state.element_mut(element),
+                self.children[element.0].as_deref_mut(),
+                outputs,
+            );
+
+            let delay = circuit.elements[element.0].delay;
+            let first_pin = timing.computed.offsets[element.0];
+            for pin in 0..timing.scratch.len() {
+                let value = timing.computed.inner[first_pin + pin];
+                let changed = value ^ timing.scratch[pin];
+                let (rise, fall) = (delay.rise as u64, delay.fall as u64);
+                timing.schedule(first_pin + pin, timing.now + rise, changed & value, value);
+                timing.schedule(first_pin + pin, timing.now + fall, changed & !value, value);
+            }
+        }
+        self.events.return_worklist(worklist);
+
+        // Apply everything due at the next time, so a delay of one matches the other modes
+        timing.now += 1;
+        let slot = timing.now as usize % timing.wheel.len();
+        for pin in std::mem::take(&mut timing.wheel[slot]) {
+            while let Some(transaction) = timing.pending[pin].front() {
+                if transaction.time != timing.now {
+                    break;
+                }
+
+                let output = &mut self.output_results.inner[pin];
+                let value = (*output & !transaction.mask) | transaction.value;
+                if value != *output {
+                    *output = value;
+                    self.events
+                        .mark_changed(ElementIdx(timing.pin_elements[pin]));
+                }
+                timing.pending[pin].pop_front();
+            }
+        }
+
+        self
+    }
+}
@@ -0,0 +1,191 @@
+mod expr;
+mod minimize;
+
+use std::collections::HashMap;
+
+pub use expr::{Expr, ParseError};
+use glam::Vec2;
+pub use minimize::{minimize, Implicant};
+
+use super::{
+    circuit::{
+        connection::{ElementIdx, OutputSpecifier},
+        Circuit, TruthTable, MAX_TRUTH_TABLE_BITS,
+    },
+    gate::{Gate, FAN_IN},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SynthError {
+    Parse(ParseError),
+    // More input bits than `MAX_TRUTH_TABLE_BITS`
+    TooManyInputs(usize),
+    // Only single bit columns can be synthesized
+    BusColumn(String),
+}
+
+impl From<ParseError> for SynthError {
+    fn from(error: ParseError) -> Self {
+        SynthError::Parse(error)
+    }
+}
+
+// Horizontal spacing between the columns of a synthesized circuit
+const COLUMN: f32 = 2.0;
+
+impl Circuit {
+    // Builds a minimal two-level circuit for an expression such as `a & !b | c`
+    // The output is named `out`, unless the expression is written as `name = a & !b | c`
+    pub fn from_expression(source: &str) -> Result<Self, SynthError> {
+        let (name, expression) = match source.split_once('=') {
+            Some((name, expression)) => (name.trim(), expression),
+            None => ("out", source),
+        };
+
+        // Errors point into the whole source, including the name
+        let expr = Expr::parse(expression).map_err(|error| match error {
+            ParseError::UnexpectedCharacter(offset, char) => {
+                ParseError::UnexpectedCharacter(offset + source.len() - expression.len(), char)
+            }
+            error => error,
+        })?;
+        let variables = expr.variables();
+        if variables.len() > MAX_TRUTH_TABLE_BITS as usize {
+            return Err(SynthError::TooManyInputs(variables.len()));
+        }
+
+        let implicants = minimize(variables.len(), &expr.minterms(&variables), &[]);
+        Ok(Self::from_sum_of_products(
+            &variables,
+            &[(name.to_string(), implicants)],
+        ))
+    }
+
+    // Builds a minimal two-level circuit with the same outputs as every row of the table
+    pub fn from_truth_table(table: &TruthTable) -> Result<Self, SynthError> {
+        if let Some(column) = table
+            .inputs
+            .iter()
+            .chain(&table.outputs)
+            .find(|column| column.width != 1)
+        {
+            return Err(SynthError::BusColumn(column.name.clone()));
+        }
+
+        let variables: Vec<_> = table
+            .inputs
+            .iter()
+            .map(|column| column.name.clone())
+            .collect();
+        let outputs: Vec<_> = table
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(output, column)| {
+                let minterms: Vec<_> = (0..table.rows.len() as u32)
+                    .filter(|&row| table.rows[row as usize].outputs[output] & 1 == 1)
+                    .collect();
+                (
+                    column.name.clone(),
+                    minimize(variables.len(), &minterms, &[]),
+                )
+            })
+            .collect();
+
+        Ok(Self::from_sum_of_products(&variables, &outputs))
+    }
+
+    // Lays out Input gates, inverters, AND gates, OR gates and Output gates in columns
+    // Product terms are shared between outputs, and single literals skip the AND gate
+    pub fn from_sum_of_products(inputs: &[String], outputs: &[(String, Vec<Implicant>)]) -> Self {
+        let mut circuit = Circuit::default();
+        let row = |index: usize| -(index as f32);
+
+        let input_ports: Vec<_> = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let port =
+                    circuit.add_gate(Gate::Input(Some(name.clone())), Vec2::new(0.0, row(index)));
+                port.output(0)
+            })
+            .collect();
+
+        let mut inverters = HashMap::new();
+        let mut literal = |circuit: &mut Circuit, (variable, positive): (usize, bool)| {
+            if positive {
+                return input_ports[variable];
+            }
+            *inverters.entry(variable).or_insert_with(|| {
+                let not = circuit.add_gate(Gate::Not, Vec2::new(COLUMN, row(variable)));
+                circuit
+                    .add_connection(input_ports[variable].to(not.input(0)))
+                    .unwrap();
+                not.output(0)
+            })
+        };
+
+        let mut products = HashMap::new();
+        for (index, (name, implicants)) in outputs.iter().enumerate() {
+            let mut terms = vec![];
+            for implicant in implicants {
+                if let Some(term) = products.get(implicant) {
+                    terms.push(*term);
+                    continue;
+                }
+
+                let literals: Vec<_> = implicant
+                    .literals(inputs.len())
+                    .map(|literal_of| literal(&mut circuit, literal_of))
+                    .collect();
+                let position = Vec2::new(COLUMN * 2.0, row(products.len()));
+                let term = combine(&mut circuit, literals, Gate::And, Gate::On, position);
+                products.insert(*implicant, term);
+                terms.push(term);
+            }
+
+            let position = Vec2::new(COLUMN * 3.0, row(index));
+            let sum = combine(&mut circuit, terms, Gate::Or, Gate::Off, position);
+            let port = circuit.add_gate(
+                Gate::Output(Some(name.clone())),
+                Vec2::new(COLUMN * 4.0, row(index)),
+            );
+            circuit.add_connection(sum.to(port.input(0))).unwrap();
+        }
+
+        circuit
+    }
+}
+
+// A single output combining the sources with a gate, using a tree of gates if there are too many
+// for one, or `empty` if there are none
+fn combine(
+    circuit: &mut Circuit,
+    mut sources: Vec<OutputSpecifier>,
+    gate: fn(u8) -> Gate,
+    empty: Gate,
+    position: Vec2,
+) -> OutputSpecifier {
+    if sources.is_empty() {
+        return circuit.add_gate(empty, position).output(0);
+    }
+
+    while sources.len() > 1 {
+        sources = sources
+            .chunks(*FAN_IN.end() as usize)
+            .map(|chunk| match chunk {
+                [source] => *source,
+                chunk => {
+                    let combined: ElementIdx = circuit.add_gate(gate(chunk.len() as u8), position);
+                    for (pin, source) in chunk.iter().enumerate() {
+                        circuit
+                            .add_connection(source.to(combined.input(pin)))
+                            .unwrap();
+                    }
+                    combined.output(0)
+                }
+            })
+            .collect();
+    }
+    sources[0]
+}
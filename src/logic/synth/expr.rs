@@ -0,0 +1,164 @@
+use std::{iter::Peekable, str::CharIndices};
+
+// A boolean expression over named single bit variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    // A character which can't start or continue an expression, at its byte offset
+    UnexpectedCharacter(usize, char),
+    UnexpectedEnd,
+}
+
+impl Expr {
+    // Operators from tightest to loosest binding are `!`, `&`, `^` and `|`
+    // `~`, `*` and `+` may be used for not, and and or, and `0` and `1` are constants
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            chars: source.char_indices().peekable(),
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            Some((offset, char)) => Err(ParseError::UnexpectedCharacter(offset, char)),
+            None => Ok(expr),
+        }
+    }
+
+    // Each variable once, in order of first appearance
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(expr: &Expr, variables: &mut Vec<String>) {
+            match expr {
+                Expr::Const(_) => {}
+                Expr::Var(name) => {
+                    if !variables.contains(name) {
+                        variables.push(name.clone());
+                    }
+                }
+                Expr::Not(inner) => collect(inner, variables),
+                Expr::And(terms) | Expr::Or(terms) | Expr::Xor(terms) => {
+                    for term in terms {
+                        collect(term, variables);
+                    }
+                }
+            }
+        }
+
+        let mut variables = vec![];
+        collect(self, &mut variables);
+        variables
+    }
+
+    pub fn eval(&self, value: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(constant) => *constant,
+            Expr::Var(name) => value(name),
+            Expr::Not(inner) => !inner.eval(value),
+            Expr::And(terms) => terms.iter().all(|term| term.eval(value)),
+            Expr::Or(terms) => terms.iter().any(|term| term.eval(value)),
+            Expr::Xor(terms) => terms.iter().filter(|term| term.eval(value)).count() & 1 == 1,
+        }
+    }
+
+    // The rows where the expression is true, with the first variable as the most significant bit
+    pub fn minterms(&self, variables: &[String]) -> Vec<u32> {
+        (0..1u32 << variables.len())
+            .filter(|row| {
+                self.eval(&|name| {
+                    let index = variables.iter().position(|variable| variable == name);
+                    index.is_some_and(|index| (row >> (variables.len() - 1 - index)) & 1 == 1)
+                })
+            })
+            .collect()
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.skip_whitespace();
+        self.chars.next()
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|(_, char)| *char)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, char)| char.is_whitespace())
+            .is_some()
+        {}
+    }
+
+    // Parses operands separated by any of `operators`, flattening chains into a single node
+    fn chain(
+        &mut self,
+        operators: &[char],
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+        node: fn(Vec<Expr>) -> Expr,
+    ) -> Result<Expr, ParseError> {
+        let mut terms = vec![operand(self)?];
+        while self.peek().is_some_and(|char| operators.contains(&char)) {
+            self.next();
+            terms.push(operand(self)?);
+        }
+
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            node(terms)
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['|', '+'], Self::xor, Expr::Or)
+    }
+
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['^'], Self::and, Expr::Xor)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['&', '*'], Self::unary, Expr::And)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.next().ok_or(ParseError::UnexpectedEnd)? {
+            (_, '!' | '~') => Ok(Expr::Not(Box::new(self.unary()?))),
+            (_, '(') => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some((_, ')')) => Ok(inner),
+                    Some((offset, char)) => Err(ParseError::UnexpectedCharacter(offset, char)),
+                    None => Err(ParseError::UnexpectedEnd),
+                }
+            }
+            (_, '0') => Ok(Expr::Const(false)),
+            (_, '1') => Ok(Expr::Const(true)),
+            (_, char) if char.is_alphabetic() || char == '_' => {
+                let mut name = char.to_string();
+                while let Some((_, char)) = self
+                    .chars
+                    .next_if(|(_, char)| char.is_alphanumeric() || *char == '_')
+                {
+                    name.push(char);
+                }
+                Ok(Expr::Var(name))
+            }
+            (offset, char) => Err(ParseError::UnexpectedCharacter(offset, char)),
+        }
+    }
+}
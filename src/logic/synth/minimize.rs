@@ -0,0 +1,174 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+// A product term over `variables` inputs, with the first variable as the most significant bit
+// Bits set in `mask` must match those of `value`, the others don't matter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Implicant {
+    pub value: u32,
+    pub mask: u32,
+}
+
+impl Implicant {
+    pub fn covers(&self, minterm: u32) -> bool {
+        minterm & self.mask == self.value
+    }
+
+    pub fn literal_count(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    // The variables of the term, along with whether each appears uncomplemented
+    pub fn literals(&self, variables: usize) -> impl Iterator<Item = (usize, bool)> + '_ {
+        (0..variables).filter_map(move |variable| {
+            let bit = 1 << (variables - 1 - variable);
+            (self.mask & bit != 0).then_some((variable, self.value & bit != 0))
+        })
+    }
+}
+
+const IMPLICANT: u8 = 1;
+const CONTAINED: u8 = 2;
+
+// Two-level minimisation with Quine-McCluskey, returning a small set of prime implicants which
+// together cover every minterm and nothing outside of the minterms and don't cares
+// Once the essential prime implicants are chosen, the rest of the cover is picked greedily
+pub fn minimize(variables: usize, minterms: &[u32], dont_cares: &[u32]) -> Vec<Implicant> {
+    let full_mask = ((1u64 << variables) - 1) as u32;
+
+    // Quine-McCluskey over a table of every cube, indexed in base 3 with a digit of 2 for a variable
+    // which doesn't matter. A cube is an implicant when both halves of it are, and those halves
+    // always come earlier in the table, so one pass finds every implicant and marks those
+    // contained in a larger one, which leaves the primes
+    let powers: Vec<usize> = (0..variables).map(|bit| 3usize.pow(bit as u32)).collect();
+    let mut flags = vec![0u8; 3usize.pow(variables as u32)];
+    for &minterm in minterms.iter().chain(dont_cares) {
+        let index: usize = (0..variables)
+            .filter(|&bit| minterm & (1 << bit) != 0)
+            .map(|bit| powers[bit])
+            .sum();
+        flags[index] = IMPLICANT;
+    }
+    let mut digits = vec![0u8; variables];
+    for index in 0..flags.len() {
+        if let Some(lowest) = digits.iter().position(|&digit| digit == 2) {
+            let power = powers[lowest];
+            if flags[index - 2 * power] & flags[index - power] & IMPLICANT != 0 {
+                flags[index] |= IMPLICANT;
+                for (bit, _) in digits.iter().enumerate().filter(|(_, &digit)| digit == 2) {
+                    flags[index - 2 * powers[bit]] |= CONTAINED;
+                    flags[index - powers[bit]] |= CONTAINED;
+                }
+            }
+        }
+        for digit in &mut digits {
+            if *digit == 2 {
+                *digit = 0;
+            } else {
+                *digit += 1;
+                break;
+            }
+        }
+    }
+
+    let mut primes: Vec<_> = (0..flags.len())
+        .filter(|&index| flags[index] == IMPLICANT)
+        .map(|index| {
+            let mut term = Implicant {
+                value: 0,
+                mask: full_mask,
+            };
+            for (bit, &power) in powers.iter().enumerate() {
+                match index / power % 3 {
+                    1 => term.value |= 1 << bit,
+                    2 => term.mask &= !(1 << bit),
+                    _ => {}
+                }
+            }
+            term
+        })
+        .collect();
+    primes.sort();
+
+    // The primes covering each minterm, and the number of uncovered minterms each prime covers
+    let mut indices = vec![None; 1 << variables];
+    for (index, &minterm) in minterms.iter().enumerate() {
+        indices[minterm as usize] = Some(index);
+    }
+    let mut covering = vec![vec![]; minterms.len()];
+    let mut counts = vec![0; primes.len()];
+    for (index, prime) in primes.iter().enumerate() {
+        let free = full_mask & !prime.mask;
+        let mut bits = free;
+        loop {
+            if let Some(minterm) = indices[(prime.value | bits) as usize] {
+                covering[minterm].push(index);
+                counts[index] += 1;
+            }
+            if bits == 0 {
+                break;
+            }
+            bits = (bits - 1) & free;
+        }
+    }
+
+    let mut covered = vec![false; minterms.len()];
+    let mut uncovered = indices.iter().flatten().count();
+    let mut cover = vec![];
+    // Returns the number of minterms the prime newly covers
+    let mut choose = |prime: usize, covered: &mut Vec<bool>, counts: &mut Vec<usize>| {
+        let mut newly = 0;
+        let free = full_mask & !primes[prime].mask;
+        let mut bits = free;
+        loop {
+            if let Some(minterm) = indices[(primes[prime].value | bits) as usize] {
+                if !covered[minterm] {
+                    covered[minterm] = true;
+                    newly += 1;
+                    for &other in &covering[minterm] {
+                        counts[other] -= 1;
+                    }
+                }
+            }
+            if bits == 0 {
+                break;
+            }
+            bits = (bits - 1) & free;
+        }
+        cover.push(primes[prime]);
+        newly
+    };
+
+    // Minterms covered by a single prime implicant need it
+    for minterm in 0..minterms.len() {
+        if let (&[prime], false) = (&covering[minterm][..], covered[minterm]) {
+            uncovered -= choose(prime, &mut covered, &mut counts);
+        }
+    }
+
+    // Counts only go down, so a prime whose count is still current when it reaches the top of the
+    // heap is the best one left
+    let key = |prime: usize, count: usize| {
+        (
+            count,
+            Reverse(primes[prime].literal_count()),
+            Reverse(prime),
+        )
+    };
+    let mut heap: BinaryHeap<_> = (0..primes.len())
+        .filter(|&prime| counts[prime] > 0)
+        .map(|prime| key(prime, counts[prime]))
+        .collect();
+    while uncovered > 0 {
+        let (count, _, Reverse(prime)) = heap.pop().unwrap();
+        if count != counts[prime] {
+            if counts[prime] > 0 {
+                heap.push(key(prime, counts[prime]));
+            }
+            continue;
+        }
+        uncovered -= choose(prime, &mut covered, &mut counts);
+    }
+
+    cover.sort();
+    cover
+}
@@ -0,0 +1,262 @@
+//! Plain-text test vectors.
+//!
+//! A test is a list of commands, one per line, run in order against a single
+//! copy of a circuit so sequential circuits keep their state between vectors:
+//!
+//! ```text
+//! # Comments run to the end of the line
+//! set a=1 b=0 carry=1    # drive Input gates by label
+//! settle                 # step until the circuit stops changing
+//! expect sum=0 carry_out=1
+//! step 2                 # step a fixed number of times
+//! reset                  # start again from a freshly loaded circuit
+//! ```
+//!
+//! Ports are named like the columns of a truth table, so unlabelled ports are
+//! `in0`, `out1` and so on. Values are decimal, or hexadecimal and binary with
+//! a `0x` or `0b` prefix. Every `expect` line is a vector which passes when all
+//! of its outputs match, and fails if they don't or if the circuit didn't
+//! settle since the previous vector.
+
+use std::fmt;
+
+use super::{
+    circuit::{connection::width_mask, Circuit, Column},
+    SettleError,
+};
+
+// Enough for any circuit which settles at all
+pub const DEFAULT_SETTLE_STEPS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VectorError {
+    // A line which doesn't fit the format, by line number
+    Syntax { line: usize, found: String },
+    UnknownInput { line: usize, name: String },
+    UnknownOutput { line: usize, name: String },
+    // A value with more bits than its port
+    TooWide { line: usize, name: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Set(Vec<(String, u64)>),
+    Step(usize),
+    Settle(usize),
+    Reset,
+    Expect(Vec<(String, u64)>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestVectors {
+    // Each command along with its line number
+    pub commands: Vec<(usize, Command)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    Mismatch {
+        output: String,
+        expected: u64,
+        actual: u64,
+    },
+    // A `settle` since the previous vector which didn't, by line number
+    Settle {
+        line: usize,
+        error: SettleError,
+    },
+}
+
+// The outcome of one `expect` line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VectorResult {
+    pub line: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl VectorResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl TestVectors {
+    pub fn parse(source: &str) -> Result<Self, VectorError> {
+        let mut commands = vec![];
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = text.split('#').next().unwrap();
+            let mut words = text.split_whitespace();
+            let Some(command) = words.next() else {
+                continue;
+            };
+
+            let syntax = |found: &str| VectorError::Syntax {
+                line,
+                found: found.to_string(),
+            };
+            let count = |words: &mut std::str::SplitWhitespace, default| match words.next() {
+                None => Ok(default),
+                Some(word) => word.parse().map_err(|_| syntax(word)),
+            };
+            let command = match command {
+                "set" => Command::Set(parse_assignments(words.by_ref(), line)?),
+                "expect" => Command::Expect(parse_assignments(words.by_ref(), line)?),
+                "step" => Command::Step(count(&mut words, 1)?),
+                "settle" => Command::Settle(count(&mut words, DEFAULT_SETTLE_STEPS)?),
+                "reset" => Command::Reset,
+                _ => return Err(syntax(command)),
+            };
+            if let Some(extra) = words.next() {
+                return Err(syntax(extra));
+            }
+            commands.push((line, command));
+        }
+        Ok(Self { commands })
+    }
+
+    // Runs every command against a copy of the circuit, returning a result for each vector
+    // Names and values are checked against the circuit's ports before anything is run
+    pub fn run(&self, circuit: &Circuit) -> Result<Vec<VectorResult>, VectorError> {
+        let inputs = circuit.input_columns();
+        let outputs = circuit.output_columns();
+        let port =
+            |columns: &[Column], name: &str| columns.iter().position(|column| column.name == name);
+
+        // Ports are resolved to their positions up front, so a typo fails the whole run
+        let mut resolved = vec![];
+        for (line, command) in &self.commands {
+            let (assignments, columns) = match command {
+                Command::Set(assignments) => (assignments, &inputs),
+                Command::Expect(assignments) => (assignments, &outputs),
+                _ => {
+                    resolved.push(vec![]);
+                    continue;
+                }
+            };
+
+            let mut ports = vec![];
+            for (name, value) in assignments {
+                let Some(index) = port(columns, name) else {
+                    let (line, name) = (*line, name.clone());
+                    return Err(match command {
+                        Command::Set(_) => VectorError::UnknownInput { line, name },
+                        _ => VectorError::UnknownOutput { line, name },
+                    });
+                };
+                if value & !width_mask(columns[index].width) != 0 {
+                    return Err(VectorError::TooWide {
+                        line: *line,
+                        name: name.clone(),
+                    });
+                }
+                ports.push((index, *value));
+            }
+            resolved.push(ports);
+        }
+
+        let fresh = || {
+            let mut fresh = Circuit {
+                elements: circuit.elements.clone(),
+                connections: circuit.connections.clone(),
+                ..Default::default()
+            };
+            fresh.set_solver_mode(circuit.solver_mode());
+            fresh.right_size_solver();
+            fresh
+        };
+        let input_ports = circuit.input_ports();
+        let output_ports = circuit.output_ports();
+
+        let mut simulated = fresh();
+        let mut results = vec![];
+        let mut failures = vec![];
+        for ((line, command), ports) in self.commands.iter().zip(resolved) {
+            match command {
+                Command::Set(_) => {
+                    for (index, value) in ports {
+                        simulated.set_input(input_ports[index], value);
+                    }
+                }
+                Command::Step(steps) => simulated.step_n(*steps),
+                Command::Settle(max_steps) => {
+                    if let Err(error) = simulated.settle(*max_steps) {
+                        failures.push(Failure::Settle { line: *line, error });
+                    }
+                }
+                Command::Reset => simulated = fresh(),
+                Command::Expect(assignments) => {
+                    for ((index, expected), (name, _)) in ports.into_iter().zip(assignments) {
+                        let actual = simulated.output_bus_value(output_ports[index].output(0));
+                        if actual != expected {
+                            failures.push(Failure::Mismatch {
+                                output: name.clone(),
+                                expected,
+                                actual,
+                            });
+                        }
+                    }
+                    results.push(VectorResult {
+                        line: *line,
+                        failures: std::mem::take(&mut failures),
+                    });
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+// `name=value` pairs, with errors reported on the given line
+pub fn parse_assignments<'a>(
+    words: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<Vec<(String, u64)>, VectorError> {
+    words
+        .map(|word| {
+            let syntax = || VectorError::Syntax {
+                line,
+                found: word.to_string(),
+            };
+            let (name, value) = word.split_once('=').ok_or_else(syntax)?;
+            if name.is_empty() {
+                return Err(syntax());
+            }
+            Ok((name.to_string(), parse_value(value).ok_or_else(syntax)?))
+        })
+        .collect()
+}
+
+fn parse_value(value: &str) -> Option<u64> {
+    let value = value.replace('_', "");
+    if let Some(hex) = value.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = value.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Mismatch {
+                output,
+                expected,
+                actual,
+            } => write!(f, "{output}: expected {expected}, got {actual}"),
+            Failure::Settle {
+                line,
+                error: SettleError::Oscillating { period },
+            } => write!(
+                f,
+                "line {line}: oscillating with a period of {period} steps"
+            ),
+            Failure::Settle {
+                line,
+                error: SettleError::StepLimit,
+            } => write!(f, "line {line}: didn't settle within the step limit"),
+        }
+    }
+}
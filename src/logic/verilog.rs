@@ -0,0 +1,149 @@
+//! Structural Verilog.
+//!
+//! Circuits are written as gate-level Verilog-2001 with one module per
+//! circuit. Input and Output gates become the ports of their module, named
+//! like the columns of a truth table, and every embedded component becomes a
+//! module of its own which is instantiated wherever the component is used.
+//!
+//! Single bit basic gates are written as primitives, buses as continuous
+//! assignments and sequential gates as `always` blocks. The only inputs with
+//! several drivers are buses shared by tri-state buffers, which are combined
+//! with `|` since disabled buffers output zero.
+//!
+//! The importer reads gate-level netlists: modules with plain or ANSI style
+//! headers, `wire` declarations, continuous assignments using `& | ^ ~` along
+//! with concatenation and bit selects, primitive gates and module instances.
+//! This covers everything the exporter writes apart from `always` blocks. Each
+//! module becomes a component shared by its instances, and gates are laid out
+//! in columns by their depth.
+
+mod export;
+mod import;
+mod parse;
+
+use std::collections::HashSet;
+
+use super::circuit::{
+    connection::{ConnectionError, ElementIdx},
+    Circuit,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportError {
+    // An element, by its path through embedded circuits, which only the simulator can drive
+    Unsupported(Vec<ElementIdx>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    // A token which doesn't fit the grammar, by line number
+    Syntax { line: usize, found: String },
+    UnexpectedEnd,
+    // A construct outside of the structural subset, such as an `always` block
+    Unsupported { line: usize, construct: String },
+    NoModules,
+    UnknownModule { line: usize, module: String },
+    // A module which instantiates itself, directly or through others
+    RecursiveModule(String),
+    UnknownPort { line: usize, port: String },
+    // A port in a module header without an `input` or `output` declaration
+    UndeclaredPort { line: usize, port: String },
+    // A net with more than one driver, or an input port which is driven inside its module
+    MultipleDrivers { line: usize, net: String },
+    WidthMismatch { line: usize },
+    // A gate which reads its own output, like a buffer feeding itself
+    Connection(ConnectionError),
+}
+
+impl Circuit {
+    // The circuit as a module named `module`, after the modules of its embedded components
+    pub fn to_verilog(&self, module: &str) -> Result<String, ExportError> {
+        export::export(self, module)
+    }
+
+    // The top module of the source, which is the last one no other module instantiates
+    pub fn from_verilog(source: &str) -> Result<Self, ImportError> {
+        import::import(source)
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "case",
+    "default",
+    "else",
+    "end",
+    "endcase",
+    "endfunction",
+    "endmodule",
+    "for",
+    "function",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "integer",
+    "module",
+    "nand",
+    "negedge",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "parameter",
+    "posedge",
+    "reg",
+    "supply0",
+    "supply1",
+    "tri",
+    "wand",
+    "wire",
+    "wor",
+    "xnor",
+    "xor",
+];
+
+// A legal identifier, as close to the name as possible
+pub fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || char == '_' {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !identifier.starts_with(|char: char| char.is_ascii_alphabetic() || char == '_') {
+        identifier.insert(0, '_');
+    }
+    if KEYWORDS.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+    identifier
+}
+
+// Identifiers already used in a scope
+#[derive(Default)]
+struct Names {
+    taken: HashSet<String>,
+}
+
+impl Names {
+    // A legal identifier for the name, numbered if it's already taken
+    fn unique(&mut self, name: &str) -> String {
+        let base = identifier(name);
+        let mut candidate = base.clone();
+        let mut number = 1;
+        while !self.taken.insert(candidate.clone()) {
+            candidate = format!("{base}_{number}");
+            number += 1;
+        }
+        candidate
+    }
+}
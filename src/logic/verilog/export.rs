@@ -0,0 +1,382 @@
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use crate::logic::{
+    circuit::{
+        connection::{width_mask, ElementIdx, InputSpecifier, OutputSpecifier},
+        embedded::Component,
+        Circuit, Column,
+    },
+    gate::Gate,
+};
+
+use super::{ExportError, Names};
+
+pub fn export(circuit: &Circuit, module: &str) -> Result<String, ExportError> {
+    let mut exporter = Exporter::default();
+    let name = exporter.module_names.unique(module);
+    exporter.module(circuit, name)?;
+    Ok(exporter.modules.join("\n"))
+}
+
+#[derive(Default)]
+struct Exporter {
+    // Module definitions, with every module after the ones it instantiates
+    modules: Vec<String>,
+    module_names: Names,
+    // The module name and port names of each component written so far
+    components: HashMap<*const Component, ModulePorts>,
+    // Embedded elements leading to the circuit being written
+    path: Vec<ElementIdx>,
+}
+
+#[derive(Clone)]
+struct ModulePorts {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl Exporter {
+    fn module(&mut self, circuit: &Circuit, name: String) -> Result<ModulePorts, ExportError> {
+        for (index, element) in circuit.elements.iter().enumerate() {
+            if let Gate::Embedded(embed) = &element.gate {
+                let component = embed.component();
+                if self.components.contains_key(&Arc::as_ptr(component)) {
+                    continue;
+                }
+
+                let name = self
+                    .module_names
+                    .unique(component.name().unwrap_or("component"));
+                self.path.push(ElementIdx(index));
+                let ports = self.module(component.circuit(), name)?;
+                self.path.pop();
+                self.components.insert(Arc::as_ptr(component), ports);
+            }
+        }
+
+        let mut writer = ModuleWriter::new(circuit);
+        for index in 0..circuit.elements.len() {
+            writer
+                .element(ElementIdx(index), &self.components)
+                .map_err(|()| {
+                    let mut path = self.path.clone();
+                    path.push(ElementIdx(index));
+                    ExportError::Unsupported(path)
+                })?;
+        }
+
+        let ports = ModulePorts {
+            name,
+            inputs: writer.inputs.clone(),
+            outputs: writer.outputs.clone(),
+        };
+        self.modules.push(writer.finish(&ports.name));
+        Ok(ports)
+    }
+}
+
+struct ModuleWriter<'a> {
+    circuit: &'a Circuit,
+    names: Names,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    // The net driven by each output pin, which for ports is the port itself
+    nets: HashMap<OutputSpecifier, String>,
+    drivers: HashMap<InputSpecifier, Vec<OutputSpecifier>>,
+    declarations: String,
+    body: String,
+}
+
+impl<'a> ModuleWriter<'a> {
+    fn new(circuit: &'a Circuit) -> Self {
+        let mut names = Names::default();
+        let mut nets = HashMap::new();
+        let mut declarations = String::new();
+
+        let mut ports = |ports: Vec<ElementIdx>, columns: Vec<_>, direction: &str| {
+            ports
+                .into_iter()
+                .zip(columns)
+                .map(|(port, column): (_, Column)| {
+                    let name = names.unique(&column.name);
+                    writeln!(declarations, "  {direction}{} {name};", range(column.width)).unwrap();
+                    nets.insert(port.output(0), name.clone());
+                    name
+                })
+                .collect::<Vec<_>>()
+        };
+        let inputs = ports(circuit.input_ports(), circuit.input_columns(), "input");
+        let outputs = ports(circuit.output_ports(), circuit.output_columns(), "output");
+
+        for (index, element) in circuit.elements.iter().enumerate() {
+            let element_idx = ElementIdx(index);
+            let count = element.gate.output_count();
+            for output in 0..count {
+                let pin = element_idx.output(output);
+                if nets.contains_key(&pin) {
+                    continue;
+                }
+
+                let name = if count == 1 {
+                    names.unique(&format!("w{index}"))
+                } else {
+                    names.unique(&format!("w{index}_{output}"))
+                };
+                // The stored state of sequential gates is assigned in `always` blocks
+                let kind = match element.gate {
+                    Gate::SrLatch
+                    | Gate::DLatch
+                    | Gate::DFlipFlop
+                    | Gate::JkFlipFlop
+                    | Gate::Register(_)
+                        if output == 0 =>
+                    {
+                        "reg"
+                    }
+                    _ => "wire",
+                };
+                let width = circuit.pin_width(pin).unwrap_or(1);
+                writeln!(declarations, "  {kind}{} {name};", range(width)).unwrap();
+                nets.insert(pin, name);
+            }
+        }
+
+        let mut drivers: HashMap<_, Vec<_>> = HashMap::new();
+        for connection in &circuit.connections {
+            drivers
+                .entry(connection.to)
+                .or_default()
+                .push(connection.from);
+        }
+
+        Self {
+            circuit,
+            names,
+            inputs,
+            outputs,
+            nets,
+            drivers,
+            declarations,
+            body: String::new(),
+        }
+    }
+
+    fn finish(self, name: &str) -> String {
+        let ports: Vec<_> = self.inputs.iter().chain(&self.outputs).cloned().collect();
+        let header = if ports.is_empty() {
+            format!("module {name};\n")
+        } else {
+            format!("module {name} ({});\n", ports.join(", "))
+        };
+        format!("{header}{}\n{}endmodule\n", self.declarations, self.body)
+    }
+
+    fn width(&self, pin: InputSpecifier) -> u8 {
+        self.circuit.pin_width(pin).unwrap_or(1)
+    }
+
+    // The value of an input, which reads low when nothing drives it
+    // Only tri-state buffers can share an input, and disabled ones output zero, so their OR is
+    // the value of the bus
+    fn expr(&self, pin: InputSpecifier) -> String {
+        let drivers = self.drivers.get(&pin).map_or(&[][..], Vec::as_slice);
+        match drivers {
+            [] => literal(self.width(pin), 0),
+            [driver] => self.nets[driver].clone(),
+            drivers => {
+                let nets: Vec<_> = drivers
+                    .iter()
+                    .map(|driver| self.nets[driver].as_str())
+                    .collect();
+                format!("({})", nets.join(" | "))
+            }
+        }
+    }
+
+    // The value of an input as a single net, for places which can't take an expression
+    fn net(&mut self, pin: InputSpecifier, name: &str) -> String {
+        match self.drivers.get(&pin).map(Vec::as_slice) {
+            Some([driver]) => self.nets[driver].clone(),
+            _ => {
+                let net = self.names.unique(name);
+                let width = self.width(pin);
+                writeln!(self.declarations, "  wire{} {net};", range(width)).unwrap();
+                let expr = self.expr(pin);
+                writeln!(self.body, "  assign {net} = {expr};").unwrap();
+                net
+            }
+        }
+    }
+
+    // Fails for gates which only the simulator can drive
+    fn element(
+        &mut self,
+        element: ElementIdx,
+        components: &HashMap<*const Component, ModulePorts>,
+    ) -> Result<(), ()> {
+        let ElementIdx(index) = element;
+        let circuit = self.circuit;
+        let gate = &circuit[element].gate;
+        let inputs: Vec<_> = (0..gate.input_count())
+            .map(|input| self.expr(element.input(input)))
+            .collect();
+        let outputs: Vec<_> = (0..gate.output_count())
+            .map(|output| self.nets[&element.output(output)].clone())
+            .collect();
+        let out = |output: usize| outputs[output].clone();
+        let width = circuit.pin_width(element.output(0)).unwrap_or(1);
+
+        let line = match gate {
+            Gate::Button | Gate::Clock { .. } => return Err(()),
+            Gate::Input(_) => return Ok(()),
+            Gate::Output(_) => format!("assign {} = {};", out(0), inputs[0]),
+            Gate::Const(false) | Gate::Off => format!("assign {} = {};", out(0), literal(width, 0)),
+            Gate::Const(true) | Gate::On => {
+                format!("assign {} = {};", out(0), literal(width, width_mask(width)))
+            }
+            Gate::And(_)
+            | Gate::Or(_)
+            | Gate::Xor(_)
+            | Gate::Nand(_)
+            | Gate::Nor(_)
+            | Gate::Xnor(_)
+            | Gate::Not
+            | Gate::Buf => {
+                let (primitive, operator, inverted) = match gate {
+                    Gate::And(_) => ("and", " & ", false),
+                    Gate::Or(_) => ("or", " | ", false),
+                    Gate::Xor(_) => ("xor", " ^ ", false),
+                    Gate::Nand(_) => ("nand", " & ", true),
+                    Gate::Nor(_) => ("nor", " | ", true),
+                    Gate::Xnor(_) => ("xnor", " ^ ", true),
+                    Gate::Not => ("not", "", true),
+                    _ => ("buf", "", false),
+                };
+
+                // Primitives are single bit, buses use bitwise operators instead
+                if width == 1 {
+                    let instance = self.names.unique(&format!("g{index}"));
+                    format!(
+                        "{primitive} {instance} ({}, {});",
+                        out(0),
+                        inputs.join(", ")
+                    )
+                } else {
+                    let expr = inputs.join(operator);
+                    let expr = match (inverted, inputs.len()) {
+                        (false, _) => expr,
+                        (true, 1) => format!("~{expr}"),
+                        (true, _) => format!("~({expr})"),
+                    };
+                    format!("assign {} = {expr};", out(0))
+                }
+            }
+            Gate::Merge(_) => {
+                let bits: Vec<_> = inputs.iter().rev().map(String::as_str).collect();
+                format!("assign {} = {{{}}};", out(0), bits.join(", "))
+            }
+            Gate::Split(bits) => {
+                if *bits == 1 {
+                    format!("assign {} = {};", out(0), inputs[0])
+                } else {
+                    let bus = self.net(element.input(0), &format!("w{index}_in"));
+                    let assigns: Vec<_> = (0..*bits as usize)
+                        .map(|bit| format!("assign {} = {bus}[{bit}];", out(bit)))
+                        .collect();
+                    assigns.join("\n  ")
+                }
+            }
+            // Disabled outputs read low, so shared nets behave as a wired OR like in the solver
+            Gate::TriState => {
+                let enable = if width == 1 {
+                    inputs[1].clone()
+                } else {
+                    format!("{{{width}{{{}}}}}", inputs[1])
+                };
+                format!("assign {} = {} & {enable};", out(0), inputs[0])
+            }
+            Gate::SrLatch => format!(
+                "always @* if ({}) {q} <= 1'b1; else if ({}) {q} <= 1'b0;\n  assign {} = ~{q};",
+                inputs[0],
+                inputs[1],
+                out(1),
+                q = out(0),
+            ),
+            Gate::DLatch => format!(
+                "always @* if ({}) {q} <= {};\n  assign {} = ~{q};",
+                inputs[1],
+                inputs[0],
+                out(1),
+                q = out(0),
+            ),
+            Gate::DFlipFlop => {
+                let clock = self.net(element.input(1), &format!("w{index}_clk"));
+                format!(
+                    "always @(posedge {clock}) {q} <= {};\n  assign {} = ~{q};",
+                    inputs[0],
+                    out(1),
+                    q = out(0),
+                )
+            }
+            Gate::JkFlipFlop => {
+                let clock = self.net(element.input(2), &format!("w{index}_clk"));
+                format!(
+                    "always @(posedge {clock}) {q} <= {} & ~{q} | ~{} & {q};\n  assign {} = ~{q};",
+                    inputs[0],
+                    inputs[1],
+                    out(1),
+                    q = out(0),
+                )
+            }
+            Gate::Register(bits) => {
+                let clock = self.net(element.input(1), &format!("w{index}_clk"));
+                let reset = self.net(element.input(3), &format!("w{index}_rst"));
+                format!(
+                    "always @(posedge {clock} or posedge {reset}) if ({reset}) {q} <= {}; else if ({}) {q} <= {};",
+                    literal(*bits, 0),
+                    inputs[2],
+                    inputs[0],
+                    q = out(0),
+                )
+            }
+            Gate::Embedded(embed) => {
+                let ports = &components[&Arc::as_ptr(embed.component())];
+                let instance = self.names.unique(&format!("u{index}"));
+                let connections: Vec<_> = ports
+                    .inputs
+                    .iter()
+                    .zip(&inputs)
+                    .map(|(port, expr)| format!(".{port}({expr})"))
+                    .chain(
+                        ports
+                            .outputs
+                            .iter()
+                            .enumerate()
+                            .map(|(output, port)| format!(".{port}({})", out(output))),
+                    )
+                    .collect();
+                format!("{} {instance} ({});", ports.name, connections.join(", "))
+            }
+        };
+
+        writeln!(self.body, "  {line}").unwrap();
+        Ok(())
+    }
+}
+
+fn range(width: u8) -> String {
+    if width == 1 {
+        String::new()
+    } else {
+        format!(" [{}:0]", width - 1)
+    }
+}
+
+fn literal(width: u8, value: u64) -> String {
+    if width == 1 {
+        format!("1'b{value}")
+    } else {
+        format!("{width}'h{value:x}")
+    }
+}
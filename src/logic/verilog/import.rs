@@ -0,0 +1,349 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::logic::{
+    circuit::{
+        connection::width_mask,
+        embedded::{Component, EmbeddedCircuit},
+        Circuit,
+    },
+    gate::{Gate, FAN_IN},
+    netlist::{Netlist, Source},
+};
+
+use super::{
+    parse::{parse, Expr, Item, Module, NetKind, Number, Ports, Statement},
+    ImportError,
+};
+
+pub fn import(source: &str) -> Result<Circuit, ImportError> {
+    let modules = parse(source)?;
+    let instantiated: HashSet<_> = modules
+        .iter()
+        .flat_map(|module| &module.statements)
+        .filter_map(|statement| match &statement.item {
+            Item::Instance { module, .. } => Some(module.as_str()),
+            _ => None,
+        })
+        .collect();
+    let top = modules
+        .iter()
+        .rev()
+        .find(|module| !instantiated.contains(module.name.as_str()))
+        .or(modules.last())
+        .ok_or(ImportError::NoModules)?;
+
+    let mut importer = Importer {
+        modules: modules
+            .iter()
+            .map(|module| (module.name.as_str(), module))
+            .collect(),
+        components: HashMap::new(),
+        stack: vec![],
+    };
+    importer.circuit(top)
+}
+
+struct Importer<'a> {
+    modules: HashMap<&'a str, &'a Module>,
+    // Every instance of a module shares one component
+    components: HashMap<&'a str, Arc<Component>>,
+    // Modules being imported, innermost last
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Importer<'a> {
+    fn component(&mut self, name: &str, line: usize) -> Result<Arc<Component>, ImportError> {
+        if let Some(component) = self.components.get(name) {
+            return Ok(component.clone());
+        }
+
+        let module = *self
+            .modules
+            .get(name)
+            .ok_or_else(|| ImportError::UnknownModule {
+                line,
+                module: name.to_string(),
+            })?;
+        if self.stack.contains(&module.name.as_str()) {
+            return Err(ImportError::RecursiveModule(module.name.clone()));
+        }
+
+        let component = Arc::new(Component::named(&module.name, self.circuit(module)?));
+        self.components.insert(&module.name, component.clone());
+        Ok(component)
+    }
+
+    fn circuit(&mut self, module: &'a Module) -> Result<Circuit, ImportError> {
+        self.stack.push(&module.name);
+        let mut builder = Builder::new(module)?;
+        for statement in &module.statements {
+            builder.statement(statement, self)?;
+        }
+        self.stack.pop();
+        builder.netlist.finish().map_err(ImportError::Connection)
+    }
+}
+
+struct Builder<'a> {
+    kinds: HashMap<&'a str, NetKind>,
+    widths: HashMap<&'a str, u8>,
+    netlist: Netlist,
+}
+
+impl<'a> Builder<'a> {
+    fn new(module: &'a Module) -> Result<Self, ImportError> {
+        let mut kinds = HashMap::new();
+        let mut widths = HashMap::new();
+        for declaration in &module.declarations {
+            for name in &declaration.names {
+                // Ports may also be declared as wires, which doesn't change their direction
+                let kind = kinds.entry(name.as_str()).or_insert(declaration.kind);
+                if *kind == NetKind::Wire {
+                    *kind = declaration.kind;
+                }
+                widths.insert(name.as_str(), declaration.width);
+            }
+        }
+
+        let mut netlist = Netlist::default();
+        for port in &module.ports {
+            match kinds.get(port.as_str()) {
+                Some(NetKind::Input) => {
+                    netlist.input(port, widths[port.as_str()]);
+                }
+                Some(NetKind::Output) => netlist.output(port),
+                _ => {
+                    return Err(ImportError::UndeclaredPort {
+                        line: module.line,
+                        port: port.clone(),
+                    })
+                }
+            }
+        }
+
+        Ok(Self {
+            kinds,
+            widths,
+            netlist,
+        })
+    }
+
+    // Nets which aren't declared are implicitly single bit wires
+    fn width(&self, net: &str) -> u8 {
+        self.widths.get(net).copied().unwrap_or(1)
+    }
+
+    fn drive(
+        &mut self,
+        net: &str,
+        source: Source,
+        width: u8,
+        line: usize,
+    ) -> Result<(), ImportError> {
+        if width != self.width(net) {
+            return Err(ImportError::WidthMismatch { line });
+        }
+        if self.kinds.get(net) == Some(&NetKind::Input) || !self.netlist.drive(net, source) {
+            return Err(ImportError::MultipleDrivers {
+                line,
+                net: net.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Statement,
+        importer: &mut Importer<'a>,
+    ) -> Result<(), ImportError> {
+        let line = statement.line;
+        match &statement.item {
+            Item::Assign { net, expr } => {
+                let (source, width) = self.build(expr, line)?;
+                self.drive(net, source, width, line)
+            }
+            Item::Primitive {
+                gate,
+                output,
+                inputs,
+            } => {
+                let unsupported = || ImportError::Unsupported {
+                    line,
+                    construct: gate.clone(),
+                };
+                let inverted = matches!(gate.as_str(), "nand" | "nor" | "xnor" | "not");
+                let gate = match (gate.as_str(), inputs.len()) {
+                    (_, 0) => return Err(unsupported()),
+                    ("not" | "buf", 2..) => return Err(unsupported()),
+                    (_, 1) if inverted => Gate::Not,
+                    (_, 1) => Gate::Buf,
+                    (_, count) if count > *FAN_IN.end() as usize => return Err(unsupported()),
+                    ("and", count) => Gate::And(count as u8),
+                    ("or", count) => Gate::Or(count as u8),
+                    ("xor", count) => Gate::Xor(count as u8),
+                    ("nand", count) => Gate::Nand(count as u8),
+                    ("nor", count) => Gate::Nor(count as u8),
+                    (_, count) => Gate::Xnor(count as u8),
+                };
+                let (source, width) = self.gate(gate, inputs, line)?;
+                self.drive(output, source, width, line)
+            }
+            Item::Instance { module, ports } => {
+                let embed = EmbeddedCircuit::instance(importer.component(module, line)?);
+                let ports = match ports {
+                    Ports::Named(ports) => ports.clone(),
+                    Ports::Ordered(exprs) => {
+                        let header = &importer.modules[module.as_str()].ports;
+                        if exprs.len() > header.len() {
+                            return Err(ImportError::UnknownPort {
+                                line,
+                                port: format!("#{}", exprs.len()),
+                            });
+                        }
+                        header.iter().cloned().zip(exprs.iter().cloned()).collect()
+                    }
+                };
+
+                let instance = self
+                    .netlist
+                    .add(embed.clone().into(), embed.output_widths());
+                let position = |names: &[Option<String>], port: &str| {
+                    names.iter().position(|name| name.as_deref() == Some(port))
+                };
+                for (port, expr) in ports {
+                    if let Some(input) = position(embed.input_names(), &port) {
+                        let Some(expr) = expr else { continue };
+                        let (source, width) = self.build(&expr, line)?;
+                        if width != embed.input_widths()[input] {
+                            return Err(ImportError::WidthMismatch { line });
+                        }
+                        self.netlist.connect(source, instance.input(input));
+                    } else if let Some(output) = position(embed.output_names(), &port) {
+                        let pin = instance.output(output);
+                        let width = embed.output_widths()[output];
+                        match expr {
+                            None => {}
+                            Some(Expr::Net(net)) => {
+                                self.drive(&net, Source::Pin(pin), width, line)?
+                            }
+                            Some(_) => {
+                                return Err(ImportError::Unsupported {
+                                    line,
+                                    construct: format!(".{port}"),
+                                })
+                            }
+                        }
+                    } else {
+                        return Err(ImportError::UnknownPort { line, port });
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // The source of an expression's value, along with its width
+    fn build(&mut self, expr: &Expr, line: usize) -> Result<(Source, u8), ImportError> {
+        match expr {
+            Expr::Net(net) => Ok((Source::Net(net.clone()), self.width(net))),
+            Expr::Bit(net, bit) => {
+                if *bit >= self.width(net) {
+                    return Err(ImportError::WidthMismatch { line });
+                }
+                Ok((Source::Bit(net.clone(), *bit), 1))
+            }
+            Expr::Const(Number { width, value }) => {
+                let width = width.unwrap_or((64 - value.leading_zeros()).max(1) as u8);
+                Ok((self.constant(width, value & width_mask(width)), width))
+            }
+            Expr::Not(inner) => match inner.as_ref() {
+                Expr::And(terms) => self.gate(Gate::Nand(terms.len() as u8), terms, line),
+                Expr::Or(terms) => self.gate(Gate::Nor(terms.len() as u8), terms, line),
+                Expr::Xor(terms) => self.gate(Gate::Xnor(terms.len() as u8), terms, line),
+                inner => self.gate(Gate::Not, std::slice::from_ref(inner), line),
+            },
+            Expr::And(terms) => self.gate(Gate::And(terms.len() as u8), terms, line),
+            Expr::Or(terms) => self.gate(Gate::Or(terms.len() as u8), terms, line),
+            Expr::Xor(terms) => self.gate(Gate::Xor(terms.len() as u8), terms, line),
+            Expr::Concat(parts) => {
+                let mut bits = vec![];
+                for part in parts.iter().rev() {
+                    let (source, width) = self.build(part, line)?;
+                    bits.extend(self.netlist.bits(source, width));
+                }
+                self.merge(bits, line)
+            }
+            Expr::Replicate(count, inner) => {
+                let (source, width) = self.build(inner, line)?;
+                let bits = self.netlist.bits(source, width);
+                let bits = (0..*count).flat_map(|_| bits.clone()).collect();
+                self.merge(bits, line)
+            }
+        }
+    }
+
+    // A gate with every term as an input, which must all be the same width
+    fn gate(
+        &mut self,
+        gate: Gate,
+        terms: &[Expr],
+        line: usize,
+    ) -> Result<(Source, u8), ImportError> {
+        if terms.len() > *FAN_IN.end() as usize {
+            return Err(ImportError::Unsupported {
+                line,
+                construct: format!("{} operands", terms.len()),
+            });
+        }
+
+        let mut sources = vec![];
+        for term in terms {
+            sources.push(self.build(term, line)?);
+        }
+        let width = sources[0].1;
+        if sources.iter().any(|(_, term_width)| *term_width != width) {
+            return Err(ImportError::WidthMismatch { line });
+        }
+
+        let element = self.netlist.add(gate, &[width]);
+        for (input, (source, _)) in sources.into_iter().enumerate() {
+            self.netlist.connect(source, element.input(input));
+        }
+        Ok((Source::Pin(element.output(0)), width))
+    }
+
+    fn constant(&mut self, width: u8, value: u64) -> Source {
+        if value == 0 || value == width_mask(width) {
+            let gate = if value == 0 { Gate::Off } else { Gate::On };
+            return Source::Pin(self.netlist.add(gate, &[width]).output(0));
+        }
+
+        let on = self.netlist.add(Gate::On, &[1]).output(0);
+        let off = self.netlist.add(Gate::Off, &[1]).output(0);
+        let merge = self.netlist.add(Gate::Merge(width), &[width]);
+        for bit in 0..width as usize {
+            let source = if value >> bit & 1 == 1 { on } else { off };
+            self.netlist.connect(Source::Pin(source), merge.input(bit));
+        }
+        Source::Pin(merge.output(0))
+    }
+
+    fn merge(&mut self, bits: Vec<Source>, line: usize) -> Result<(Source, u8), ImportError> {
+        match bits.len() {
+            1 => Ok((bits.into_iter().next().unwrap(), 1)),
+            2..=64 => {
+                let width = bits.len() as u8;
+                let merge = self.netlist.add(Gate::Merge(width), &[width]);
+                for (input, bit) in bits.into_iter().enumerate() {
+                    self.netlist.connect(bit, merge.input(input));
+                }
+                Ok((Source::Pin(merge.output(0)), width))
+            }
+            _ => Err(ImportError::WidthMismatch { line }),
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use std::{iter::Peekable, str::Chars};
+
+use super::{ImportError, KEYWORDS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(Number),
+    Symbol(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Number {
+    // Unsized numbers take the width of their value
+    pub width: Option<u8>,
+    pub value: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Module {
+    pub name: String,
+    pub line: usize,
+    pub ports: Vec<String>,
+    pub declarations: Vec<Declaration>,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetKind {
+    Input,
+    Output,
+    Wire,
+}
+
+#[derive(Clone, Debug)]
+pub struct Declaration {
+    pub kind: NetKind,
+    pub width: u8,
+    pub names: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub line: usize,
+    pub item: Item,
+}
+
+#[derive(Clone, Debug)]
+pub enum Item {
+    Assign {
+        net: String,
+        expr: Expr,
+    },
+    // A built in gate such as `and`, whose first terminal is its output
+    Primitive {
+        gate: String,
+        output: String,
+        inputs: Vec<Expr>,
+    },
+    Instance {
+        module: String,
+        ports: Ports,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum Ports {
+    // Ports in the order of the module header, which may be left empty
+    Ordered(Vec<Option<Expr>>),
+    Named(Vec<(String, Option<Expr>)>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Net(String),
+    Bit(String, u8),
+    Const(Number),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+    // Most significant part first
+    Concat(Vec<Expr>),
+    Replicate(u8, Box<Expr>),
+}
+
+pub fn parse(source: &str) -> Result<Vec<Module>, ImportError> {
+    let mut parser = Parser {
+        tokens: lex(source)?,
+        position: 0,
+    };
+    let mut modules = vec![];
+    while parser.peek().is_some() {
+        modules.push(parser.module()?);
+    }
+    Ok(modules)
+}
+
+// Tokens along with their line numbers, without whitespace or comments
+fn lex(source: &str) -> Result<Vec<(usize, Token)>, ImportError> {
+    fn take_while(chars: &mut Peekable<Chars>, matches: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(char) = chars.next_if(|char| matches(*char)) {
+            taken.push(char);
+        }
+        taken
+    }
+
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = source.chars().peekable();
+    while let Some(&char) = chars.peek() {
+        match char {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            char if char.is_whitespace() => {
+                chars.next();
+            }
+            '/' => {
+                chars.next();
+                match chars.next() {
+                    Some('/') => {
+                        take_while(&mut chars, |char| char != '\n');
+                    }
+                    Some('*') => {
+                        let mut previous = ' ';
+                        loop {
+                            let char = chars.next().ok_or(ImportError::UnexpectedEnd)?;
+                            if char == '\n' {
+                                line += 1;
+                            }
+                            if previous == '*' && char == '/' {
+                                break;
+                            }
+                            previous = char;
+                        }
+                    }
+                    _ => {
+                        return Err(ImportError::Syntax {
+                            line,
+                            found: "/".to_string(),
+                        })
+                    }
+                }
+            }
+            char if char.is_ascii_alphabetic() || char == '_' || char == '\\' => {
+                // Escaped identifiers run until whitespace
+                let name = if char == '\\' {
+                    chars.next();
+                    take_while(&mut chars, |char| !char.is_whitespace())
+                } else {
+                    take_while(&mut chars, |char| {
+                        char.is_ascii_alphanumeric() || char == '_' || char == '$'
+                    })
+                };
+                tokens.push((line, Token::Ident(name)));
+            }
+            char if char.is_ascii_digit() || char == '\'' => {
+                let number = number(&mut chars).ok_or_else(|| ImportError::Syntax {
+                    line,
+                    found: char.to_string(),
+                })?;
+                tokens.push((line, Token::Number(number)));
+            }
+            char => {
+                chars.next();
+                tokens.push((line, Token::Symbol(char)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A decimal number, or a based number such as `8'hff` or `'b1`
+fn number(chars: &mut Peekable<Chars>) -> Option<Number> {
+    let mut digits = String::new();
+    while let Some(char) = chars.next_if(|char| char.is_ascii_digit() || *char == '_') {
+        digits.push(char);
+    }
+    let digits = digits.replace('_', "");
+
+    if chars.next_if_eq(&'\'').is_none() {
+        let value = digits.parse().ok()?;
+        return Some(Number { width: None, value });
+    }
+
+    let width = if digits.is_empty() {
+        None
+    } else {
+        Some(
+            digits
+                .parse()
+                .ok()
+                .filter(|width| (1..=64).contains(width))?,
+        )
+    };
+    let radix = match chars.next()?.to_ascii_lowercase() {
+        'b' => 2,
+        'o' => 8,
+        'd' => 10,
+        'h' => 16,
+        _ => return None,
+    };
+    let mut digits = String::new();
+    while let Some(char) = chars.next_if(|char| char.is_ascii_alphanumeric() || *char == '_') {
+        if char != '_' {
+            digits.push(char);
+        }
+    }
+    let value = u64::from_str_radix(&digits, radix).ok()?;
+    Some(Number { width, value })
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(1, |(line, _)| *line)
+    }
+
+    fn unexpected<T>(&self) -> Result<T, ImportError> {
+        match self.peek() {
+            None => Err(ImportError::UnexpectedEnd),
+            Some(token) => Err(ImportError::Syntax {
+                line: self.line(),
+                found: match token {
+                    Token::Ident(name) => name.clone(),
+                    Token::Number(number) => number.value.to_string(),
+                    Token::Symbol(symbol) => symbol.to_string(),
+                },
+            }),
+        }
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let matches = self.is_symbol(symbol);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ImportError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ImportError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ImportError> {
+        match self.peek() {
+            Some(Token::Ident(name)) if name == keyword => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    fn small_number(&mut self) -> Result<u8, ImportError> {
+        match self.peek() {
+            Some(Token::Number(Number { value, .. })) if *value <= u8::MAX as u64 => {
+                let value = *value as u8;
+                self.position += 1;
+                Ok(value)
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    // Items separated by commas, up to but not including `end`
+    fn list<T>(
+        &mut self,
+        end: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, ImportError>,
+    ) -> Result<Vec<T>, ImportError> {
+        let mut items = vec![];
+        if self.is_symbol(end) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if !self.eat(',') {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn module(&mut self) -> Result<Module, ImportError> {
+        let line = self.line();
+        self.keyword("module")?;
+        let name = self.ident()?;
+        let mut module = Module {
+            name,
+            line,
+            ports: vec![],
+            declarations: vec![],
+            statements: vec![],
+        };
+        if self.eat('(') {
+            // Ports are either just named, or declared in the header with their direction and width
+            let mut declaration: Option<Declaration> = None;
+            module.ports = self.list(')', |parser| {
+                let kind = match parser.peek() {
+                    Some(Token::Ident(keyword)) if keyword == "input" => Some(NetKind::Input),
+                    Some(Token::Ident(keyword)) if keyword == "output" => Some(NetKind::Output),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    parser.position += 1;
+                    parser.net_type();
+                    let width = parser.range()?;
+                    declaration = Some(Declaration {
+                        kind,
+                        width,
+                        names: vec![],
+                    });
+                }
+
+                let port = parser.ident()?;
+                if let Some(declaration) = &declaration {
+                    module.declarations.push(Declaration {
+                        names: vec![port.clone()],
+                        ..declaration.clone()
+                    });
+                }
+                Ok(port)
+            })?;
+            self.expect(')')?;
+        }
+        self.expect(';')?;
+
+        loop {
+            let line = self.line();
+            let keyword = self.ident()?;
+            let kind = match keyword.as_str() {
+                "endmodule" => return Ok(module),
+                "input" => Some(NetKind::Input),
+                "output" => Some(NetKind::Output),
+                // Regs are only assigned by `always` blocks, which aren't supported
+                "wire" | "reg" => Some(NetKind::Wire),
+                _ => None,
+            };
+
+            if let Some(kind) = kind {
+                if kind != NetKind::Wire {
+                    self.net_type();
+                }
+                let width = self.range()?;
+                // Nets may be assigned where they're declared
+                let names = self.list(';', |parser| {
+                    let name = parser.ident()?;
+                    let expr = if parser.eat('=') {
+                        Some(parser.expr()?)
+                    } else {
+                        None
+                    };
+                    Ok((name, expr))
+                })?;
+                for (name, expr) in &names {
+                    if let Some(expr) = expr {
+                        module.statements.push(Statement {
+                            line,
+                            item: Item::Assign {
+                                net: name.clone(),
+                                expr: expr.clone(),
+                            },
+                        });
+                    }
+                }
+                module.declarations.push(Declaration {
+                    kind,
+                    width,
+                    names: names.into_iter().map(|(name, _)| name).collect(),
+                });
+            } else if keyword == "assign" {
+                let assigns = self.list(';', |parser| {
+                    let net = parser.ident()?;
+                    parser.expect('=')?;
+                    Ok(Item::Assign {
+                        net,
+                        expr: parser.expr()?,
+                    })
+                })?;
+                module
+                    .statements
+                    .extend(assigns.into_iter().map(|item| Statement { line, item }));
+            } else if PRIMITIVES.contains(&keyword.as_str()) {
+                // The instance name is optional
+                if !self.is_symbol('(') {
+                    self.ident()?;
+                }
+                self.expect('(')?;
+                let output = self.ident()?;
+                self.expect(',')?;
+                let inputs = self.list(')', Self::expr)?;
+                self.expect(')')?;
+                module.statements.push(Statement {
+                    line,
+                    item: Item::Primitive {
+                        gate: keyword,
+                        output,
+                        inputs,
+                    },
+                });
+            } else if !KEYWORDS.contains(&keyword.as_str())
+                && matches!(self.peek(), Some(Token::Ident(_)))
+            {
+                self.ident()?;
+                self.expect('(')?;
+                let ports = if self.is_symbol('.') {
+                    Ports::Named(self.list(')', |parser| {
+                        parser.expect('.')?;
+                        let port = parser.ident()?;
+                        parser.expect('(')?;
+                        let expr = if parser.is_symbol(')') {
+                            None
+                        } else {
+                            Some(parser.expr()?)
+                        };
+                        parser.expect(')')?;
+                        Ok((port, expr))
+                    })?)
+                } else {
+                    Ports::Ordered(self.list(')', |parser| {
+                        if parser.is_symbol(',') || parser.is_symbol(')') {
+                            Ok(None)
+                        } else {
+                            parser.expr().map(Some)
+                        }
+                    })?)
+                };
+                self.expect(')')?;
+                module.statements.push(Statement {
+                    line,
+                    item: Item::Instance {
+                        module: keyword,
+                        ports,
+                    },
+                });
+            } else {
+                return Err(ImportError::Unsupported {
+                    line,
+                    construct: keyword,
+                });
+            }
+            self.expect(';')?;
+        }
+    }
+
+    // Skips the optional `wire` or `reg` after a port direction
+    fn net_type(&mut self) {
+        if matches!(self.peek(), Some(Token::Ident(keyword)) if keyword == "wire" || keyword == "reg")
+        {
+            self.position += 1;
+        }
+    }
+
+    // The width of an optional `[msb:0]` range
+    fn range(&mut self) -> Result<u8, ImportError> {
+        if !self.eat('[') {
+            return Ok(1);
+        }
+        let line = self.line();
+        let msb = self.small_number()?;
+        self.expect(':')?;
+        let lsb = self.small_number()?;
+        self.expect(']')?;
+        if lsb != 0 || msb >= 64 {
+            return Err(ImportError::Unsupported {
+                line,
+                construct: format!("[{msb}:{lsb}]"),
+            });
+        }
+        Ok(msb + 1)
+    }
+
+    // Operators from tightest to loosest binding are `~`, `&`, `^` and `~^`, and `|`
+    fn expr(&mut self) -> Result<Expr, ImportError> {
+        self.chain('|', Self::xor, Expr::Or)
+    }
+
+    // `~^` is an inverted `^`, and `^~` parses as a `^` of an inverted operand
+    fn xor(&mut self) -> Result<Expr, ImportError> {
+        let mut terms = vec![self.and()?];
+        let mut inverted = false;
+        loop {
+            if self.eat('^') {
+                terms.push(self.and()?);
+            } else if self.is_symbol('~')
+                && self.tokens.get(self.position + 1).map(|(_, token)| token)
+                    == Some(&Token::Symbol('^'))
+            {
+                self.position += 2;
+                inverted = !inverted;
+                terms.push(self.and()?);
+            } else {
+                break;
+            }
+        }
+
+        let expr = if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Expr::Xor(terms)
+        };
+        Ok(if inverted {
+            Expr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, ImportError> {
+        self.chain('&', Self::unary, Expr::And)
+    }
+
+    fn chain(
+        &mut self,
+        operator: char,
+        operand: fn(&mut Self) -> Result<Expr, ImportError>,
+        node: fn(Vec<Expr>) -> Expr,
+    ) -> Result<Expr, ImportError> {
+        let mut terms = vec![operand(self)?];
+        while self.eat(operator) {
+            terms.push(operand(self)?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            node(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, ImportError> {
+        if self.eat('~') {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat('(') {
+            let inner = self.expr()?;
+            self.expect(')')?;
+            return Ok(inner);
+        }
+        if self.eat('{') {
+            let first = self.expr()?;
+            // A number followed by a nested concatenation is a replication
+            if let (Expr::Const(Number { value, .. }), true) = (&first, self.is_symbol('{')) {
+                let count = u8::try_from(*value).or_else(|_| self.unexpected())?;
+                let inner = self.unary()?;
+                self.expect('}')?;
+                return Ok(Expr::Replicate(count, Box::new(inner)));
+            }
+            let mut parts = vec![first];
+            while self.eat(',') {
+                parts.push(self.expr()?);
+            }
+            self.expect('}')?;
+            return Ok(Expr::Concat(parts));
+        }
+
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                let number = *number;
+                self.position += 1;
+                Ok(Expr::Const(number))
+            }
+            Some(Token::Ident(_)) => {
+                let name = self.ident()?;
+                if self.eat('[') {
+                    let bit = self.small_number()?;
+                    self.expect(']')?;
+                    Ok(Expr::Bit(name, bit))
+                } else {
+                    Ok(Expr::Net(name))
+                }
+            }
+            _ => self.unexpected(),
+        }
+    }
+}
+
+pub const PRIMITIVES: &[&str] = &["and", "or", "xor", "nand", "nor", "xnor", "not", "buf"];
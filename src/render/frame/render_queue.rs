@@ -3,11 +3,14 @@ mod handles;
 use common::{bounds::Bounds, handle::Handle};
 use lyon::tessellation::VertexBuffers;
 
-use glam::Vec4;
+use glam::{Vec2, Vec4};
 use lyon::{
     geom::{point, Box2D},
     path::Path,
-    tessellation::{BuffersBuilder, StrokeOptions, StrokeTessellator, StrokeVertex},
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex,
+    },
 };
 
 #[cfg(feature = "rayon")]
@@ -152,4 +155,50 @@ impl RenderQueue {
             )
             .unwrap();
     }
+
+    pub fn fill_bounds(&mut self, bounds: Bounds, color: Vec4) {
+        let box_2d = Box2D::from_points([
+            point(bounds.top_left.x, bounds.top_left.y),
+            point(bounds.bottom_right.x, bounds.bottom_right.y),
+        ]);
+
+        FillTessellator::new()
+            .tessellate_rectangle(
+                &box_2d,
+                &FillOptions::default(),
+                &mut BuffersBuilder::new(&mut self.lines, |vertex: FillVertex| {
+                    VertexUV::new(vertex.position().x, vertex.position().y, 0.0, 0.0, color)
+                }),
+            )
+            .unwrap();
+    }
+
+    // Straight segments joining each point to the next
+    pub fn draw_polyline(&mut self, points: &[Vec2], width: f32, color: Vec4) {
+        let Some((first, rest)) = points.split_first() else {
+            return;
+        };
+
+        let mut path = Path::builder();
+        path.begin(point(first.x, first.y));
+        for next in rest {
+            path.line_to(point(next.x, next.y));
+        }
+        path.end(false);
+        let path = path.build();
+
+        let options = StrokeOptions::default()
+            .with_line_width(width)
+            .with_tolerance(0.0001);
+
+        StrokeTessellator::new()
+            .tessellate_path(
+                &path,
+                &options,
+                &mut BuffersBuilder::new(&mut self.lines, |vertex: StrokeVertex| {
+                    VertexUV::new(vertex.position().x, vertex.position().y, 0.0, 0.0, color)
+                }),
+            )
+            .unwrap();
+    }
 }